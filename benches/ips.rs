@@ -0,0 +1,85 @@
+//!
+//! Instructions per second
+//!
+//! Runs the same program on cpus connected to different kinds
+//! of buses and reports how many instructions each of them
+//! executes per second.
+//!
+//! Run with `cargo bench`.
+//!
+
+use m6502::memory_map::MemoryMap;
+use m6502::mos6502::{Bus, Byte, Cpu, MainBus};
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::rc::Rc;
+use std::time::Instant;
+
+const INSTRUCTIONS: u32 = 2_000_000;
+
+/// The best of the rounds is reported
+const ROUNDS: u32 = 5;
+
+/// Loads at 0x0200
+///
+/// ```ignore
+/// start:  ldx #0
+/// loop:   txa
+///         sta $0300,x
+///         adc $0300,x
+///         eor #$5A
+///         inc $10
+///         dex
+///         bne loop
+///         jsr sub
+///         jmp start
+/// sub:    pha
+///         pla
+///         rts
+/// ```
+const PROGRAM: [Byte; 25] = [
+    0xA2, 0x00, 0x8A, 0x9D, 0x00, 0x03, 0x7D, 0x00, 0x03, 0x49, 0x5A, 0xE6, 0x10, 0xCA, 0xD0, 0xF2,
+    0x20, 0x16, 0x02, 0x4C, 0x00, 0x02, 0x48, 0x68, 0x60,
+];
+
+fn run<B: Bus>(name: &str, mut cpu: Cpu<B>) {
+    cpu.load_program(&PROGRAM, 0x0200, PROGRAM.len(), true)
+        .unwrap();
+
+    let mut best = f64::MAX;
+    let mut cycles = 0;
+    for _ in 0..ROUNDS {
+        let elapsed_before = cpu.time().elapsed();
+        let begin = Instant::now();
+        for _ in 0..INSTRUCTIONS {
+            black_box(cpu.full_instruction()).unwrap();
+        }
+        let elapsed = begin.elapsed().as_secs_f64();
+        if elapsed < best {
+            best = elapsed;
+            cycles = cpu.time().elapsed() - elapsed_before;
+        }
+    }
+
+    println!(
+        "{:<12} {:>12.0} instructions/s {:>8.2} MHz",
+        name,
+        f64::from(INSTRUCTIONS) / best,
+        cycles as f64 / best / 1e6
+    );
+}
+
+fn main() {
+    let mut shared = Cpu::new();
+    shared.connect_to(Rc::new(RefCell::new(MainBus::new())));
+    run("shared", shared);
+
+    run("owned", Cpu::with_bus(MainBus::new()));
+
+    let map = MemoryMap::builder()
+        .ram(0x0000..=0x1FFF)
+        .mask(0x07FF)
+        .build()
+        .unwrap();
+    run("memory map", Cpu::with_bus(map));
+}
@@ -0,0 +1,40 @@
+extern crate m6502;
+
+use m6502::apple1::Apple1;
+use std::process::Command;
+
+//
+// Apple I
+//
+// Usage: apple1 [FILE...]
+//
+// Each file is pasted into the Woz Monitor before the machine starts,
+// e.g. a hex dump like "0300: A9 C1 20 EF FF". Press Ctrl-D to quit.
+//
+
+fn stty(args: &[&str]) {
+    // Best effort, the terminal may just be line buffered
+    let _ = Command::new("stty").args(args).status();
+}
+
+fn main() {
+    let mut apple = Apple1::new(8);
+    for file in std::env::args().skip(1) {
+        match std::fs::read_to_string(&file) {
+            Ok(text) => apple.paste(&text),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    stty(&["-icanon", "-echo"]);
+    let result = apple.run_terminal();
+    stty(&["sane"]);
+
+    if let Err(err) = result {
+        eprintln!("\nThe cpu stopped: {:?}", err);
+        std::process::exit(1);
+    }
+}
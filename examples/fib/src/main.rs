@@ -2,7 +2,7 @@ extern crate m6502;
 extern crate olc_pixel_game_engine;
 
 use crate::olc_pixel_game_engine as olc;
-use m6502::mos6502::{Address, Asm, Cpu, CpuError};
+use m6502::mos6502::{Address, Asm, Cpu, CpuError, RESET_VECTOR};
 
 enum FibonaciiExampleView {
     CpuMonitor,
@@ -39,6 +39,9 @@ impl olc::Application for FibonacciExample {
             self.preassembled_program.len(),
             true,
         );
+        // Resetting the cpu starts the program over
+        self.cpu.writ_byte(RESET_VECTOR, 0x00);
+        self.cpu.writ_byte(RESET_VECTOR + 1, 0x80);
 
         self.disassembly = Asm::from_addr_range(&mut self.cpu, 0x8000, 32);
         let regs = self.cpu.regset_mut();
@@ -63,7 +66,10 @@ impl olc::Application for FibonacciExample {
         // Next instruction
         if !self.finished {
             if self.continuing || olc::get_key(olc::Key::SPACE).released {
-                self.cpu.full_instruction();
+                // Stop running on its own if the cpu has trapped
+                if self.cpu.full_instruction().is_err() {
+                    self.continuing = false;
+                }
             }
 
             // Continue execution
@@ -75,6 +81,10 @@ impl olc::Application for FibonacciExample {
         // Reset cpu
         if olc::get_key(olc::Key::R).released {
             self.cpu.reset();
+            // The reset sequence loads the program counter from the reset vector
+            self.cpu
+                .full_instruction()
+                .expect("The reset sequence only reads memory");
         }
 
         // Watch cpu
@@ -2,7 +2,7 @@ extern crate m6502;
 extern crate olc_pixel_game_engine;
 
 use crate::olc_pixel_game_engine as olc;
-use m6502::mos6502::{Address, Asm, Cpu, CpuError};
+use m6502::mos6502::{Address, Asm, Cpu, CpuError, RESET_VECTOR};
 
 enum WindowView {
     CpuMonitor,
@@ -42,6 +42,10 @@ impl olc::Application for GCDExample {
         {
             panic!("Failed opening source file.");
         }
+        // Resetting the cpu starts the program over
+        let [low, high] = self.initial_pc.to_le_bytes();
+        self.cpu.writ_byte(RESET_VECTOR, low);
+        self.cpu.writ_byte(RESET_VECTOR + 1, high);
 
         self.disassembly = Asm::from_addr_range(&mut self.cpu, self.initial_pc, 45);
         let regs = self.cpu.regset_mut();
@@ -66,7 +70,10 @@ impl olc::Application for GCDExample {
         // Next instruction
         if !self.finished {
             if self.continuing || olc::get_key(olc::Key::SPACE).released {
-                self.cpu.full_instruction();
+                // Stop running on its own if the cpu has trapped
+                if self.cpu.full_instruction().is_err() {
+                    self.continuing = false;
+                }
                 self.started = true;
             }
 
@@ -79,9 +86,12 @@ impl olc::Application for GCDExample {
         // Reset cpu
         if olc::get_key(olc::Key::R).released {
             self.cpu.reset();
+            // The reset sequence loads the program counter from the reset vector
+            self.cpu
+                .full_instruction()
+                .expect("The reset sequence only reads memory");
             self.continuing = false;
             self.started = false;
-            self.cpu.regset_mut().set_prog_counter(self.initial_pc);
         }
 
         // Watch cpu
@@ -0,0 +1,342 @@
+use crate::mc6821::Pia;
+use crate::memory_map::{Device, MemoryMap};
+use crate::mos6502::{Address, Byte, Cpu, CpuError, ResetKind};
+use crate::mos6551::{SerialBackend, Stdio};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+//
+// Apple I
+//
+// The smallest complete machine: a 6502, 4 to 8 KiB of RAM from 0x0000,
+// a PIA at 0xD010-0xD013 and the 256 byte Woz Monitor at 0xFF00.
+//
+// The keyboard is on port A of the PIA, strobed by CA1. The display
+// is on port B, where PB7 tells whether it is busy. Both deal in ASCII
+// with bit 7 set. The display has 40 columns and 24 rows of uppercase
+// characters only, with a blinking `@` for a cursor, and scrolls up
+// when a line is added at its bottom.
+//
+
+/// The clock of the cpu, 14.31818 MHz divided by 14
+pub const CLOCK_HZ: u64 = 1_022_727;
+
+pub const COLUMNS: usize = 40;
+pub const ROWS: usize = 24;
+
+pub const KBD: Address = 0xD010;
+pub const KBDCR: Address = 0xD011;
+pub const DSP: Address = 0xD012;
+pub const DSPCR: Address = 0xD013;
+
+/// Where the monitor keeps the line being typed
+pub const INPUT_BUFFER: Address = 0x0200;
+
+const CARRIAGE_RETURN: Byte = 0x0D;
+/// The Woz Monitor erases with an underscore
+const RUBOUT: Byte = b'_';
+/// Ctrl-D, which ends `run_terminal()`
+const END_OF_TRANSMISSION: Byte = 0x04;
+/// How many bytes `paste_hex_dump()` puts on a line
+const DUMP_LINE: usize = 8;
+
+/// The Woz Monitor, by Steve Wozniak, 1976
+pub const WOZ_MONITOR: [Byte; 256] = [
+    0xD8, 0x58, 0xA0, 0x7F, 0x8C, 0x12, 0xD0, 0xA9, 0xA7, 0x8D, 0x11, 0xD0, 0x8D, 0x13, 0xD0, 0xC9,
+    0xDF, 0xF0, 0x13, 0xC9, 0x9B, 0xF0, 0x03, 0xC8, 0x10, 0x0F, 0xA9, 0xDC, 0x20, 0xEF, 0xFF, 0xA9,
+    0x8D, 0x20, 0xEF, 0xFF, 0xA0, 0x01, 0x88, 0x30, 0xF6, 0xAD, 0x11, 0xD0, 0x10, 0xFB, 0xAD, 0x10,
+    0xD0, 0x99, 0x00, 0x02, 0x20, 0xEF, 0xFF, 0xC9, 0x8D, 0xD0, 0xD4, 0xA0, 0xFF, 0xA9, 0x00, 0xAA,
+    0x0A, 0x85, 0x2B, 0xC8, 0xB9, 0x00, 0x02, 0xC9, 0x8D, 0xF0, 0xD4, 0xC9, 0xAE, 0x90, 0xF4, 0xF0,
+    0xF0, 0xC9, 0xBA, 0xF0, 0xEB, 0xC9, 0xD2, 0xF0, 0x3B, 0x86, 0x28, 0x86, 0x29, 0x84, 0x2A, 0xB9,
+    0x00, 0x02, 0x49, 0xB0, 0xC9, 0x0A, 0x90, 0x06, 0x69, 0x88, 0xC9, 0xFA, 0x90, 0x11, 0x0A, 0x0A,
+    0x0A, 0x0A, 0xA2, 0x04, 0x0A, 0x26, 0x28, 0x26, 0x29, 0xCA, 0xD0, 0xF8, 0xC8, 0xD0, 0xE0, 0xC4,
+    0x2A, 0xF0, 0x97, 0x24, 0x2B, 0x50, 0x10, 0xA5, 0x28, 0x81, 0x26, 0xE6, 0x26, 0xD0, 0xB5, 0xE6,
+    0x27, 0x4C, 0x44, 0xFF, 0x6C, 0x24, 0x00, 0x30, 0x2B, 0xA2, 0x02, 0xB5, 0x27, 0x95, 0x25, 0x95,
+    0x23, 0xCA, 0xD0, 0xF7, 0xD0, 0x14, 0xA9, 0x8D, 0x20, 0xEF, 0xFF, 0xA5, 0x25, 0x20, 0xDC, 0xFF,
+    0xA5, 0x24, 0x20, 0xDC, 0xFF, 0xA9, 0xBA, 0x20, 0xEF, 0xFF, 0xA9, 0xA0, 0x20, 0xEF, 0xFF, 0xA1,
+    0x24, 0x20, 0xDC, 0xFF, 0x86, 0x2B, 0xA5, 0x24, 0xC5, 0x28, 0xA5, 0x25, 0xE5, 0x29, 0xB0, 0xC1,
+    0xE6, 0x24, 0xD0, 0x02, 0xE6, 0x25, 0xA5, 0x24, 0x29, 0x07, 0x10, 0xC8, 0x48, 0x4A, 0x4A, 0x4A,
+    0x4A, 0x20, 0xE5, 0xFF, 0x68, 0x29, 0x0F, 0x09, 0xB0, 0xC9, 0xBA, 0x90, 0x02, 0x69, 0x06, 0x2C,
+    0x12, 0xD0, 0x30, 0xFB, 0x8D, 0x12, 0xD0, 0x60, 0x00, 0x00, 0x00, 0x0F, 0x00, 0xFF, 0x00, 0x00,
+];
+
+///
+/// Display
+///
+/// The terminal section of the Apple I. It keeps what is on the screen
+/// and renders it for a host terminal, on which the cursor is an `@`
+/// followed by a backspace.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    lines: VecDeque<Vec<Byte>>,
+    rendered: Vec<Byte>,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        let mut lines = VecDeque::with_capacity(ROWS);
+        lines.push_back(Vec::with_capacity(COLUMNS));
+        Self {
+            lines,
+            rendered: b"@\x08".to_vec(),
+        }
+    }
+
+    /// **put()** - Shows a character. Bit 7 is ignored, lowercase is shown
+    /// as uppercase and control characters other than CR do nothing.
+    pub fn put(&mut self, data: Byte) {
+        let data = data & 0x7F;
+        match data {
+            CARRIAGE_RETURN => self.new_line(),
+            0x20..=0x5F => self.print(data),
+            0x60..=0x7E => self.print(data & 0x5F),
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, data: Byte) {
+        self.rendered.extend(&[data, b'@', 0x08]);
+        let line = self.lines.back_mut().expect("a display has a line");
+        line.push(data);
+        if line.len() == COLUMNS {
+            self.new_line();
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.rendered.extend(b" \x08\r\n@\x08");
+        if self.lines.len() == ROWS {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(Vec::with_capacity(COLUMNS));
+    }
+
+    /// **cursor()** - The row and the column of the cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        let line = self.lines.back().expect("a display has a line");
+        (self.lines.len() - 1, line.len())
+    }
+
+    /// **lines()** - The rows of the screen, from the top, up to the cursor
+    pub fn lines(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect()
+    }
+
+    /// **text()** - The rows of the screen, joined by newlines
+    pub fn text(&self) -> String {
+        self.lines().join("\n")
+    }
+
+    /// **take_rendered()** - What is to be written to a host terminal since
+    /// the last call
+    pub fn take_rendered(&mut self) -> Vec<Byte> {
+        std::mem::take(&mut self.rendered)
+    }
+}
+
+/// The PIA as the Apple I has it, with its IRQ outputs left unconnected
+struct Unwired(Arc<Mutex<Pia>>);
+
+impl Device for Unwired {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        self.0.lock().unwrap().read(offset)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        self.0.lock().unwrap().write(offset, data)
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        self.0.lock().unwrap().peek(offset)
+    }
+
+    fn tick(&mut self) {
+        self.0.lock().unwrap().tick()
+    }
+}
+
+///
+/// Apple1
+///
+/// An Apple I, reset and ready to run the Woz Monitor.
+///
+pub struct Apple1 {
+    cpu: Cpu<MemoryMap>,
+    pia: Arc<Mutex<Pia>>,
+    display: Display,
+}
+
+impl Apple1 {
+    /// **new()** - An Apple I with `ram_kib` KiB of RAM.
+    /// Panics unless it has 4 to 8 KiB.
+    pub fn new(ram_kib: usize) -> Self {
+        assert!(
+            (4..=8).contains(&ram_kib),
+            "an Apple I has 4 to 8 KiB of RAM"
+        );
+
+        let pia = Arc::new(Mutex::new(Pia::new()));
+        // PB7 is low while the display is ready, which it always is
+        pia.lock().unwrap().set_port_b(0x00);
+
+        let ram_end = (ram_kib * 1024 - 1) as Address;
+        let map = MemoryMap::builder()
+            .ram(0x0000..=ram_end)
+            .device(KBD..=DSPCR, Arc::new(Mutex::new(Unwired(pia.clone()))))
+            .rom(0xFF00..=0xFFFF, &WOZ_MONITOR)
+            .build()
+            .expect("the Apple I memory map is valid");
+
+        let mut apple = Self {
+            cpu: Cpu::with_bus(map),
+            pia,
+            display: Display::new(),
+        };
+        apple.restart(ResetKind::PowerOn);
+        apple
+    }
+
+    /// **reset()** - Presses the RESET key, which restarts the monitor.
+    /// The screen is left alone, like on the real thing.
+    pub fn reset(&mut self) {
+        self.restart(ResetKind::Warm);
+    }
+
+    fn restart(&mut self, kind: ResetKind) {
+        self.pia.lock().unwrap().reset();
+        self.cpu.reset_with(kind);
+        self.cpu
+            .full_instruction()
+            .expect("the reset sequence needs no program");
+    }
+
+    pub fn cpu(&self) -> &Cpu<MemoryMap> {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu<MemoryMap> {
+        &mut self.cpu
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    /// **pending_keys()** - The keystrokes the monitor has not read yet
+    pub fn pending_keys(&self) -> usize {
+        self.pia.lock().unwrap().pending_keys()
+    }
+
+    /// **key()** - Types a key on the keyboard, which only has uppercase
+    /// letters. Newlines are typed as CR and backspace (or delete) as
+    /// the underscore the monitor erases with.
+    pub fn key(&mut self, key: Byte) {
+        let key = match key {
+            b'\n' => CARRIAGE_RETURN,
+            0x08 | 0x7F => RUBOUT,
+            _ => key.to_ascii_uppercase(),
+        };
+        self.pia.lock().unwrap().push_keys(&[key | 0x80]);
+    }
+
+    /// **paste()** - Types text, line by line
+    pub fn paste(&mut self, text: &str) {
+        for line in text.lines() {
+            for &key in line.as_bytes() {
+                self.key(key);
+            }
+            self.key(CARRIAGE_RETURN);
+        }
+    }
+
+    /// **paste_hex_dump()** - Types the monitor commands which store
+    /// `data` from `address` on, as in `0300: A9 00 AA`
+    pub fn paste_hex_dump(&mut self, address: Address, data: &[Byte]) {
+        let mut dump = String::new();
+        for (index, chunk) in data.chunks(DUMP_LINE).enumerate() {
+            let start = address.wrapping_add((index * DUMP_LINE) as Address);
+            dump += &format!("{:04X}:", start);
+            for byte in chunk {
+                dump += &format!(" {:02X}", byte);
+            }
+            dump.push('\n');
+        }
+        self.paste(&dump);
+    }
+
+    /// **step()** - Executes an instruction and shows what it printed
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        self.cpu.full_instruction()?;
+        let output = self.pia.lock().unwrap().take_output();
+        for data in output {
+            self.display.put(data);
+        }
+        Ok(())
+    }
+
+    /// **run_for()** - Executes instructions for (at least) `cycles` cycles
+    pub fn run_for(&mut self, cycles: u64) -> Result<(), CpuError> {
+        let end = self.cpu.time().elapsed() + cycles;
+        while self.cpu.time().elapsed() < end {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// **run_terminal()** - Runs the machine in real time, with the
+    /// keyboard on stdin and the display on stdout. Returns once Ctrl-D
+    /// (EOT) is typed or stdin ends, and the keys before are read, or if
+    /// the cpu fails. The keys typed after Ctrl-D are left for the next
+    /// call. Stdin is best put into non-canonical mode without echo
+    /// beforehand (`stty -icanon -echo`).
+    pub fn run_terminal(&mut self) -> Result<(), CpuError> {
+        const SLICE: Duration = Duration::from_millis(10);
+        let cycles = CLOCK_HZ / 100;
+
+        // Every `Stdio` reads from the same stdin thread
+        let mut keyboard = Stdio::new();
+        let mut stdout = io::stdout();
+        let mut quitting = false;
+        loop {
+            let start = Instant::now();
+            while !quitting {
+                match keyboard.receive() {
+                    Some(END_OF_TRANSMISSION) => quitting = true,
+                    Some(key) => self.key(key),
+                    None => {
+                        quitting = keyboard.ended();
+                        break;
+                    }
+                }
+            }
+            self.run_for(cycles)?;
+
+            // A closed stdout is a display nobody looks at
+            let _ = stdout
+                .write_all(&self.display.take_rendered())
+                .and_then(|_| stdout.flush());
+            if quitting && self.pending_keys() == 0 {
+                return Ok(());
+            }
+            if let Some(rest) = SLICE.checked_sub(start.elapsed()) {
+                std::thread::sleep(rest);
+            }
+        }
+    }
+}
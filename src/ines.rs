@@ -0,0 +1,242 @@
+use crate::mappers::{CnRom, Mirroring, Mmc1, UxRom};
+use crate::memory_map::{MemoryMap, MemoryMapBuilder, MemoryMapError};
+use crate::mos6502::{Address, Bus, Byte, Cpu, CpuError, CpuVariant, ResetKind};
+use std::sync::{Arc, Mutex};
+
+//
+// iNES
+//
+// The de facto format of NES cartridge dumps, described
+// [here](https://www.nesdev.org/wiki/INES) and, for its NES 2.0
+// extension, [here](https://www.nesdev.org/wiki/NES_2.0).
+//
+// A 16 byte header is followed by an optional 512 byte trainer,
+// the PRG ROM and the CHR ROM. The header tells their sizes, the
+// mapper of the cartridge and how its nametables are mirrored.
+//
+
+const MAGIC: [Byte; 4] = [b'N', b'E', b'S', 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_UNIT: usize = 16 * 1024;
+const CHR_UNIT: usize = 8 * 1024;
+const PRG_RAM_UNIT: usize = 8 * 1024;
+
+/// Where the trainer is loaded, inside of the PRG RAM
+const TRAINER_ADDRESS: Address = 0x7000;
+
+///
+/// INesError
+/// \
+/// **Truncated** - The file is shorter than its header says.\
+/// **BadMagic** - The file does not begin with `NES<EOF>`.\
+/// **NoPrgRom** - The header declares no PRG ROM.\
+/// **SizeOverflow** - A NES 2.0 size is too large to be real.\
+/// **UnsupportedMapper** - There is no mapper with this number in the crate.\
+/// **BadPrgSize** - The size of the PRG ROM does not suit the mapper.\
+/// **MemoryMap** - The cartridge could not be mounted into the memory map.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum INesError {
+    Truncated { expected: usize, found: usize },
+    BadMagic,
+    NoPrgRom,
+    SizeOverflow,
+    UnsupportedMapper(u16),
+    BadPrgSize(usize),
+    MemoryMap(MemoryMapError),
+}
+
+///
+/// INes
+///
+/// A parsed iNES or NES 2.0 image.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct INes {
+    /// Whether the header is a NES 2.0 one
+    pub nes2: bool,
+    pub mapper: u16,
+    /// Always 0 unless the header is a NES 2.0 one
+    pub submapper: Byte,
+    pub mirroring: Mirroring,
+    /// Whether the PRG RAM is kept by a battery
+    pub battery: bool,
+    pub trainer: Option<Vec<Byte>>,
+    pub prg: Vec<Byte>,
+    /// Empty if the cartridge has CHR RAM instead
+    pub chr: Vec<Byte>,
+    /// The size of the PRG RAM (and NVRAM) at 0x6000
+    pub prg_ram_size: usize,
+}
+
+fn error(reason: INesError) -> CpuError {
+    CpuError::BadRom(reason)
+}
+
+/// **rom_size()** - The size of a ROM in a NES 2.0 header. When the
+/// upper nibble is 0xF, the lower byte is an exponent and a multiplier.
+fn rom_size(lsb: Byte, msb: Byte, unit: usize) -> Option<usize> {
+    if msb == 0x0F {
+        let multiplier = usize::from(lsb & 0x03) * 2 + 1;
+        1usize
+            .checked_shl(u32::from(lsb >> 2))?
+            .checked_mul(multiplier)
+    } else {
+        Some((usize::from(msb) << 8 | usize::from(lsb)) * unit)
+    }
+}
+
+/// **ram_size()** - The size of a RAM in a NES 2.0 header, given as a shift count
+fn ram_size(shift: Byte) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+impl INes {
+    /// **parse()** - Parses an iNES or NES 2.0 image. Old iNES headers with
+    /// garbage in their last bytes (e.g. "DiskDude!") get the upper
+    /// nibble of their mapper number ignored, as most emulators do.
+    pub fn parse(bytes: &[Byte]) -> Result<Self, CpuError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(error(INesError::Truncated {
+                expected: HEADER_SIZE,
+                found: bytes.len(),
+            }));
+        }
+        let header = &bytes[..HEADER_SIZE];
+        if header[..4] != MAGIC {
+            return Err(error(INesError::BadMagic));
+        }
+
+        let [flags6, flags7] = [header[6], header[7]];
+        let nes2 = flags7 & 0x0C == 0x08;
+        let mut mapper = u16::from(flags6 >> 4);
+        let mut submapper = 0;
+
+        let (prg_size, chr_size, prg_ram_size) = if nes2 {
+            mapper |= u16::from(flags7 & 0xF0) | u16::from(header[8] & 0x0F) << 8;
+            submapper = header[8] >> 4;
+            let prg = rom_size(header[4], header[9] & 0x0F, PRG_UNIT);
+            let chr = rom_size(header[5], header[9] >> 4, CHR_UNIT);
+            let ram = ram_size(header[10] & 0x0F) + ram_size(header[10] >> 4);
+            match (prg, chr) {
+                (Some(prg), Some(chr)) => (prg, chr, ram),
+                _ => return Err(error(INesError::SizeOverflow)),
+            }
+        } else {
+            if header[12..].iter().all(|&b| b == 0) {
+                mapper |= u16::from(flags7 & 0xF0);
+            }
+            // A size of 0 means 8K, for compatibility
+            let ram = usize::from(header[8].max(1)) * PRG_RAM_UNIT;
+            (
+                usize::from(header[4]) * PRG_UNIT,
+                usize::from(header[5]) * CHR_UNIT,
+                ram,
+            )
+        };
+
+        if prg_size == 0 {
+            return Err(error(INesError::NoPrgRom));
+        }
+
+        let has_trainer = flags6 & 0x04 != 0;
+        let trainer_size = if has_trainer { TRAINER_SIZE } else { 0 };
+        let expected = (HEADER_SIZE + trainer_size)
+            .checked_add(prg_size)
+            .and_then(|size| size.checked_add(chr_size))
+            .ok_or_else(|| error(INesError::SizeOverflow))?;
+        if bytes.len() < expected {
+            return Err(error(INesError::Truncated {
+                expected,
+                found: bytes.len(),
+            }));
+        }
+
+        let prg_start = HEADER_SIZE + trainer_size;
+        let chr_start = prg_start + prg_size;
+        let mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        Ok(Self {
+            nes2,
+            mapper,
+            submapper,
+            mirroring,
+            battery: flags6 & 0x02 != 0,
+            trainer: has_trainer.then(|| bytes[HEADER_SIZE..prg_start].to_vec()),
+            prg: bytes[prg_start..chr_start].to_vec(),
+            chr: bytes[chr_start..expected].to_vec(),
+            prg_ram_size,
+        })
+    }
+
+    /// **from_file()** - Reads and parses an iNES or NES 2.0 file
+    pub fn from_file(filename: &str) -> Result<Self, CpuError> {
+        let bytes = std::fs::read(filename).map_err(|_| CpuError::FailedLoadingProgram)?;
+        Self::parse(&bytes)
+    }
+
+    /// **memory_map()** - Mounts the cartridge into a memory map, next to
+    /// whatever `builder` already has (e.g. the PPU and the APU):\
+    /// 0x0000-0x1FFF - the 2K of internal RAM, mirrored\
+    /// 0x6000-0x7FFF - the PRG RAM, if there is one, with the trainer loaded\
+    /// 0x8000-0xFFFF - the PRG ROM, through the mapper of the cartridge.
+    /// A 16K PRG ROM without a mapper is mirrored at 0xC000.
+    pub fn memory_map(&self, builder: MemoryMapBuilder) -> Result<MemoryMap, CpuError> {
+        let mut builder = builder.ram(0x0000..=0x1FFF).mask(0x07FF);
+        if self.prg_ram_size > 0 || self.trainer.is_some() {
+            builder = builder.ram(0x6000..=0x7FFF);
+        }
+
+        let prg = self.prg.clone();
+        let chr = self.chr.clone();
+        builder = match self.mapper {
+            0 => match prg.len() {
+                0x4000 => builder.rom(0x8000..=0xFFFF, &prg).mask(0x3FFF),
+                0x8000 => builder.rom(0x8000..=0xFFFF, &prg),
+                size => return Err(error(INesError::BadPrgSize(size))),
+            },
+            1 => builder.device(0x8000..=0xFFFF, Arc::new(Mutex::new(Mmc1::new(prg, chr)))),
+            2 => builder.device(
+                0x8000..=0xFFFF,
+                Arc::new(Mutex::new(UxRom::new(prg, chr, self.mirroring))),
+            ),
+            3 => builder.device(
+                0x8000..=0xFFFF,
+                Arc::new(Mutex::new(CnRom::new(prg, chr, self.mirroring))),
+            ),
+            mapper => return Err(error(INesError::UnsupportedMapper(mapper))),
+        };
+
+        let mut map = builder
+            .build()
+            .map_err(|reason| error(INesError::MemoryMap(reason)))?;
+        if let Some(trainer) = &self.trainer {
+            for (offset, &data) in trainer.iter().enumerate() {
+                map.write(TRAINER_ADDRESS + offset as Address, data);
+            }
+        }
+        Ok(map)
+    }
+
+    /// **cpu()** - A 2A03 connected to the cartridge alone, after it has
+    /// gone through the power-on reset, so that the program counter is
+    /// taken from the reset vector.
+    pub fn cpu(&self) -> Result<Cpu<MemoryMap>, CpuError> {
+        let map = self.memory_map(MemoryMap::builder())?;
+        let mut cpu = Cpu::with_bus_variant(CpuVariant::Ricoh2A03, map);
+        cpu.reset_with(ResetKind::PowerOn);
+        cpu.full_instruction()?;
+        Ok(cpu)
+    }
+}
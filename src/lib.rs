@@ -1,7 +1,16 @@
 extern crate getset;
 
+pub mod apple1;
+pub mod ines;
+pub mod loaders;
+pub mod mappers;
+pub mod mc6821;
+pub mod memory_map;
 pub mod mos6502;
-mod mos6502_addressing_modes;
 mod mos6502_instruction_set;
+mod mos6502_micro_ops;
+pub mod mos6522;
+pub mod mos6532;
+pub mod mos6551;
 
 mod test;
@@ -0,0 +1,727 @@
+use crate::mos6502::{Address, Byte, CpuError, Word};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+//
+// Loaders
+//
+// Toolchains rarely emit raw binaries. Their output tells where each piece
+// of the program belongs and, often, where the execution should begin. The
+// loaders here parse such files into an `Image` -- a list of segments and an
+// optional start address -- which `Cpu::load_image()` puts into memory.
+//
+// Errors in text formats are reported as `CpuError::BadRecord` with the
+// number of the offending line, counting from 1. Errors in binary formats
+// are reported as `CpuError::BadObject` with the offset of the offending
+// byte in the file.
+//
+
+///
+/// Segment
+///
+/// A contiguous piece of an image.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub address: Address,
+    pub data: Vec<Byte>,
+}
+
+///
+/// Image
+///
+/// A program as a set of segments, which do not have to be contiguous,
+/// and the address at which it should be started, if known.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    pub start: Option<Address>,
+}
+
+///
+/// RecordError
+/// \
+/// Why a record of a text image has been rejected.\
+/// \
+/// **MissingStartCode** - The line does not begin with `:` (Intel HEX) or `S` (S-record).\
+/// **BadDigit** - The record contains a character which is not a hexadecimal digit.\
+/// **Truncated** - The record is too short for its fixed fields, or ends in the middle of a byte.\
+/// **LengthMismatch** - The byte count of the record disagrees with its actual length.\
+/// **BadChecksum** - The checksum of the record is wrong.\
+/// **UnknownType** - The record type is not defined by the format.\
+/// **Malformed** - The contents of the record do not fit its type.\
+/// **AddressOutOfRange** - The record refers to memory beyond the 64 KiB address space.\
+/// **RecordCountMismatch** - An S5/S6 record disagrees with the number of data records.\
+/// **MissingEnd** - The image ends without an end of file (Intel HEX) or a termination
+/// (S-record) record.\
+/// **DataAfterEnd** - There are more records after the end one.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    MissingStartCode,
+    BadDigit,
+    Truncated,
+    LengthMismatch { expected: usize, found: usize },
+    BadChecksum { expected: Byte, found: Byte },
+    UnknownType(Byte),
+    Malformed,
+    AddressOutOfRange,
+    RecordCountMismatch { expected: u32, found: u32 },
+    MissingEnd,
+    DataAfterEnd,
+}
+
+use RecordError::*;
+
+/// The size of the address space of the cpu
+const ADDRESS_SPACE: u32 = 0x10000;
+
+impl Image {
+    /// **add()** - Adds data at an address. Data which directly
+    /// follows the last segment is appended to it. Returns false,
+    /// adding nothing, if the data does not fit in the address space.
+    fn add(&mut self, address: u32, data: &[Byte]) -> bool {
+        match address.checked_add(data.len() as u32) {
+            Some(end) if end <= ADDRESS_SPACE => {}
+            _ => return false,
+        }
+        if data.is_empty() {
+            return true;
+        }
+
+        let address = address as Address;
+        match self.segments.last_mut() {
+            Some(last)
+                if u32::from(last.address) + last.data.len() as u32 == u32::from(address) =>
+            {
+                last.data.extend_from_slice(data)
+            }
+            _ => self.segments.push(Segment {
+                address,
+                data: data.to_vec(),
+            }),
+        }
+        true
+    }
+
+    /// **from_ihex()** - Parses an Intel HEX image. Extended segment and
+    /// linear addresses are supported as long as the data stays in the
+    /// 64 KiB address space. Either kind of start address record sets
+    /// the start of the image.
+    pub fn from_ihex(text: &str) -> Result<Self, CpuError> {
+        let mut reader = Reader::default();
+        for (line, record) in records(text) {
+            reader
+                .ihex_record(record)
+                .map_err(|reason| CpuError::BadRecord { line, reason })?;
+        }
+        reader.finish(text)
+    }
+
+    /// **from_srec()** - Parses a Motorola S-record image. The header
+    /// record is skipped, the record count ones are checked and the
+    /// termination record sets the start of the image.
+    pub fn from_srec(text: &str) -> Result<Self, CpuError> {
+        let mut reader = Reader::default();
+        for (line, record) in records(text) {
+            reader
+                .srec_record(record)
+                .map_err(|reason| CpuError::BadRecord { line, reason })?;
+        }
+        reader.finish(text)
+    }
+}
+
+/// The non-empty lines of a text image and their numbers
+fn records(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+/// **decode_hex()** - Decodes pairs of hexadecimal digits
+fn decode_hex(digits: &str) -> Result<Vec<Byte>, RecordError> {
+    let digits = digits.as_bytes();
+    if !digits.len().is_multiple_of(2) {
+        return Err(Truncated);
+    }
+
+    let digit = |c: u8| (c as char).to_digit(16).ok_or(BadDigit);
+    digits
+        .chunks(2)
+        .map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as Byte))
+        .collect()
+}
+
+fn to_address(address: u32) -> Result<Address, RecordError> {
+    Address::try_from(address).map_err(|_| AddressOutOfRange)
+}
+
+/// The state of parsing a text image
+#[derive(Default)]
+struct Reader {
+    image: Image,
+    /// The base added to the addresses of Intel HEX data records
+    base: u32,
+    /// The number of S-record data records so far
+    data_records: u32,
+    ended: bool,
+}
+
+impl Reader {
+    /// **ihex_record()** - `:LLAAAATT<data>CC`, the checksum being
+    /// the two's complement of the sum of all other bytes
+    fn ihex_record(&mut self, record: &str) -> Result<(), RecordError> {
+        if self.ended {
+            return Err(DataAfterEnd);
+        }
+
+        let bytes = decode_hex(record.strip_prefix(':').ok_or(MissingStartCode)?)?;
+        if bytes.len() < 5 {
+            return Err(Truncated);
+        }
+
+        let count = usize::from(bytes[0]);
+        if bytes.len() != count + 5 {
+            return Err(LengthMismatch {
+                expected: count,
+                found: bytes.len() - 5,
+            });
+        }
+
+        let (&found, rest) = bytes.split_last().unwrap();
+        let expected = rest
+            .iter()
+            .fold(0, |sum: Byte, b| sum.wrapping_add(*b))
+            .wrapping_neg();
+        if found != expected {
+            return Err(BadChecksum { expected, found });
+        }
+
+        let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let data = &bytes[4..4 + count];
+        let word = || u32::from(u16::from_be_bytes([data[0], data[1]]));
+        match (bytes[3], count) {
+            (0x00, _) => {
+                let address = self.base.checked_add(offset).ok_or(AddressOutOfRange)?;
+                if !self.image.add(address, data) {
+                    return Err(AddressOutOfRange);
+                }
+            }
+            (0x01, 0) => self.ended = true,
+            (0x02, 2) => self.base = word() << 4,
+            (0x03, 4) => {
+                let ip = u32::from(u16::from_be_bytes([data[2], data[3]]));
+                self.image.start = Some(to_address((word() << 4) + ip)?);
+            }
+            (0x04, 2) => self.base = word() << 16,
+            (0x05, 4) => {
+                let linear = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                self.image.start = Some(to_address(linear)?);
+            }
+            (0x00..=0x05, _) => return Err(Malformed),
+            (kind, _) => return Err(UnknownType(kind)),
+        }
+        Ok(())
+    }
+
+    /// **srec_record()** - `S<type><count><address><data><checksum>`, the
+    /// count covering everything after it and the checksum being the
+    /// one's complement of their sum
+    fn srec_record(&mut self, record: &str) -> Result<(), RecordError> {
+        if self.ended {
+            return Err(DataAfterEnd);
+        }
+
+        let rest = record
+            .strip_prefix('S')
+            .or_else(|| record.strip_prefix('s'))
+            .ok_or(MissingStartCode)?;
+        let kind = rest.chars().next().ok_or(Truncated)?;
+        let kind = kind.to_digit(10).ok_or(BadDigit)? as Byte;
+        let bytes = decode_hex(&rest[1..])?;
+        if bytes.is_empty() {
+            return Err(Truncated);
+        }
+
+        let count = usize::from(bytes[0]);
+        if bytes.len() != count + 1 {
+            return Err(LengthMismatch {
+                expected: count,
+                found: bytes.len() - 1,
+            });
+        }
+
+        let (&found, rest) = bytes.split_last().unwrap();
+        let expected = !rest.iter().fold(0, |sum: Byte, b| sum.wrapping_add(*b));
+        if found != expected {
+            return Err(BadChecksum { expected, found });
+        }
+
+        let address_len = match kind {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(UnknownType(kind)),
+        };
+        if count < address_len + 1 {
+            return Err(Truncated);
+        }
+
+        let address = bytes[1..=address_len]
+            .iter()
+            .fold(0, |address, b| address << 8 | u32::from(*b));
+        let data = &bytes[1 + address_len..bytes.len() - 1];
+        match kind {
+            0 => {}
+            1..=3 => {
+                self.data_records += 1;
+                if !self.image.add(address, data) {
+                    return Err(AddressOutOfRange);
+                }
+            }
+            5 | 6 if data.is_empty() => {
+                if address != self.data_records {
+                    return Err(RecordCountMismatch {
+                        expected: address,
+                        found: self.data_records,
+                    });
+                }
+            }
+            7..=9 if data.is_empty() => {
+                self.image.start = Some(to_address(address)?);
+                self.ended = true;
+            }
+            _ => return Err(Malformed),
+        }
+        Ok(())
+    }
+
+    fn finish(self, text: &str) -> Result<Image, CpuError> {
+        if !self.ended {
+            return Err(CpuError::BadRecord {
+                line: text.lines().count().max(1),
+                reason: MissingEnd,
+            });
+        }
+        Ok(self.image)
+    }
+}
+
+///
+/// ObjectError
+/// \
+/// Why a binary image has been rejected.\
+/// \
+/// **Truncated** - The file ends in the middle of something.\
+/// **BadMagic** - The file does not begin with the marker of its format.\
+/// **UnsupportedMode** - The o65 file is for the 65816 or uses 32 bit sizes.\
+/// **BadSegment** - A relocation or an export refers to an unknown segment.\
+/// **BadRelocation** - The type of a relocation is unknown or meant for the 65816.\
+/// **RelocationOutOfRange** - A relocation points outside of its segment.\
+/// **UndefinedSymbol** - The undefined reference with the given index is not
+/// in the symbol table.\
+/// **AddressOutOfRange** - A segment does not fit in the 64 KiB address space.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectError {
+    Truncated,
+    BadMagic,
+    UnsupportedMode(Word),
+    BadSegment(Byte),
+    BadRelocation(Byte),
+    RelocationOutOfRange,
+    UndefinedSymbol(usize),
+    AddressOutOfRange,
+}
+
+fn bad_object(offset: usize, reason: ObjectError) -> CpuError {
+    CpuError::BadObject { offset, reason }
+}
+
+impl Image {
+    /// **from_prg()** - Parses a Commodore program file -- a little
+    /// endian load address followed by the data. The program is
+    /// expected to start where it is loaded.
+    pub fn from_prg(bytes: &[Byte]) -> Result<Self, CpuError> {
+        if bytes.len() < 2 {
+            return Err(bad_object(bytes.len(), ObjectError::Truncated));
+        }
+
+        let address = Address::from_le_bytes([bytes[0], bytes[1]]);
+        let mut image = Image {
+            start: Some(address),
+            ..Image::default()
+        };
+        if !image.add(u32::from(address), &bytes[2..]) {
+            return Err(bad_object(2, ObjectError::AddressOutOfRange));
+        }
+        Ok(image)
+    }
+}
+
+/// The bytes of a binary image, read from the front
+struct Cursor<'a> {
+    bytes: &'a [Byte],
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [Byte], CpuError> {
+        let taken = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| bad_object(self.bytes.len(), ObjectError::Truncated))?;
+        self.offset += len;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<Byte, CpuError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<Word, CpuError> {
+        let word = self.take(2)?;
+        Ok(Word::from_le_bytes([word[0], word[1]]))
+    }
+
+    /// **name()** - A zero terminated string
+    fn name(&mut self) -> Result<String, CpuError> {
+        let len = self.bytes[self.offset.min(self.bytes.len())..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| bad_object(self.bytes.len(), ObjectError::Truncated))?;
+        let name = String::from_utf8_lossy(self.take(len)?).into_owned();
+        self.offset += 1;
+        Ok(name)
+    }
+}
+
+//
+// o65
+//
+// The relocatable object format of the xa65 assembler, as described by
+// [this](http://www.6502.org/users/andre/o65/fileformat.html) document.
+// Only the 6502 flavour is supported -- 16 bit sizes, no 65816 relocations.
+//
+
+const O65_MAGIC: [Byte; 6] = [0x01, 0x00, b'o', b'6', b'5', 0x00];
+
+/// The mode bits of an o65 file
+const O65_65816: Word = 0x8000;
+const O65_PAGED: Word = 0x4000;
+const O65_SIZE32: Word = 0x2000;
+const O65_BSSZERO: Word = 0x0200;
+
+/// The segment numbers used by relocations and exports
+const SEG_UNDEFINED: Byte = 0;
+const SEG_ABSOLUTE: Byte = 1;
+const SEG_TEXT: Byte = 2;
+const SEG_DATA: Byte = 3;
+const SEG_BSS: Byte = 4;
+const SEG_ZERO: Byte = 5;
+
+/// The relocation types
+const RELOC_WORD: Byte = 0x80;
+const RELOC_HIGH: Byte = 0x40;
+const RELOC_LOW: Byte = 0x20;
+
+///
+/// O65Bases
+///
+/// Where the segments of an o65 object are placed.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct O65Bases {
+    pub text: Address,
+    pub data: Address,
+    pub bss: Address,
+    pub zero: Address,
+}
+
+///
+/// O65Export
+///
+/// A global symbol of an o65 object. `value` is relative to
+/// the base of `segment` which the object was assembled for.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct O65Export {
+    pub name: String,
+    pub segment: Byte,
+    pub value: Address,
+}
+
+/// A single entry of a relocation table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct O65Relocation {
+    /// The offset of the entry in the file
+    at: usize,
+    /// The offset of the relocated byte(s) in the segment
+    offset: usize,
+    kind: Byte,
+    segment: Byte,
+    /// The index of the undefined reference, if any
+    symbol: usize,
+    /// The low byte of a `RELOC_HIGH` value
+    low: Byte,
+}
+
+///
+/// O65
+///
+/// A parsed o65 object. Segment bases are the ones which the
+/// object was assembled for.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct O65 {
+    pub mode: Word,
+    pub text_base: Address,
+    pub text: Vec<Byte>,
+    pub data_base: Address,
+    pub data: Vec<Byte>,
+    pub bss_base: Address,
+    pub bss_len: Word,
+    pub zero_base: Address,
+    pub zero_len: Word,
+    pub stack_len: Word,
+    /// The names of the symbols which the object refers to but does not define
+    pub undefined: Vec<String>,
+    pub exports: Vec<O65Export>,
+    text_relocations: Vec<O65Relocation>,
+    data_relocations: Vec<O65Relocation>,
+}
+
+impl O65 {
+    /// **parse()** - Parses an o65 file
+    pub fn parse(bytes: &[Byte]) -> Result<Self, CpuError> {
+        let mut cursor = Cursor { bytes, offset: 0 };
+        if cursor.take(O65_MAGIC.len()).ok() != Some(&O65_MAGIC[..]) {
+            return Err(bad_object(0, ObjectError::BadMagic));
+        }
+
+        let mode = cursor.word()?;
+        if mode & (O65_65816 | O65_SIZE32) != 0 {
+            return Err(bad_object(
+                O65_MAGIC.len(),
+                ObjectError::UnsupportedMode(mode),
+            ));
+        }
+
+        let mut header = [0; 9];
+        for field in header.iter_mut() {
+            *field = cursor.word()?;
+        }
+        let [text_base, text_len, data_base, data_len, bss_base, bss_len, zero_base, zero_len, stack_len] =
+            header;
+
+        // The header options are of no interest when loading
+        loop {
+            let len = usize::from(cursor.byte()?);
+            if len == 0 {
+                break;
+            }
+            cursor.take(len.saturating_sub(1))?;
+        }
+
+        let text = cursor.take(usize::from(text_len))?.to_vec();
+        let data = cursor.take(usize::from(data_len))?.to_vec();
+
+        let mut undefined = Vec::new();
+        for _ in 0..cursor.word()? {
+            undefined.push(cursor.name()?);
+        }
+
+        let paged = mode & O65_PAGED != 0;
+        let text_relocations = relocations(&mut cursor, text.len(), undefined.len(), paged)?;
+        let data_relocations = relocations(&mut cursor, data.len(), undefined.len(), paged)?;
+
+        let mut exports = Vec::new();
+        for _ in 0..cursor.word()? {
+            let name = cursor.name()?;
+            let at = cursor.offset;
+            let segment = cursor.byte()?;
+            if !(SEG_ABSOLUTE..=SEG_ZERO).contains(&segment) {
+                return Err(bad_object(at, ObjectError::BadSegment(segment)));
+            }
+            let value = cursor.word()?;
+            exports.push(O65Export {
+                name,
+                segment,
+                value,
+            });
+        }
+
+        Ok(Self {
+            mode,
+            text_base,
+            text,
+            data_base,
+            data,
+            bss_base,
+            bss_len,
+            zero_base,
+            zero_len,
+            stack_len,
+            undefined,
+            exports,
+            text_relocations,
+            data_relocations,
+        })
+    }
+
+    /// **bases_at()** - Places text, data and bss one after another
+    /// from `base`. The zero page segment stays where it is.
+    pub fn bases_at(&self, base: Address) -> O65Bases {
+        let data = base.wrapping_add(self.text.len() as Word);
+        O65Bases {
+            text: base,
+            data,
+            bss: data.wrapping_add(self.data.len() as Word),
+            zero: self.zero_base,
+        }
+    }
+
+    /// How much a segment is moved by relocating it to `bases`
+    fn delta(&self, segment: Byte, bases: &O65Bases) -> Word {
+        match segment {
+            SEG_TEXT => bases.text.wrapping_sub(self.text_base),
+            SEG_DATA => bases.data.wrapping_sub(self.data_base),
+            SEG_BSS => bases.bss.wrapping_sub(self.bss_base),
+            SEG_ZERO => bases.zero.wrapping_sub(self.zero_base),
+            _ => 0,
+        }
+    }
+
+    /// **relocate()** - Relocates the object to `bases`, resolving its
+    /// undefined references against `symbols`. The bss segment is
+    /// included, cleared, if the object asks for it. The image starts
+    /// at the beginning of the text segment.
+    pub fn relocate(
+        &self,
+        bases: &O65Bases,
+        symbols: &HashMap<String, Address>,
+    ) -> Result<Image, CpuError> {
+        let mut text = self.text.clone();
+        let mut data = self.data.clone();
+        for (segment, relocations) in [
+            (&mut text, &self.text_relocations),
+            (&mut data, &self.data_relocations),
+        ] {
+            for r in relocations {
+                let delta = if r.segment == SEG_UNDEFINED {
+                    let name = &self.undefined[r.symbol];
+                    *symbols
+                        .get(name)
+                        .ok_or_else(|| bad_object(r.at, ObjectError::UndefinedSymbol(r.symbol)))?
+                } else {
+                    self.delta(r.segment, bases)
+                };
+
+                match r.kind {
+                    RELOC_WORD => {
+                        let word = Word::from_le_bytes([segment[r.offset], segment[r.offset + 1]]);
+                        let [lo, hi] = word.wrapping_add(delta).to_le_bytes();
+                        segment[r.offset] = lo;
+                        segment[r.offset + 1] = hi;
+                    }
+                    RELOC_HIGH => {
+                        let word = Word::from_le_bytes([r.low, segment[r.offset]]);
+                        segment[r.offset] = word.wrapping_add(delta).to_le_bytes()[1];
+                    }
+                    _ => segment[r.offset] = segment[r.offset].wrapping_add(delta as Byte),
+                }
+            }
+        }
+
+        let mut image = Image {
+            start: Some(bases.text),
+            ..Image::default()
+        };
+        let mut fits = image.add(u32::from(bases.text), &text);
+        fits &= image.add(u32::from(bases.data), &data);
+        if self.mode & O65_BSSZERO != 0 {
+            fits &= image.add(u32::from(bases.bss), &vec![0; usize::from(self.bss_len)]);
+        }
+        if !fits {
+            return Err(bad_object(0, ObjectError::AddressOutOfRange));
+        }
+        Ok(image)
+    }
+
+    /// **symbol_table()** - The exported symbols after relocating
+    /// the object to `bases`
+    pub fn symbol_table(&self, bases: &O65Bases) -> HashMap<String, Address> {
+        self.exports
+            .iter()
+            .map(|export| {
+                let value = export.value.wrapping_add(self.delta(export.segment, bases));
+                (export.name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+/// **relocations()** - Reads the relocation table of a segment
+fn relocations(
+    cursor: &mut Cursor,
+    segment_len: usize,
+    undefined: usize,
+    paged: bool,
+) -> Result<Vec<O65Relocation>, CpuError> {
+    let mut result = Vec::new();
+    // The first offset is relative to the byte before the segment
+    let mut offset = 0usize;
+    loop {
+        let at = cursor.offset;
+        let step = cursor.byte()?;
+        match step {
+            0 => return Ok(result),
+            255 => {
+                offset += 254;
+                continue;
+            }
+            _ => offset += usize::from(step),
+        }
+
+        let type_byte = cursor.byte()?;
+        let kind = type_byte & 0xE0;
+        let segment = type_byte & 0x1F;
+        if segment > SEG_ZERO {
+            return Err(bad_object(at + 1, ObjectError::BadSegment(segment)));
+        }
+
+        let symbol = if segment == SEG_UNDEFINED {
+            let index = usize::from(cursor.word()?);
+            if index >= undefined {
+                return Err(bad_object(at, ObjectError::UndefinedSymbol(index)));
+            }
+            index
+        } else {
+            0
+        };
+
+        let (len, low) = match kind {
+            RELOC_WORD => (2, 0),
+            RELOC_HIGH if paged => (1, 0),
+            RELOC_HIGH => (1, cursor.byte()?),
+            RELOC_LOW => (1, 0),
+            _ => return Err(bad_object(at + 1, ObjectError::BadRelocation(type_byte))),
+        };
+
+        // `offset` is one past the relocated byte(s)
+        if offset == 0 || offset - 1 + len > segment_len {
+            return Err(bad_object(at, ObjectError::RelocationOutOfRange));
+        }
+        result.push(O65Relocation {
+            at,
+            offset: offset - 1,
+            kind,
+            segment,
+            symbol,
+            low,
+        });
+    }
+}
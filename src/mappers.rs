@@ -0,0 +1,579 @@
+use crate::memory_map::{lock, Device, SharedDevice};
+use crate::mos6502::{Address, Byte, IrqLine};
+
+//
+// Mappers
+//
+// A 6502 sees only 64 KiB at once. Machines with more memory than that
+// switch _banks_ in and out of some window of the address space, usually
+// by writing to a register. The logic which does this is called a _mapper_.
+//
+// All mappers here are `Device`s, so they are mounted into a `MemoryMap`
+// like anything else. The offsets they are given are relative to where
+// they are mounted. `peek_bank()` reads a given bank as if it was switched
+// into the window which covers the offset, whatever is switched in at the
+// moment -- this is what debuggers and disassemblers use.
+//
+
+///
+/// Banks
+///
+/// Memory split into equally sized banks. Bank numbers wrap around
+/// the number of banks, just like on hardware which ignores the
+/// upper bits of a bank register.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Banks {
+    data: Vec<Byte>,
+    size: usize,
+}
+
+impl Banks {
+    /// **new()** - Splits `data` into banks of `size` bytes. The last
+    /// bank is padded with zeroes. There is always at least one bank.
+    /// Panics if `size` is zero.
+    pub fn new(mut data: Vec<Byte>, size: usize) -> Self {
+        assert!(size > 0, "banks cannot be empty");
+        let count = data.len().div_ceil(size).max(1);
+        data.resize(count * size, 0x00);
+        Self { data, size }
+    }
+
+    pub fn count(&self) -> usize {
+        self.data.len() / self.size
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn index(&self, bank: usize, offset: usize) -> usize {
+        (bank % self.count()) * self.size + offset % self.size
+    }
+
+    pub fn read(&self, bank: usize, offset: usize) -> Byte {
+        self.data[self.index(bank, offset)]
+    }
+
+    pub fn write(&mut self, bank: usize, offset: usize, data: Byte) {
+        let index = self.index(bank, offset);
+        self.data[index] = data;
+    }
+}
+
+const KIB: usize = 1024;
+
+///
+/// BankSwitcher
+///
+/// The simplest kind of banking. The region it is mounted at is split
+/// into windows of the bank size (8K or 16K for most cartridges). Writing
+/// `n` anywhere inside a window switches bank `n` into it. Initially
+/// window `i` shows bank `i`.
+///
+#[derive(Debug, Clone)]
+pub struct BankSwitcher {
+    banks: Banks,
+    windows: Vec<usize>,
+}
+
+impl BankSwitcher {
+    /// **new()** - `windows` windows of `bank_size` bytes.
+    /// Panics if either of them is zero.
+    pub fn new(data: Vec<Byte>, bank_size: usize, windows: usize) -> Self {
+        assert!(windows > 0, "a bank switcher needs at least one window");
+        Self {
+            banks: Banks::new(data, bank_size),
+            windows: (0..windows).collect(),
+        }
+    }
+
+    /// **with_16k_banks()** - Two 16K windows, for a region of 32K
+    pub fn with_16k_banks(data: Vec<Byte>) -> Self {
+        Self::new(data, 16 * KIB, 2)
+    }
+
+    /// **with_8k_banks()** - Four 8K windows, for a region of 32K
+    pub fn with_8k_banks(data: Vec<Byte>) -> Self {
+        Self::new(data, 8 * KIB, 4)
+    }
+
+    /// **bank()** - The bank which is switched into a window
+    pub fn bank(&self, window: usize) -> usize {
+        self.windows[window]
+    }
+
+    fn window(&self, offset: Address) -> usize {
+        (usize::from(offset) / self.banks.size()).min(self.windows.len() - 1)
+    }
+}
+
+impl Device for BankSwitcher {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        let window = self.window(offset);
+        self.windows[window] = usize::from(data);
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        let bank = self.windows[self.window(offset)];
+        Some(self.banks.read(bank, usize::from(offset)))
+    }
+
+    fn peek_bank(&self, bank: usize, offset: Address) -> Option<Byte> {
+        Some(self.banks.read(bank, usize::from(offset)))
+    }
+}
+
+///
+/// Mirroring
+///
+/// How the two nametables of the NES are laid out in the four
+/// nametable slots of the PPU.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
+
+//
+// NES mappers
+//
+// All of them are meant to be mounted at 0x8000..=0xFFFF. Besides the
+// PRG ROM, which the cpu sees, they also bank the CHR memory, which is
+// seen only by the PPU through `read_chr()` and `write_chr()`. When a
+// cartridge has no CHR ROM, it has 8K of CHR RAM instead.
+//
+
+const PRG_BANK: usize = 16 * KIB;
+const CHR_SIZE: usize = 8 * KIB;
+
+#[derive(Debug, Clone)]
+struct Chr {
+    banks: Banks,
+    ram: bool,
+}
+
+impl Chr {
+    fn new(chr: Vec<Byte>, bank_size: usize) -> Self {
+        let ram = chr.is_empty();
+        let chr = if ram { vec![0x00; CHR_SIZE] } else { chr };
+        Self {
+            banks: Banks::new(chr, bank_size),
+            ram,
+        }
+    }
+}
+
+///
+/// UxRom (iNES mapper 2)
+///
+/// The 16K at 0x8000 are switchable, the 16K at 0xC000 are fixed to
+/// the last bank. Any write to 0x8000-0xFFFF selects the bank.
+///
+#[derive(Debug, Clone)]
+pub struct UxRom {
+    prg: Banks,
+    chr: Chr,
+    bank: usize,
+    mirroring: Mirroring,
+}
+
+impl UxRom {
+    pub fn new(prg: Vec<Byte>, chr: Vec<Byte>, mirroring: Mirroring) -> Self {
+        Self {
+            prg: Banks::new(prg, PRG_BANK),
+            chr: Chr::new(chr, CHR_SIZE),
+            bank: 0,
+            mirroring,
+        }
+    }
+
+    pub fn bank(&self) -> usize {
+        self.bank
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    pub fn read_chr(&self, address: Address) -> Byte {
+        self.chr.banks.read(0, usize::from(address))
+    }
+
+    pub fn write_chr(&mut self, address: Address, data: Byte) {
+        if self.chr.ram {
+            self.chr.banks.write(0, usize::from(address), data);
+        }
+    }
+}
+
+impl Device for UxRom {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, _offset: Address, data: Byte) {
+        self.bank = usize::from(data);
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        self.peek_bank(self.bank, offset)
+    }
+
+    /// The upper window always holds the last bank
+    fn peek_bank(&self, bank: usize, offset: Address) -> Option<Byte> {
+        let bank = if usize::from(offset) < PRG_BANK {
+            bank
+        } else {
+            self.prg.count() - 1
+        };
+        Some(self.prg.read(bank, usize::from(offset)))
+    }
+}
+
+///
+/// CnRom (iNES mapper 3)
+///
+/// The PRG ROM (16K mirrored, or 32K) is fixed. Any write to
+/// 0x8000-0xFFFF selects the 8K CHR bank.
+///
+#[derive(Debug, Clone)]
+pub struct CnRom {
+    prg: Banks,
+    chr: Chr,
+    chr_bank: usize,
+    mirroring: Mirroring,
+}
+
+impl CnRom {
+    pub fn new(prg: Vec<Byte>, chr: Vec<Byte>, mirroring: Mirroring) -> Self {
+        Self {
+            prg: Banks::new(prg, PRG_BANK),
+            chr: Chr::new(chr, CHR_SIZE),
+            chr_bank: 0,
+            mirroring,
+        }
+    }
+
+    pub fn chr_bank(&self) -> usize {
+        self.chr_bank
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    pub fn read_chr(&self, address: Address) -> Byte {
+        self.chr.banks.read(self.chr_bank, usize::from(address))
+    }
+
+    pub fn write_chr(&mut self, address: Address, data: Byte) {
+        if self.chr.ram {
+            self.chr
+                .banks
+                .write(self.chr_bank, usize::from(address), data);
+        }
+    }
+}
+
+impl Device for CnRom {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, _offset: Address, data: Byte) {
+        self.chr_bank = usize::from(data);
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        let bank = usize::from(offset) / PRG_BANK;
+        Some(self.prg.read(bank, usize::from(offset)))
+    }
+}
+
+///
+/// Mmc1 (iNES mapper 1)
+///
+/// The registers are written serially -- five writes of bit 0, the
+/// last of which decides the register by its address. A write with
+/// bit 7 set resets the shift register and fixes the last PRG bank
+/// at 0xC000.
+///
+/// Control (0x8000-0x9FFF): `CPPMM`\
+/// **MM** - mirroring (one screen lower, one screen upper, vertical, horizontal)\
+/// **PP** - PRG mode (0, 1: switch 32K at 0x8000; 2: fix the first bank at
+/// 0x8000; 3: fix the last bank at 0xC000)\
+/// **C** - CHR mode (0: switch 8K; 1: switch two separate 4K banks)
+///
+/// CHR bank 0 (0xA000-0xBFFF), CHR bank 1 (0xC000-0xDFFF) and
+/// PRG bank (0xE000-0xFFFF) select the banks.
+///
+#[derive(Debug, Clone)]
+pub struct Mmc1 {
+    prg: Banks,
+    chr: Chr,
+    shift: Byte,
+    writes: u8,
+    control: Byte,
+    chr_banks: [usize; 2],
+    prg_bank: usize,
+}
+
+impl Mmc1 {
+    pub fn new(prg: Vec<Byte>, chr: Vec<Byte>) -> Self {
+        Self {
+            prg: Banks::new(prg, PRG_BANK),
+            chr: Chr::new(chr, 4 * KIB),
+            shift: 0,
+            writes: 0,
+            control: 0x0C,
+            chr_banks: [0, 1],
+            prg_bank: 0,
+        }
+    }
+
+    pub fn control(&self) -> Byte {
+        self.control
+    }
+
+    pub fn prg_bank(&self) -> usize {
+        self.prg_bank
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    /// The 16K bank which is switched in at 0x8000 (`slot` 0) or 0xC000 (`slot` 1)
+    fn prg_slot(&self, slot: usize) -> usize {
+        match (self.control >> 2) & 0b11 {
+            0 | 1 => (self.prg_bank & !1) + slot,
+            2 if slot == 0 => 0,
+            2 => self.prg_bank,
+            _ if slot == 0 => self.prg_bank,
+            _ => self.prg.count() - 1,
+        }
+    }
+
+    /// The 4K CHR bank which is switched in at 0x0000 (`slot` 0) or 0x1000 (`slot` 1)
+    fn chr_slot(&self, slot: usize) -> usize {
+        if self.control & 0x10 == 0 {
+            (self.chr_banks[0] & !1) + slot
+        } else {
+            self.chr_banks[slot]
+        }
+    }
+
+    pub fn read_chr(&self, address: Address) -> Byte {
+        let slot = usize::from(address >> 12) & 1;
+        self.chr
+            .banks
+            .read(self.chr_slot(slot), usize::from(address))
+    }
+
+    pub fn write_chr(&mut self, address: Address, data: Byte) {
+        if self.chr.ram {
+            let slot = usize::from(address >> 12) & 1;
+            let bank = self.chr_slot(slot);
+            self.chr.banks.write(bank, usize::from(address), data);
+        }
+    }
+}
+
+impl Device for Mmc1 {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        if data & 0x80 != 0 {
+            self.shift = 0;
+            self.writes = 0;
+            self.control |= 0x0C;
+            return;
+        }
+
+        self.shift |= (data & 1) << self.writes;
+        self.writes += 1;
+        if self.writes < 5 {
+            return;
+        }
+
+        let value = self.shift;
+        match offset >> 13 {
+            0 => self.control = value,
+            1 => self.chr_banks[0] = usize::from(value),
+            2 => self.chr_banks[1] = usize::from(value),
+            _ => self.prg_bank = usize::from(value & 0x0F),
+        }
+        self.shift = 0;
+        self.writes = 0;
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        let slot = usize::from(offset) / PRG_BANK;
+        Some(self.prg.read(self.prg_slot(slot), usize::from(offset)))
+    }
+
+    fn peek_bank(&self, bank: usize, offset: Address) -> Option<Byte> {
+        Some(self.prg.read(bank, usize::from(offset)))
+    }
+}
+
+///
+/// C64Pla
+///
+/// The address decoding of the Commodore 64 (without a cartridge). It is
+/// meant to be mounted over the whole address space. There are 64K of RAM
+/// underneath everything -- the ROMs and the I/O area are overlaid on top
+/// of it, as selected by the lowest three bits of the processor port at
+/// 0x0001 (with its data direction register at 0x0000):
+///
+/// **LORAM** (bit 0) and **HIRAM** (bit 1) - BASIC at 0xA000 needs both,
+/// the KERNAL at 0xE000 needs HIRAM\
+/// **CHAREN** (bit 2) - If either LORAM or HIRAM is set, 0xD000 shows the
+/// I/O area when it is set or the character ROM when it is clear
+///
+/// Writes to the ROMs go through to the RAM underneath. The bank of
+/// `peek_bank()` is the value of these three bits to decode with.
+///
+pub struct C64Pla {
+    ram: Vec<Byte>,
+    basic: Vec<Byte>,
+    kernal: Vec<Byte>,
+    chargen: Vec<Byte>,
+    io: Option<SharedDevice>,
+    ddr: Byte,
+    port: Byte,
+}
+
+impl std::fmt::Debug for C64Pla {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("C64Pla")
+            .field("ddr", &self.ddr)
+            .field("port", &self.port)
+            .field("has_io", &self.io.is_some())
+            .finish()
+    }
+}
+
+/// What is visible at an address of the C64
+enum C64Chip {
+    Ram,
+    Basic,
+    Kernal,
+    Chargen,
+    Io,
+}
+
+impl C64Pla {
+    /// **new()** - The ROMs are 8K (BASIC and KERNAL) and 4K (character ROM)
+    pub fn new(basic: Vec<Byte>, kernal: Vec<Byte>, chargen: Vec<Byte>) -> Self {
+        Self {
+            ram: vec![0x00; 64 * KIB],
+            basic,
+            kernal,
+            chargen,
+            io: None,
+            ddr: 0x2F,
+            port: 0x37,
+        }
+    }
+
+    /// **with_io()** - Mounts a device at the I/O area (0xD000-0xDFFF)
+    pub fn with_io(mut self, io: SharedDevice) -> Self {
+        self.io = Some(io);
+        self
+    }
+
+    /// **config()** - The LORAM, HIRAM and CHAREN lines, as seen by the PLA.
+    /// Inputs are pulled up.
+    pub fn config(&self) -> Byte {
+        ((self.port & self.ddr) | !self.ddr) & 0b111
+    }
+
+    fn decode(config: Byte, address: Address) -> C64Chip {
+        let loram = config & 0b001 != 0;
+        let hiram = config & 0b010 != 0;
+        let charen = config & 0b100 != 0;
+
+        match address {
+            0xA000..=0xBFFF if loram && hiram => C64Chip::Basic,
+            0xD000..=0xDFFF if (loram || hiram) && charen => C64Chip::Io,
+            0xD000..=0xDFFF if loram || hiram => C64Chip::Chargen,
+            0xE000..=0xFFFF if hiram => C64Chip::Kernal,
+            _ => C64Chip::Ram,
+        }
+    }
+
+    fn peek_with(&self, config: Byte, address: Address) -> Option<Byte> {
+        let rom = |rom: &Vec<Byte>, base: Address| rom.get(usize::from(address - base)).copied();
+
+        match Self::decode(config, address) {
+            C64Chip::Basic => rom(&self.basic, 0xA000),
+            C64Chip::Kernal => rom(&self.kernal, 0xE000),
+            C64Chip::Chargen => rom(&self.chargen, 0xD000),
+            C64Chip::Io => lock(self.io.as_ref()?).peek(address - 0xD000),
+            C64Chip::Ram => match address {
+                0x0000 => Some(self.ddr),
+                0x0001 => Some((self.port & self.ddr) | !self.ddr),
+                _ => Some(self.ram[usize::from(address)]),
+            },
+        }
+    }
+}
+
+impl Device for C64Pla {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        match Self::decode(self.config(), offset) {
+            C64Chip::Io => lock(self.io.as_ref()?).read(offset - 0xD000),
+            _ => self.peek(offset),
+        }
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        match (Self::decode(self.config(), offset), offset) {
+            (C64Chip::Io, _) => {
+                if let Some(io) = &self.io {
+                    lock(io).write(offset - 0xD000, data);
+                }
+            }
+            (_, 0x0000) => self.ddr = data,
+            (_, 0x0001) => self.port = data,
+            _ => self.ram[usize::from(offset)] = data,
+        }
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        self.peek_with(self.config(), offset)
+    }
+
+    fn peek_bank(&self, bank: usize, offset: Address) -> Option<Byte> {
+        self.peek_with(bank as Byte & 0b111, offset)
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        if let Some(io) = &self.io {
+            lock(io).connect_irq(line);
+        }
+    }
+
+    fn tick(&mut self) {
+        if let Some(io) = &self.io {
+            lock(io).tick();
+        }
+    }
+}
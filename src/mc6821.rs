@@ -0,0 +1,414 @@
+use crate::memory_map::Device;
+use crate::mos6502::{Address, Byte, IrqLine, IrqSource};
+use std::collections::VecDeque;
+
+//
+// MC6821 PIA
+//
+// The Peripheral Interface Adapter (sold by MOS as the 6520) has two
+// 8 bit ports, each with a data direction register, a control register
+// and two control lines, C1 and C2. It occupies 4 registers:
+//
+// 0x0   data A or DDR A, as selected by bit 2 of CRA
+// 0x1   CRA
+// 0x2   data B or DDR B, as selected by bit 2 of CRB
+// 0x3   CRB
+//
+// A control register is laid out as follows:
+//
+// bit 0     - C1 IRQ enable
+// bit 1     - C1 active on its rising (1) or falling (0) edge
+// bit 2     - data register (1) or DDR (0) selected
+// bits 5-3  - C2 mode, see `C2Mode`
+// bit 6     - C2 flag, read only
+// bit 7     - C1 flag, read only
+//
+// Reading a data register clears both flags of its port. Keyboards and
+// displays are usually wired with the key on port A, strobed by CA1, and
+// the characters on port B, which is what the host side API assumes.
+//
+
+/// The registers, by offset
+pub const DATA_A: Address = 0x0;
+pub const CRA: Address = 0x1;
+pub const DATA_B: Address = 0x2;
+pub const CRB: Address = 0x3;
+
+/// The bits of a control register
+pub const CR_C1_IRQ: Byte = 0x01;
+pub const CR_C1_RISING: Byte = 0x02;
+pub const CR_DATA: Byte = 0x04;
+pub const CR_C2_FLAG: Byte = 0x40;
+pub const CR_C1_FLAG: Byte = 0x80;
+
+///
+/// C2Mode
+/// \
+/// The mode of CA2 or CB2, bits 5-3 of the control register.\
+/// \
+/// **Input** - An interrupt input, active on the given edge.\
+/// **Handshake** - Goes low when port A is read (port B written) and
+/// high again on the active edge of C1.\
+/// **Pulse** - Goes low for one cycle when port A is read (port B written).\
+/// **Low**, **High** - A fixed output.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum C2Mode {
+    Input { irq: bool, rising: bool },
+    Handshake,
+    Pulse,
+    Low,
+    High,
+}
+
+impl C2Mode {
+    fn from_control(control: Byte) -> Self {
+        match (control >> 3) & 0x07 {
+            bits @ 0..=3 => C2Mode::Input {
+                irq: bits & 0x01 != 0,
+                rising: bits & 0x02 != 0,
+            },
+            4 => C2Mode::Handshake,
+            5 => C2Mode::Pulse,
+            6 => C2Mode::Low,
+            _ => C2Mode::High,
+        }
+    }
+}
+
+/// One half of the PIA
+#[derive(Debug)]
+struct Port {
+    output: Byte,
+    ddr: Byte,
+    input: Byte,
+    control: Byte,
+    c1_flag: bool,
+    c2_flag: bool,
+    c1: bool,
+    c2_in: bool,
+    c2_out: bool,
+    /// Port B strobes C2 when written, port A when read
+    strobe_on_write: bool,
+}
+
+impl Port {
+    fn new(strobe_on_write: bool) -> Self {
+        Self {
+            output: 0,
+            ddr: 0,
+            input: 0xFF,
+            control: 0,
+            c1_flag: false,
+            c2_flag: false,
+            c1: true,
+            c2_in: true,
+            c2_out: true,
+            strobe_on_write,
+        }
+    }
+
+    fn pins(&self) -> Byte {
+        (self.output & self.ddr) | (self.input & !self.ddr)
+    }
+
+    fn c2_mode(&self) -> C2Mode {
+        C2Mode::from_control(self.control)
+    }
+
+    fn c2(&self) -> bool {
+        match self.c2_mode() {
+            C2Mode::Input { .. } => self.c2_in,
+            _ => self.c2_out,
+        }
+    }
+
+    fn control_register(&self) -> Byte {
+        let mut control = self.control & 0x3F;
+        if self.c1_flag {
+            control |= CR_C1_FLAG;
+        }
+        if self.c2_flag {
+            control |= CR_C2_FLAG;
+        }
+        control
+    }
+
+    fn irq(&self) -> bool {
+        let c2_irq = matches!(self.c2_mode(), C2Mode::Input { irq: true, .. });
+        (self.c1_flag && self.control & CR_C1_IRQ != 0) || (self.c2_flag && c2_irq)
+    }
+
+    /// The value of a register, without the side effects of reading it
+    fn register(&self, control: bool) -> Byte {
+        if control {
+            self.control_register()
+        } else if self.control & CR_DATA != 0 {
+            self.pins()
+        } else {
+            self.ddr
+        }
+    }
+
+    fn read(&mut self, control: bool) -> Byte {
+        let value = self.register(control);
+        if !control && self.control & CR_DATA != 0 {
+            self.c1_flag = false;
+            self.c2_flag = false;
+            if !self.strobe_on_write {
+                self.strobe();
+            }
+        }
+        value
+    }
+
+    fn write(&mut self, control: bool, data: Byte) {
+        if control {
+            self.control = data & 0x3F;
+            self.c2_out = self.c2_mode() != C2Mode::Low;
+            // The C2 flag is only ever set while C2 is an input
+            if !matches!(self.c2_mode(), C2Mode::Input { .. }) {
+                self.c2_flag = false;
+            }
+        } else if self.control & CR_DATA != 0 {
+            self.output = data;
+            if self.strobe_on_write {
+                self.strobe();
+            }
+        } else {
+            self.ddr = data;
+        }
+    }
+
+    /// Starts a handshake or a pulse on C2
+    fn strobe(&mut self) {
+        if matches!(self.c2_mode(), C2Mode::Handshake | C2Mode::Pulse) {
+            self.c2_out = false;
+        }
+    }
+
+    fn set_c1(&mut self, level: bool) {
+        if level == self.c1 {
+            return;
+        }
+        self.c1 = level;
+        if level == (self.control & CR_C1_RISING != 0) {
+            self.c1_flag = true;
+            if self.c2_mode() == C2Mode::Handshake {
+                self.c2_out = true;
+            }
+        }
+    }
+
+    fn set_c2(&mut self, level: bool) {
+        let previous = std::mem::replace(&mut self.c2_in, level);
+        if let C2Mode::Input { rising, .. } = self.c2_mode() {
+            if previous != level && level == rising {
+                self.c2_flag = true;
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.c2_mode() == C2Mode::Pulse {
+            self.c2_out = true;
+        }
+    }
+}
+
+///
+/// Pia
+///
+/// A Motorola MC6821 (or MOS 6520) Peripheral Interface Adapter. Mount
+/// it into a `MemoryMap` as a device -- it decodes the lower 2 address lines.
+///
+/// The host may drive the pins directly or push keystrokes, which are
+/// put on port A one by one, each with a strobe on CA1, as soon as the
+/// previous one has been read. Everything written to port B is
+/// collected as output.
+///
+#[derive(Debug)]
+pub struct Pia {
+    a: Port,
+    b: Port,
+    keys: VecDeque<Byte>,
+    output: Vec<Byte>,
+    irq: Option<IrqSource>,
+}
+
+impl Default for Pia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pia {
+    pub fn new() -> Self {
+        Self {
+            a: Port::new(false),
+            b: Port::new(true),
+            keys: VecDeque::new(),
+            output: Vec::new(),
+            irq: None,
+        }
+    }
+
+    /// **reset()** - Pulls the RESET pin. All registers are cleared,
+    /// the pins, the pending keystrokes and the output are kept.
+    pub fn reset(&mut self) {
+        for port in [&mut self.a, &mut self.b].iter_mut() {
+            // The pins are driven from the outside
+            **port = Port {
+                input: port.input,
+                c1: port.c1,
+                c2_in: port.c2_in,
+                ..Port::new(port.strobe_on_write)
+            };
+        }
+        self.update_irq();
+    }
+
+    /// **port_a()** - The levels of the pins of port A
+    pub fn port_a(&self) -> Byte {
+        self.a.pins()
+    }
+
+    /// **port_b()** - The levels of the pins of port B
+    pub fn port_b(&self) -> Byte {
+        self.b.pins()
+    }
+
+    /// **set_port_a()** - Drives the pins of port A which are inputs
+    pub fn set_port_a(&mut self, data: Byte) {
+        self.a.input = data;
+    }
+
+    /// **set_port_b()** - Drives the pins of port B which are inputs
+    pub fn set_port_b(&mut self, data: Byte) {
+        self.b.input = data;
+    }
+
+    /// **ca2()** - The level of CA2
+    pub fn ca2(&self) -> bool {
+        self.a.c2()
+    }
+
+    /// **cb2()** - The level of CB2
+    pub fn cb2(&self) -> bool {
+        self.b.c2()
+    }
+
+    /// **set_ca1()** - Drives CA1. Its active edge sets the CA1 flag and
+    /// ends a CA2 handshake.
+    pub fn set_ca1(&mut self, level: bool) {
+        self.a.set_c1(level);
+        self.update_irq();
+    }
+
+    /// **set_ca2()** - Drives CA2, if it is an input
+    pub fn set_ca2(&mut self, level: bool) {
+        self.a.set_c2(level);
+        self.update_irq();
+    }
+
+    /// **set_cb1()** - Drives CB1. Its active edge sets the CB1 flag and
+    /// ends a CB2 handshake.
+    pub fn set_cb1(&mut self, level: bool) {
+        self.b.set_c1(level);
+        self.update_irq();
+    }
+
+    /// **set_cb2()** - Drives CB2, if it is an input
+    pub fn set_cb2(&mut self, level: bool) {
+        self.b.set_c2(level);
+        self.update_irq();
+    }
+
+    /// **push_keys()** - Queues keystrokes for port A
+    pub fn push_keys(&mut self, keys: &[Byte]) {
+        self.keys.extend(keys);
+    }
+
+    /// **pending_keys()** - The number of keystrokes not yet put on port A
+    pub fn pending_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// **output()** - The bytes written to port B so far
+    pub fn output(&self) -> &[Byte] {
+        &self.output
+    }
+
+    /// **take_output()** - The bytes written to port B so far, which are forgotten
+    pub fn take_output(&mut self) -> Vec<Byte> {
+        std::mem::take(&mut self.output)
+    }
+
+    fn update_irq(&mut self) {
+        let asserted = self.a.irq() || self.b.irq();
+        if let Some(irq) = &self.irq {
+            irq.set(asserted);
+        }
+    }
+
+    /// Puts the next keystroke on port A once the last one is read
+    fn feed_key(&mut self) {
+        if self.a.c1_flag {
+            return;
+        }
+        if let Some(key) = self.keys.pop_front() {
+            let active = self.a.control & CR_C1_RISING != 0;
+            self.a.input = key;
+            self.a.set_c1(!active);
+            self.a.set_c1(active);
+            self.a.set_c1(!active);
+            self.update_irq();
+        }
+    }
+}
+
+impl Device for Pia {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        let control = offset & 0x01 != 0;
+        let value = if offset & 0x02 == 0 {
+            self.a.read(control)
+        } else {
+            self.b.read(control)
+        };
+        self.update_irq();
+        Some(value)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        let control = offset & 0x01 != 0;
+        if offset & 0x02 == 0 {
+            self.a.write(control, data);
+        } else {
+            self.b.write(control, data);
+            if !control && self.b.control & CR_DATA != 0 {
+                self.output.push(data);
+            }
+        }
+        self.update_irq();
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        let control = offset & 0x01 != 0;
+        if offset & 0x02 == 0 {
+            Some(self.a.register(control))
+        } else {
+            Some(self.b.register(control))
+        }
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        self.irq = Some(line.source("6821"));
+        self.update_irq();
+    }
+
+    fn tick(&mut self) {
+        self.a.tick();
+        self.b.tick();
+        self.feed_key();
+    }
+}
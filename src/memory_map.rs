@@ -0,0 +1,489 @@
+use crate::mos6502::{Address, Bus, Byte, IrqLine};
+use std::cell::Cell;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+//
+// Memory map
+//
+// The 6502 does not know anything about what is behind an address.
+// It is the address decoding logic of each machine which decides
+// whether a given address selects RAM, ROM or the registers of some
+// device. Usually only some of the address lines are decoded, which
+// is why the same memory appears at several places -- it is _mirrored_.
+//
+// `MemoryMap` describes such a machine declaratively. Regions are
+// mounted at address ranges and each of them may have a mask which
+// selects the address lines it decodes.
+//
+
+///
+/// Device
+///
+/// A memory mapped device. It is given the offset of the accessed
+/// address inside the region it is mounted at (after the mask is applied).
+///
+pub trait Device {
+    /// **read()** - Read one of the registers of the device. Reads
+    /// may have side effects, like clearing interrupt flags. `None`
+    /// means that the device does not drive the data bus.
+    fn read(&mut self, offset: Address) -> Option<Byte>;
+
+    /// **write()** - Write to one of the registers of the device
+    fn write(&mut self, offset: Address, data: Byte);
+
+    /// **peek()** - Read without any side effects, used by debuggers
+    /// and disassemblers
+    fn peek(&self, _offset: Address) -> Option<Byte> {
+        None
+    }
+
+    /// **peek_bank()** - Read without any side effects from a given
+    /// bank of a banked device, whatever is switched in at the moment.
+    /// Devices which are not banked have just one bank.
+    fn peek_bank(&self, _bank: usize, offset: Address) -> Option<Byte> {
+        self.peek(offset)
+    }
+
+    /// **connect_irq()** - Called when a cpu gets connected to the map
+    fn connect_irq(&mut self, _line: &IrqLine) {}
+
+    /// **tick()** - Called once for every cycle of the cpu, after
+    /// its bus access. Devices which count time do it here.
+    fn tick(&mut self) {}
+}
+
+/// A device which can be shared between the memory map and the host.
+/// It is `Send`, so that a cpu with a memory map may be moved to
+/// another thread.
+pub type SharedDevice = Arc<Mutex<dyn Device + Send>>;
+
+pub(crate) fn lock(device: &SharedDevice) -> MutexGuard<'_, dyn Device + Send + 'static> {
+    device.lock().unwrap()
+}
+
+///
+/// OpenBus
+///
+/// What a read returns when nothing drives the data bus.
+/// \
+/// **LastValue** - The value which was last seen on the data bus.
+/// It is what most 6502 machines do, since the bus lines keep
+/// their charge for a while.\
+/// **Fixed** - Always the given value.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OpenBus {
+    #[default]
+    LastValue,
+    Fixed(Byte),
+}
+
+///
+/// MemoryMapError
+/// \
+/// **Overlap** - Two regions are mounted over the same addresses.\
+/// **EmptyRange** - The end of a range is before its start.\
+/// **MirrorLoop** - A mirror (eventually) points back into itself.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryMapError {
+    Overlap {
+        first: (Address, Address),
+        second: (Address, Address),
+    },
+    EmptyRange {
+        start: Address,
+        end: Address,
+    },
+    MirrorLoop {
+        start: Address,
+        end: Address,
+    },
+}
+
+enum Handler {
+    Ram(Vec<Byte>),
+    Rom(Vec<Byte>),
+    Mirror(Address),
+    Device(SharedDevice),
+}
+
+impl std::fmt::Debug for Handler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Handler::Ram(mem) => write!(f, "Ram({:#x} bytes)", mem.len()),
+            Handler::Rom(mem) => write!(f, "Rom({:#x} bytes)", mem.len()),
+            Handler::Mirror(target) => write!(f, "Mirror({:#06x})", target),
+            Handler::Device(_) => write!(f, "Device"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Region {
+    start: Address,
+    end: Address,
+    mask: Address,
+    read_only: bool,
+    handler: Handler,
+}
+
+impl Region {
+    #[inline]
+    fn contains(&self, address: Address) -> bool {
+        self.start <= address && address <= self.end
+    }
+
+    #[inline]
+    fn offset(&self, address: Address) -> Address {
+        (address - self.start) & self.mask
+    }
+}
+
+/// A mirror may point to another mirror, but not endlessly
+const MAX_MIRROR_DEPTH: usize = 8;
+
+///
+/// MemoryMap
+///
+/// A bus made of regions which are decoded by address.
+/// Create one with `MemoryMap::builder()`.
+///
+#[derive(Debug)]
+pub struct MemoryMap {
+    /// Sorted by start address, never overlapping
+    regions: Vec<Region>,
+    /// The indices of the regions of distinct devices, so
+    /// that a device mounted twice is still ticked once
+    devices: Vec<usize>,
+    open_bus: OpenBus,
+    last_value: Cell<Byte>,
+}
+
+impl MemoryMap {
+    pub fn builder() -> MemoryMapBuilder {
+        MemoryMapBuilder::default()
+    }
+
+    fn find(&self, address: Address) -> Option<usize> {
+        let index = self.regions.partition_point(|region| region.end < address);
+        self.regions
+            .get(index)
+            .filter(|region| region.contains(address))
+            .map(|_| index)
+    }
+
+    /// Follows the mirrors and returns the index of the region
+    /// which handles an address with the offset inside of it
+    fn resolve(&self, mut address: Address) -> Option<(usize, Address)> {
+        for _ in 0..MAX_MIRROR_DEPTH {
+            let index = self.find(address)?;
+            let region = &self.regions[index];
+            let offset = region.offset(address);
+            match region.handler {
+                Handler::Mirror(target) => address = target.wrapping_add(offset),
+                _ => return Some((index, offset)),
+            }
+        }
+        None
+    }
+
+    fn open_bus_value(&self) -> Byte {
+        match self.open_bus {
+            OpenBus::LastValue => self.last_value.get(),
+            OpenBus::Fixed(value) => value,
+        }
+    }
+
+    /// **set_read_only()** - Write protects (or unprotects) the region
+    /// which handles an address. Returns whether there is such a region.
+    pub fn set_read_only(&mut self, address: Address, read_only: bool) -> bool {
+        match self.resolve(address) {
+            Some((index, _)) => {
+                self.regions[index].read_only = read_only;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// **peek()** - Read an address without any side effects
+    pub fn peek(&self, address: Address) -> Byte {
+        self.peek_with(address, |device, offset| device.peek(offset))
+    }
+
+    fn peek_with<F>(&self, address: Address, peek: F) -> Byte
+    where
+        F: Fn(&dyn Device, Address) -> Option<Byte>,
+    {
+        let value = match self.resolve(address) {
+            Some((index, offset)) => match &self.regions[index].handler {
+                Handler::Ram(mem) | Handler::Rom(mem) => mem.get(usize::from(offset)).copied(),
+                Handler::Device(device) => peek(&*lock(device), offset),
+                Handler::Mirror(_) => unreachable!(),
+            },
+            None => None,
+        };
+
+        value.unwrap_or_else(|| self.open_bus_value())
+    }
+}
+
+impl Bus for MemoryMap {
+    fn read(&self, address: Address) -> Option<Byte> {
+        let value = match self.resolve(address) {
+            Some((index, offset)) => match &self.regions[index].handler {
+                Handler::Ram(mem) | Handler::Rom(mem) => mem.get(usize::from(offset)).copied(),
+                Handler::Device(device) => lock(device).read(offset),
+                Handler::Mirror(_) => unreachable!(),
+            },
+            None => None,
+        };
+
+        let value = value.unwrap_or_else(|| self.open_bus_value());
+        self.last_value.set(value);
+        Some(value)
+    }
+
+    fn write(&mut self, address: Address, data: Byte) {
+        self.last_value.set(data);
+
+        if let Some((index, offset)) = self.resolve(address) {
+            let region = &mut self.regions[index];
+            if region.read_only {
+                return;
+            }
+
+            match &mut region.handler {
+                Handler::Ram(mem) => {
+                    if let Some(cell) = mem.get_mut(usize::from(offset)) {
+                        *cell = data;
+                    }
+                }
+                Handler::Rom(_) => {}
+                Handler::Device(device) => lock(device).write(offset, data),
+                Handler::Mirror(_) => unreachable!(),
+            }
+        }
+    }
+
+    fn read_seq(&self, address: Address, len: u16) -> Option<Vec<Byte>> {
+        let result = (0..len)
+            .map(|i| self.peek(address.wrapping_add(i)))
+            .collect();
+        Some(result)
+    }
+
+    fn peek(&self, address: Address) -> Option<Byte> {
+        Some(MemoryMap::peek(self, address))
+    }
+
+    fn peek_bank(&self, bank: usize, address: Address) -> Option<Byte> {
+        Some(self.peek_with(address, |device, offset| device.peek_bank(bank, offset)))
+    }
+
+    fn write_protected(&self, address: Address) -> bool {
+        match self.resolve(address) {
+            Some((index, _)) => self.regions[index].read_only,
+            None => false,
+        }
+    }
+
+    fn is_mapped(&self, address: Address) -> bool {
+        self.resolve(address).is_some()
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        for &index in &self.devices {
+            if let Handler::Device(device) = &self.regions[index].handler {
+                lock(device).connect_irq(line);
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        for &index in &self.devices {
+            if let Handler::Device(device) = &self.regions[index].handler {
+                lock(device).tick();
+            }
+        }
+    }
+}
+
+///
+/// MemoryMapBuilder
+///
+/// Mounts regions one after another. The mask of a region can be
+/// set by calling `mask()` right after mounting it -- the offset
+/// inside the region is then `(address - start) & mask`. This is
+/// how a region which decodes only some of the address lines is
+/// mirrored all over its range.
+///
+/// ```ignore
+/// let map = MemoryMap::builder()
+///     .ram(0x0000..=0x1FFF)
+///     .mask(0x07FF)
+///     .device(0x4000..=0x4017, apu)
+///     .rom(0x8000..=0xFFFF, &prg)
+///     .mask(0x3FFF)
+///     .open_bus(OpenBus::LastValue)
+///     .build()?;
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct MemoryMapBuilder {
+    regions: Vec<Region>,
+    open_bus: OpenBus,
+}
+
+impl MemoryMapBuilder {
+    fn mount(mut self, range: RangeInclusive<Address>, handler: Handler) -> Self {
+        let read_only = matches!(handler, Handler::Rom(_));
+        self.regions.push(Region {
+            start: *range.start(),
+            end: *range.end(),
+            mask: 0xFFFF,
+            read_only,
+            handler,
+        });
+        self
+    }
+
+    /// **ram()** - Mounts zeroed RAM over a range. Its size is the one
+    /// of the range, or less if the region is masked.
+    pub fn ram(self, range: RangeInclusive<Address>) -> Self {
+        self.mount(range, Handler::Ram(Vec::new()))
+    }
+
+    /// **rom()** - Mounts ROM with the given contents over a range.
+    /// Writes to it are ignored and reads past its contents are
+    /// not driven.
+    pub fn rom(self, range: RangeInclusive<Address>, contents: &[Byte]) -> Self {
+        self.mount(range, Handler::Rom(contents.to_vec()))
+    }
+
+    /// **rom_file()** - Mounts ROM with the contents of an image file
+    pub fn rom_file<P: AsRef<Path>>(
+        self,
+        range: RangeInclusive<Address>,
+        path: P,
+    ) -> std::io::Result<Self> {
+        let contents = fs::read(path)?;
+        Ok(self.rom(range, &contents))
+    }
+
+    /// **mirror()** - Makes a range show what is mounted at `target`
+    pub fn mirror(self, range: RangeInclusive<Address>, target: Address) -> Self {
+        self.mount(range, Handler::Mirror(target))
+    }
+
+    /// **device()** - Mounts the registers of a device over a range
+    pub fn device(self, range: RangeInclusive<Address>, device: SharedDevice) -> Self {
+        self.mount(range, Handler::Device(device))
+    }
+
+    /// **mask()** - Sets the mask of the region which was mounted last
+    pub fn mask(mut self, mask: Address) -> Self {
+        if let Some(region) = self.regions.last_mut() {
+            region.mask = mask;
+        }
+        self
+    }
+
+    /// **read_only()** - Write protects the region which was mounted last.
+    /// ROM is always write protected.
+    pub fn read_only(mut self) -> Self {
+        if let Some(region) = self.regions.last_mut() {
+            region.read_only = true;
+        }
+        self
+    }
+
+    /// **open_bus()** - Sets what reads of unmapped addresses return
+    pub fn open_bus(mut self, open_bus: OpenBus) -> Self {
+        self.open_bus = open_bus;
+        self
+    }
+
+    /// **build()** - Checks the regions and creates the map
+    pub fn build(mut self) -> Result<MemoryMap, MemoryMapError> {
+        for region in &self.regions {
+            if region.end < region.start {
+                return Err(MemoryMapError::EmptyRange {
+                    start: region.start,
+                    end: region.end,
+                });
+            }
+        }
+
+        self.regions.sort_by_key(|region| region.start);
+        for pair in self.regions.windows(2) {
+            if pair[1].start <= pair[0].end {
+                return Err(MemoryMapError::Overlap {
+                    first: (pair[0].start, pair[0].end),
+                    second: (pair[1].start, pair[1].end),
+                });
+            }
+        }
+
+        for region in &mut self.regions {
+            let size = usize::from(region.end - region.start) + 1;
+            let size = size.min(usize::from(region.mask) + 1);
+            if let Handler::Ram(mem) = &mut region.handler {
+                *mem = vec![0x00; size];
+            }
+        }
+
+        let mut devices: Vec<usize> = Vec::new();
+        for (index, region) in self.regions.iter().enumerate() {
+            if let Handler::Device(device) = &region.handler {
+                let seen = devices
+                    .iter()
+                    .any(|&other| match &self.regions[other].handler {
+                        Handler::Device(other) => Arc::ptr_eq(device, other),
+                        _ => false,
+                    });
+                if !seen {
+                    devices.push(index);
+                }
+            }
+        }
+
+        let map = MemoryMap {
+            regions: self.regions,
+            devices,
+            open_bus: self.open_bus,
+            last_value: Cell::new(0x00),
+        };
+
+        for region in &map.regions {
+            if let Handler::Mirror(_) = region.handler {
+                if mirror_loops(&map, region.start) {
+                    return Err(MemoryMapError::MirrorLoop {
+                        start: region.start,
+                        end: region.end,
+                    });
+                }
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Whether following the mirrors from an address never ends
+fn mirror_loops(map: &MemoryMap, mut address: Address) -> bool {
+    for _ in 0..MAX_MIRROR_DEPTH {
+        match map.find(address).map(|index| &map.regions[index]) {
+            Some(region) => match region.handler {
+                Handler::Mirror(target) => address = target.wrapping_add(region.offset(address)),
+                _ => return false,
+            },
+            None => return false,
+        }
+    }
+    true
+}
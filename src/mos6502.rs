@@ -1,13 +1,18 @@
-use crate::mos6502::InterruptKind::Irq;
-use crate::mos6502_addressing_modes::*;
+use crate::ines::INesError;
+use crate::loaders::{Image, ObjectError, RecordError, O65};
+use crate::mos6502::InterruptKind::{Irq, Nmi};
 use crate::mos6502_instruction_set::*;
+use crate::mos6502_micro_ops::{self, MicroState};
 
 use getset::{CopyGetters, Getters, MutGetters, Setters};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::prelude::*;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{fmt, fmt::Debug};
 
 pub type Address = u16;
@@ -15,8 +20,46 @@ pub type Word = u16;
 pub type Opcode = u8;
 pub type Byte = u8;
 
-pub type AddressingModeFn = fn(&mut Cpu) -> Result<AddressingOutput, CpuError>;
-pub type InstructionFn = fn(&mut Cpu) -> Result<(), CpuError>;
+///
+/// **instruction_fns!** - Generates `InstructionFn` out of the
+/// names of the instruction functions
+///
+macro_rules! instruction_fns {
+    ($($fun: ident),* $(,)?) => {
+        ///
+        /// InstructionFn
+        ///
+        /// Names one of the functions of the instruction set.
+        /// Calling it through `call()` is a plain `match`, so
+        /// every instruction is compiled for the concrete bus
+        /// of the cpu and the accesses to it can be inlined.
+        ///
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub enum InstructionFn {
+            $($fun),*
+        }
+
+        impl InstructionFn {
+            #[inline]
+            pub(crate) fn call<B: Bus + ?Sized>(self, cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+                match self {
+                    $(InstructionFn::$fun => $fun(cpu)),*
+                }
+            }
+        }
+    };
+}
+
+instruction_fns! {
+    adc, and, asl, bcc, bcs, beq, bit, bmi, bne, bpl, brk, bvc, bvs, clc, cld, cli, clv, cmp,
+    cpx, cpy, dec, dex, dey, eor, inc, inx, iny, jmp, jsr, lda, ldx, ldy, lsr, nop, ora, pha,
+    php, pla, plp, rol, ror, rti, rts, sbc, sec, sed, sei, sta, stx, sty, tax, tay, tsx, txa,
+    txs, tya, jam, slo, rla, sre, rra, sax, lax, dcp, isc, anc, alr, arr, sbx, xaa, lxa, ahx,
+    tas, shy, shx, las, bra, phx, phy, plx, ply, stz, trb, tsb, wai, stp, rmb0, rmb1, rmb2,
+    rmb3, rmb4, rmb5, rmb6, rmb7, smb0, smb1, smb2, smb3, smb4, smb5, smb6, smb7, bbr0, bbr1,
+    bbr2, bbr3, bbr4, bbr5, bbr6, bbr7, bbs0, bbs1, bbs2, bbs3, bbs4, bbs5, bbs6, bbs7,
+}
 
 /// This structure represents the registers each MOS 6502 has.
 /// They include one 8-bit accumulator register (A), two 8-bit
@@ -36,11 +79,10 @@ pub type InstructionFn = fn(&mut Cpu) -> Result<(), CpuError>;
 /// |+-------- Overflow\
 /// +--------- Negative
 ///
-
 /// **bit!** and **bit_setter!** - Generate a getter and a setter for a concrete bit of a field.
 ///
 /// Example:
-/// ```
+/// ```ignore
 ///
 /// impl RegisterSet {
 ///     #[inline]
@@ -54,11 +96,10 @@ pub type InstructionFn = fn(&mut Cpu) -> Result<(), CpuError>;
 ///     }
 /// }
 /// ```
-
 macro_rules! bit_setter {
     ($n: expr, $name: ident) => {
         #[inline]
-        pub(crate) fn $name(&mut self, value: bool) {
+        pub fn $name(&mut self, value: bool) {
             self.status = (self.status & !(1 << $n)) | ((value as u8) << $n);
         }
     };
@@ -67,7 +108,7 @@ macro_rules! bit_setter {
 macro_rules! bit {
     ($n: expr, $name: ident) => {
         #[inline]
-        pub(crate) fn $name(&self) -> bool {
+        pub fn $name(&self) -> bool {
             (self.status & (1 << $n)) != 0
         }
     };
@@ -96,7 +137,7 @@ impl RegisterSet {
         }
     }
 
-    pub(crate) fn new_custompc(custom_prog_counter: Address) -> Self {
+    pub fn new_custompc(custom_prog_counter: Address) -> Self {
         Self {
             prog_counter: custom_prog_counter,
             ..Self::new()
@@ -142,14 +183,214 @@ impl Timings {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Getters, CopyGetters, Setters, MutGetters, Default)]
-#[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+///
+/// InterruptHandling
+///
+/// The state of the interrupt lines of the cpu.
+///
+/// The IRQ line is _level-triggered_ -- an interrupt is
+/// requested for as long as the line is asserted and the
+/// **Interrupt disable** flag is clear.
+/// The NMI line is _edge-triggered_ -- asserting it latches
+/// a single request, which is kept until it is serviced.
+///
+/// The lines are sampled at the end of every cycle, but the
+/// decision whether to service an interrupt is based on the
+/// samples from the next-to-last cycle of an instruction.
+/// This is why a change of the **Interrupt disable** flag by
+/// `cli`, `sei` or `plp` takes effect one instruction later.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Getters, CopyGetters, Default)]
+#[getset(get_copy = "pub")]
 pub struct InterruptHandling {
+    /// Whether the reset sequence is going to start on the next cycle
+    pending_reset: bool,
+    /// Whether an NMI is going to be serviced after the current instruction
     pending_nmi: bool,
+    /// Whether an IRQ is going to be serviced after the current instruction
     pending_irq: bool,
+
+    nmi_line: bool,
+    /// Set on a rising edge of `nmi_line` and cleared once
+    /// the cpu has jumped through the NMI vector
+    nmi_latched: bool,
+
+    nmi_sampled: bool,
+    irq_sampled: bool,
+}
+
+impl InterruptHandling {
+    /// Samples the lines at the end of a cycle. If `boundary` is set,
+    /// the cycle is the last one of an instruction.
+    fn poll(&mut self, irq_requested: bool, boundary: bool) {
+        if boundary {
+            self.pending_nmi = self.nmi_sampled;
+            self.pending_irq = self.irq_sampled;
+        }
+
+        self.nmi_sampled = self.nmi_latched;
+        self.irq_sampled = irq_requested;
+    }
+
+    /// Whether the NMI vector should be used by an interrupt sequence.
+    /// Acknowledges the NMI.
+    pub(crate) fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_latched)
+    }
+}
+
+///
+/// IrqLine
+///
+/// The IRQ line of the cpu. Every device which can request
+/// interrupts holds its own _source_ of the line and asserts
+/// or releases it independently. The line is a wired-OR --
+/// it is asserted while at least one of its sources is.
+///
+/// Cloning an `IrqLine` gives another handle to the same line.
+/// The handles may be sent to other threads together with
+/// the cpu.
+///
+#[derive(Debug, Clone, Default)]
+pub struct IrqLine {
+    sources: Arc<Mutex<IrqSources>>,
+    /// The number of asserting sources, which the cpu polls on
+    /// every cycle without locking `sources`
+    asserting: Arc<AtomicUsize>,
+}
+
+/// The sources of a line, indexed by their id. The slot of a
+/// dropped source is freed and given to the next new one.
+#[derive(Debug, Default)]
+struct IrqSources {
+    slots: Vec<Option<IrqSourceState>>,
+}
+
+#[derive(Debug)]
+struct IrqSourceState {
+    name: String,
+    asserted: bool,
+}
+
+impl IrqSources {
+    fn get(&self, id: usize) -> &IrqSourceState {
+        self.slots[id].as_ref().expect("the source is registered")
+    }
+
+    fn get_mut(&mut self, id: usize) -> &mut IrqSourceState {
+        self.slots[id].as_mut().expect("the source is registered")
+    }
+}
+
+impl IrqLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sources(&self) -> std::sync::MutexGuard<'_, IrqSources> {
+        self.sources.lock().unwrap()
+    }
+
+    /// **source()** - Registers a new source of the line, initially released
+    pub fn source(&self, name: &str) -> IrqSource {
+        let mut sources = self.sources();
+        let state = Some(IrqSourceState {
+            name: name.to_string(),
+            asserted: false,
+        });
+
+        let id = match sources.slots.iter().position(Option::is_none) {
+            Some(free) => {
+                sources.slots[free] = state;
+                free
+            }
+            None => {
+                sources.slots.push(state);
+                sources.slots.len() - 1
+            }
+        };
+
+        IrqSource {
+            line: self.clone(),
+            id,
+        }
+    }
+
+    /// **asserted()** - Whether any of the sources is asserting the line
+    pub fn asserted(&self) -> bool {
+        self.asserting.load(Ordering::Relaxed) != 0
+    }
+
+    /// **asserting()** - The names of the sources which are currently
+    /// asserting the line
+    pub fn asserting(&self) -> Vec<String> {
+        self.sources()
+            .slots
+            .iter()
+            .flatten()
+            .filter(|source| source.asserted)
+            .map(|source| source.name.clone())
+            .collect()
+    }
+
+    /// **source_count()** - The number of sources currently registered
+    pub fn source_count(&self) -> usize {
+        self.sources().slots.iter().flatten().count()
+    }
+}
+
+///
+/// IrqSource
+///
+/// A single device's hold on the IRQ line. The source is
+/// released and unregistered when it is dropped.
+///
+#[derive(Debug)]
+pub struct IrqSource {
+    line: IrqLine,
+    id: usize,
+}
+
+impl IrqSource {
+    /// **set()** - Asserts or releases this source
+    pub fn set(&self, asserted: bool) {
+        let mut sources = self.line.sources();
+        let source = sources.get_mut(self.id);
+        if source.asserted != asserted {
+            source.asserted = asserted;
+            if asserted {
+                self.line.asserting.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.line.asserting.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn assert(&self) {
+        self.set(true);
+    }
+
+    pub fn release(&self) {
+        self.set(false);
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.line.sources().get(self.id).asserted
+    }
+
+    pub fn name(&self) -> String {
+        self.line.sources().get(self.id).name.clone()
+    }
+}
+
+impl Drop for IrqSource {
+    fn drop(&mut self) {
+        self.release();
+        self.line.sources().slots[self.id] = None;
+    }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum InterruptKind {
     Nmi,
     Irq,
@@ -160,18 +401,23 @@ pub enum InterruptKind {
 /// concrete address, also called _vector_.
 /// There is no mistake in the values of `IRQ_VECTOR` and `BRK_VECTOR` - they
 /// both point to 0xfffe.
-pub(crate) const NMI_VECTOR: Address = 0xfffa;
-pub(crate) const RESET_VECTOR: Address = 0xfffc;
-pub(crate) const IRQ_VECTOR: Address = 0xfffe;
-pub(crate) const BRK_VECTOR: Address = 0xfffe;
+pub const NMI_VECTOR: Address = 0xfffa;
+pub const RESET_VECTOR: Address = 0xfffc;
+pub const IRQ_VECTOR: Address = 0xfffe;
+pub const BRK_VECTOR: Address = 0xfffe;
 
 ///
 /// Cpu
 ///
 /// The struct representation of the MOS 6502.
 ///
+/// The cpu owns the bus it is connected to. As long as the
+/// bus is `Send`, so is the cpu, and it may be moved to another
+/// thread. Buses shared with the rest of the program are
+/// connected through a `SharedBus`, which is also the default.
+///
 #[derive(Getters, CopyGetters, Setters, MutGetters)]
-pub struct Cpu {
+pub struct Cpu<B: Bus + ?Sized = SharedBus> {
     /// **regset**
     /// The set of register that the cpu has
     // #[derive(Getters, CopyGetters, Setters, MutGetters)]
@@ -193,26 +439,325 @@ pub struct Cpu {
     /// instance.
     inter: InterruptHandling,
 
-    /// **bus_conn**
-    /// This cpu's connection to the "outer world".
-    /// This field stores a mutable reference to a
-    /// struct which has the trait CommunicationInterface,
-    /// a.k.a can be read from and written to. This
-    /// interface is stored as optional "shared pointer"
-    /// using the interior mutability pattern.
-    bus_conn: Option<Rc<RefCell<dyn CommunicationInterface>>>,
+    /// **irq_line**
+    /// The wired-OR of the interrupt requests of
+    /// all devices. Devices register their own
+    /// sources of the line.
+    #[getset(get = "pub")]
+    irq_line: IrqLine,
+
+    /// **host_irq**
+    /// The source of the IRQ line which is driven
+    /// by `set_irq_line()`.
+    host_irq: IrqSource,
 
     /// **i**
     /// This field stores the current instruction which
     /// is being executed. It should be stored directly
     /// from the `cycle_clock()` function and not
     /// modified until `self.time.residual` is not
-    /// decremented to 0. This is "residual time" is
-    /// used in order to emulate clock cycle accuracy.
-    /// Note the part with "emulate" and not "achieve".
-    /// The current implementation is not clock cycle
-    /// accurate.
+    /// decremented to 0.
     i: Option<Instruction>,
+
+    /// **micro**
+    /// The progress of the current instruction through
+    /// its cycles and the values latched on the way.
+    micro: MicroState,
+
+    /// **illegal_opcode_policy**
+    /// Describes what the cpu should do when it
+    /// fetches an opcode which is not part of the
+    /// documented instruction set.
+    #[getset(get_copy = "pub", set = "pub")]
+    illegal_opcode_policy: IllegalOpcodePolicy,
+
+    /// **rom_write_policy**
+    /// Describes what the cpu should do when it
+    /// writes to a write protected address.
+    #[getset(get_copy = "pub", set = "pub")]
+    rom_write_policy: RomWritePolicy,
+
+    /// **strict_bus**
+    /// Whether an access to an address at which
+    /// nothing is mapped stops the execution with
+    /// `CpuError::BusFault`. Off by default, so
+    /// such reads give whatever the bus returns.
+    #[getset(get_copy = "pub", set = "pub")]
+    strict_bus: bool,
+
+    /// **bus_faults**
+    /// The writes to write protected addresses
+    /// recorded under `RomWritePolicy::Record`, and
+    /// the faults to be reported under `Trap` or
+    /// with a strict bus.
+    #[getset(get = "pub")]
+    bus_faults: Vec<BusFault>,
+
+    /// **bus_hooks**
+    /// The callbacks which observe every bus access
+    /// of the cpu, in the order of their installation.
+    bus_hooks: Vec<BusHook>,
+
+    /// **magic_constants**
+    /// The values which the unstable undocumented
+    /// instructions mix into their results. They
+    /// differ between chips so they are left
+    /// configurable, one per instruction.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    magic_constants: MagicConstants,
+
+    /// **variant**
+    /// The concrete chip which is emulated. It is
+    /// chosen when the cpu is constructed and
+    /// decides how opcodes are decoded.
+    #[getset(get_copy = "pub")]
+    variant: CpuVariant,
+
+    /// **run_state**
+    /// Whether the cpu is executing instructions
+    /// or has been put to sleep by `wai` or `stp`.
+    #[getset(get_copy = "pub", set = "pub(crate)")]
+    run_state: RunState,
+
+    /// **bus**
+    /// This cpu's connection to the "outer world" --
+    /// anything which has the trait `Bus`, a.k.a can be
+    /// read from and written to. It is the last field so
+    /// that a `Cpu<dyn Bus>` can be used as well.
+    #[getset(get = "pub", get_mut = "pub")]
+    bus: B,
+}
+
+/// The value of the magic constant of `xaa` and `lxa` most commonly observed on real chips
+pub const DEFAULT_MAGIC_CONSTANT: Byte = 0xEE;
+
+///
+/// MagicConstants
+///
+/// The chip dependent values of the unstable undocumented instructions.
+///
+/// `xaa` and `lxa` "OR" the accumulator with their constant before
+/// "AND"-ing it, so that `0xFF` keeps all of the memory operand.
+/// `ahx`, `tas`, `shx` and `shy` "OR" the high byte of the base address
+/// plus one with theirs, so that `0xFF` drops that term, as it happens
+/// when the access is delayed by RDY. `las` "OR"s the stack pointer with
+/// its constant. The defaults are what most chips do.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Getters, CopyGetters, Setters, MutGetters)]
+#[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+pub struct MagicConstants {
+    xaa: Byte,
+    lxa: Byte,
+    ahx: Byte,
+    tas: Byte,
+    shx: Byte,
+    shy: Byte,
+    las: Byte,
+}
+
+impl Default for MagicConstants {
+    fn default() -> Self {
+        MagicConstants {
+            xaa: DEFAULT_MAGIC_CONSTANT,
+            lxa: DEFAULT_MAGIC_CONSTANT,
+            ahx: 0x00,
+            tas: 0x00,
+            shx: 0x00,
+            shy: 0x00,
+            las: 0x00,
+        }
+    }
+}
+
+///
+/// IllegalOpcodePolicy
+/// \
+/// What to do when an undocumented opcode is fetched.\
+/// \
+/// **Trap** - Stop and report `CpuError::IllegalOpcode`. The program counter
+/// is left pointing at the offending opcode.\
+/// **Nop** - Skip the instruction, consuming as many bytes and cycles as the
+/// real hardware would. The addressing cycles are kept, a read-modify-write
+/// writes the value back unmodified and a write becomes a dummy read.\
+/// **Execute** - Perform the undocumented behaviour of the NMOS 6502.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IllegalOpcodePolicy {
+    #[default]
+    Trap,
+    Nop,
+    Execute,
+}
+
+///
+/// RomWritePolicy
+/// \
+/// What to do when the cpu writes to an address which its bus
+/// reports as write protected (usually ROM). The write itself
+/// is always lost.\
+/// \
+/// **Ignore** - Nothing else happens, just like on the real hardware.\
+/// **Record** - The write is recorded as a `BusFault` and the
+/// execution continues.\
+/// **Trap** - The cycle is completed and `CpuError::WriteToRom` is reported.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RomWritePolicy {
+    #[default]
+    Ignore,
+    Record,
+    Trap,
+}
+
+///
+/// BusFault
+///
+/// An access which the bus could not serve as intended. `pc` is
+/// the address of the instruction which has done it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusFault {
+    pub address: Address,
+    pub data: Byte,
+    pub pc: Address,
+    pub kind: BusFaultKind,
+}
+
+///
+/// BusFaultKind
+/// \
+/// **WriteToRom** - A write to a write protected address.\
+/// **UnmappedRead** - A read from an address at which nothing is mapped.\
+/// **UnmappedWrite** - A write to an address at which nothing is mapped.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusFaultKind {
+    WriteToRom,
+    UnmappedRead,
+    UnmappedWrite,
+}
+
+///
+/// BusAccess
+///
+/// A single bus transaction of the cpu, as seen by a bus hook.
+/// For a read `data` is the value which the cpu has received.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusAccess {
+    pub address: Address,
+    pub data: Byte,
+    pub direction: AccessDirection,
+    pub kind: AccessKind,
+}
+
+///
+/// AccessDirection
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessDirection {
+    Read,
+    Write,
+}
+
+///
+/// AccessKind
+/// \
+/// Why the cpu accesses the bus.\
+/// \
+/// **Opcode** - The fetch of an opcode.\
+/// **Operand** - The fetch of an operand which follows the opcode.\
+/// **Data** - The memory which an instruction reads or writes, pointers included.\
+/// **Dummy** - An access whose result is thrown away, such as the read of the
+/// next byte by an implied instruction or the double write of read-modify-write
+/// instructions.\
+/// **Stack** - A push or a pull.\
+/// **Vector** - The fetch of an interrupt or reset vector.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Opcode,
+    Operand,
+    Data,
+    Dummy,
+    Stack,
+    Vector,
+}
+
+/// A callback observing the bus accesses of a cpu
+pub type BusHook = Box<dyn FnMut(&BusAccess) + Send>;
+
+///
+/// CpuVariant
+/// \
+/// The chip which the cpu emulates.\
+/// \
+/// **Nmos** - The original MOS 6502, undocumented opcodes included.\
+/// **Cmos** - The WDC 65C02. It adds new instructions and addressing modes,
+/// fixes the page wrap of `jmp (ind)`, clears the **Decimal** flag when
+/// entering an interrupt and decodes every unused opcode as a `nop`.\
+/// **Ricoh2A03** - The cpu of the NES. It decodes opcodes as the NMOS 6502
+/// does, but its decimal mode is disconnected -- the **Decimal** flag can
+/// be set, yet arithmetic is always binary.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CpuVariant {
+    #[default]
+    Nmos,
+    Cmos,
+    Ricoh2A03,
+}
+
+impl CpuVariant {
+    /// **has_decimal_mode()** - Whether the **Decimal** flag affects arithmetic
+    pub fn has_decimal_mode(self) -> bool {
+        self != CpuVariant::Ricoh2A03
+    }
+
+    /// **opcodes()** - The table of the opcodes of the chip
+    pub fn opcodes(self) -> &'static [OpcodeInfo; 256] {
+        match self {
+            CpuVariant::Nmos | CpuVariant::Ricoh2A03 => &NMOS_OPCODES,
+            CpuVariant::Cmos => &CMOS_OPCODES,
+        }
+    }
+}
+
+///
+/// RunState
+/// \
+/// **Running** - Instructions are fetched and executed as usual.\
+/// **Waiting** - `wai` has been executed. The cpu sleeps until an
+/// interrupt is signalled, even if it is a masked one.\
+/// **Stopped** - `stp` has been executed. Only a reset wakes the cpu.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunState {
+    #[default]
+    Running,
+    Waiting,
+    Stopped,
+}
+
+///
+/// ResetKind
+/// \
+/// **Warm** - The reset button is pressed while the machine is running.
+/// The registers keep their values, except for the ones changed by
+/// the reset sequence itself.\
+/// **PowerOn** - The machine is switched on. The registers are cleared.\
+/// **PowerOnRandom** - The machine is switched on. The registers hold
+/// garbage, generated from the given seed. Useful for catching programs
+/// which rely on uninitialised registers.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResetKind {
+    #[default]
+    Warm,
+    PowerOn,
+    PowerOnRandom {
+        seed: u64,
+    },
 }
 
 ///
@@ -229,7 +774,18 @@ pub struct Cpu {
 /// the given operands has been used.\
 /// **FailedLoadingProgram** - While reading the input file an error has occures.\
 /// **BadAddressing** - This error occures either when addressing or when addressing
-/// is exptected and it has not happened.
+/// is exptected and it has not happened.\
+/// **IllegalOpcode** - An undocumented opcode has been fetched from `address`
+/// and the cpu is not allowed to execute it.\
+/// **WriteToRom** - The instruction at `pc` has written to the write protected
+/// `address` while the `RomWritePolicy` is `Trap`.\
+/// **BusFault** - An access to `address` has failed, e.g. because nothing is
+/// mapped there and the bus is strict.\
+/// **BadRecord** - A text image has been rejected because of the record on
+/// the given `line`.\
+/// **BadObject** - A binary image has been rejected because of what is at
+/// the given `offset` of it.\
+/// **BadRom** - A cartridge image has been rejected.
 ///
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CpuError {
@@ -238,9 +794,30 @@ pub enum CpuError {
     ExpectedOperandMissing,
     FailedLoadingProgram,
     BadAddressing,
+    IllegalOpcode {
+        opcode: Opcode,
+        address: Address,
+    },
+    WriteToRom {
+        address: Address,
+        pc: Address,
+    },
+    BusFault {
+        address: Address,
+        kind: BusFaultKind,
+    },
+    BadRecord {
+        line: usize,
+        reason: RecordError,
+    },
+    BadObject {
+        offset: usize,
+        reason: ObjectError,
+    },
+    BadRom(INesError),
 }
 
-impl Cpu {
+impl<B: Bus + ?Sized> Cpu<B> {
     /// Acts as post-fix increment operation (pc++):
     /// Increments the Program Counter and returns the **old** value.
     #[inline]
@@ -254,10 +831,40 @@ impl Cpu {
         self.regset.prog_counter
     }
 
-    pub(crate) fn interrupt_handles(&self) -> &InterruptHandling {
+    pub fn interrupt_handles(&self) -> &InterruptHandling {
         &self.inter
     }
 
+    pub(crate) fn interrupt_handles_mut(&mut self) -> &mut InterruptHandling {
+        &mut self.inter
+    }
+
+    /// **set_irq_line()** - Asserts or releases the "host" source of the IRQ line
+    pub fn set_irq_line(&mut self, asserted: bool) {
+        self.host_irq.set(asserted);
+    }
+
+    /// **irq_sources_asserting()** - The names of the sources which
+    /// are currently holding the IRQ line asserted
+    pub fn irq_sources_asserting(&self) -> Vec<String> {
+        self.irq_line.asserting()
+    }
+
+    /// **set_nmi_line()** - Asserts or releases the NMI line.
+    /// Only asserting a released line requests an NMI.
+    pub fn set_nmi_line(&mut self, asserted: bool) {
+        if asserted && !self.inter.nmi_line {
+            self.inter.nmi_latched = true;
+        }
+        self.inter.nmi_line = asserted;
+    }
+
+    /// Whether arithmetic should currently be done in decimal mode
+    #[inline]
+    pub(crate) fn decimal_active(&self) -> bool {
+        self.regset.decimal_mode() && self.variant.has_decimal_mode()
+    }
+
     pub(crate) fn i(&self) -> Option<&Instruction> {
         self.i.as_ref()
     }
@@ -266,96 +873,284 @@ impl Cpu {
         self.i.as_mut()
     }
 
+    pub(crate) fn micro(&self) -> &MicroState {
+        &self.micro
+    }
+
+    pub(crate) fn micro_mut(&mut self) -> &mut MicroState {
+        &mut self.micro
+    }
+
+    #[cfg(test)]
     pub(crate) fn i_decode_and_set(&mut self, opc: Opcode, optional_operand: Operand) {
-        self.i = Some(Instruction::decode_by(opc));
+        self.i = Some(Instruction::decode_for(self.variant, opc));
         self.i.as_mut().unwrap().operand = optional_operand;
     }
 }
 
-impl std::fmt::Debug for Cpu {
+impl<B: Bus + ?Sized> std::fmt::Debug for Cpu<B> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Cpu")
             .field("regset", &self.regset)
             .field("time", &self.time)
             .field("inter", &self.inter)
-            .field("is_attached", &self.bus_conn.is_some())
+            .field("irq_sources_asserting", &self.irq_sources_asserting())
+            .field("is_attached", &self.bus.attached())
             .field("curr_i", &self.i)
+            .field("illegal_opcode_policy", &self.illegal_opcode_policy)
+            .field("rom_write_policy", &self.rom_write_policy)
+            .field("magic_constants", &self.magic_constants)
+            .field("variant", &self.variant)
+            .field("run_state", &self.run_state)
             .finish()
     }
 }
 
 impl Cpu {
     /// **new()** - Creates a new instance of a cpu with its default
-    /// field values
+    /// field values, which is not connected to any bus yet
     pub fn new() -> Self {
-        Self {
-            regset: RegisterSet::new(),
-            time: Timings {
-                elapsed: 0,
-                residual: 0,
-            },
-            inter: InterruptHandling {
-                pending_irq: false,
-                pending_nmi: false,
-            },
-            bus_conn: None,
-            i: None,
-        }
+        Cpu::with_bus(SharedBus::default())
     }
 
     /// **new_connected()** - Creates a new instance of a cpu with a bus interface
     /// supported
-    pub(crate) fn new_connected(bus_conn: Option<Rc<RefCell<dyn CommunicationInterface>>>) -> Self {
+    pub(crate) fn new_connected(bus_conn: Option<Rc<RefCell<dyn Bus>>>) -> Self {
+        let mut cpu = Cpu::new();
+        if let Some(conn) = bus_conn {
+            cpu.connect_to(conn);
+        }
+        cpu
+    }
+
+    pub fn new_custompc(custom_prog_counter: Address) -> Self {
         Self {
-            bus_conn,
+            regset: RegisterSet::new_custompc(custom_prog_counter),
             ..Cpu::new()
         }
     }
 
-    pub(crate) fn new_custompc(custom_prog_counter: Address) -> Self {
+    /// **new_variant()** - Creates a new instance of a cpu which
+    /// emulates the given chip
+    pub fn new_variant(variant: CpuVariant) -> Self {
         Self {
-            regset: RegisterSet::new_custompc(custom_prog_counter),
+            variant,
             ..Cpu::new()
         }
     }
 
+    /// **connect()** - Connects the cpu to a shared bus, providing a
+    /// context for read and write operations.
+    /// The bus is also handed the IRQ line, so that its devices
+    /// can register their own sources.
+    pub fn connect_to(&mut self, conn: Rc<RefCell<dyn Bus>>) {
+        if !self.bus.attached() {
+            self.bus = SharedBus::new(conn);
+            self.bus.connect_irq(&self.irq_line);
+        }
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    /// **with_bus()** - Creates a new instance of a cpu which owns the
+    /// given bus. The bus is handed the IRQ line right away.
+    pub fn with_bus(mut bus: B) -> Self {
+        let irq_line = IrqLine::new();
+        let host_irq = irq_line.source("host");
+        bus.connect_irq(&irq_line);
+
+        Self {
+            regset: RegisterSet::new(),
+            time: Timings {
+                elapsed: 0,
+                residual: 0,
+            },
+            inter: InterruptHandling::default(),
+            irq_line,
+            host_irq,
+            i: None,
+            micro: MicroState::default(),
+            illegal_opcode_policy: IllegalOpcodePolicy::default(),
+            rom_write_policy: RomWritePolicy::default(),
+            strict_bus: false,
+            bus_faults: Vec::new(),
+            bus_hooks: Vec::new(),
+            magic_constants: MagicConstants::default(),
+            variant: CpuVariant::default(),
+            run_state: RunState::default(),
+            bus,
+        }
+    }
+
+    /// **with_bus_variant()** - Creates a new instance of a cpu which
+    /// emulates the given chip and owns the given bus
+    pub fn with_bus_variant(variant: CpuVariant, bus: B) -> Self {
+        let mut cpu = Cpu::with_bus(bus);
+        cpu.variant = variant;
+        cpu
+    }
+
+    /// **into_bus()** - Disconnects the cpu and gives its bus back
+    pub fn into_bus(self) -> B {
+        self.bus
+    }
+}
+
+impl<B: Bus + ?Sized> Cpu<B> {
     /// **full_instruction()** - Execute one full instruction
-    pub fn full_instruction(&mut self) {
-        self.clock_cycle();
+    pub fn full_instruction(&mut self) -> Result<(), CpuError> {
+        self.clock_cycle()?;
         while self.time.residual != 0 {
-            self.clock_cycle();
+            self.clock_cycle()?;
         }
+        Ok(())
     }
 
     /// **clock_cycle()** - Perform a single cpu cycle
     ///
-    /// This implementation is not cycle correct, but
-    /// successfully emulates being so. This is done
-    /// by keeping the amount of cycles which have to
-    /// skipped/wasted after each actual instruction
-    /// execution.
-    pub fn clock_cycle(&mut self) {
+    /// The first cycle of each instruction fetches its
+    /// opcode. Every following cycle performs exactly
+    /// one step of the instruction's micro program --
+    /// the bus access (dummy ones included) which the
+    /// hardware does on that very cycle. The instruction
+    /// itself takes effect on its last cycle.
+    ///
+    /// The bus is ticked after the access of each cycle,
+    /// even while the cpu sleeps, and the interrupt lines
+    /// are polled at the end of it. A pending interrupt is
+    /// serviced instead of fetching the next instruction.
+    ///
+    /// If an error occurs while decoding, addressing
+    /// or executing, no clock cycle is counted. A write
+    /// to ROM trapped by the `RomWritePolicy`, as well
+    /// as an unmapped access with a strict bus, is
+    /// reported after its cycle has been completed.
+    ///
+    /// While the cpu is waiting or stopped, cycles
+    /// only pass without anything being executed.
+    pub fn clock_cycle(&mut self) -> Result<(), CpuError> {
+        if self.strict_bus && !self.bus.attached() {
+            return Err(CpuError::BusInterfaceMissing);
+        }
+
         if self.time.residual() == 0 {
-            let opcode = self.fetch();
+            let irq = self.irq_line.asserted();
+            if self.run_state == RunState::Waiting && (irq || self.inter.nmi_latched) {
+                self.run_state = RunState::Running;
+                self.inter.pending_nmi = self.inter.nmi_latched;
+                self.inter.pending_irq = irq && !self.regset.irq_disabled();
+            }
 
-            self.i = Some(Instruction::decode_by(opcode));
-            self.time.residual = self.i.as_ref().unwrap().time;
-            load_operand_curr_i(self);
+            if self.run_state != RunState::Running {
+                *self.time_mut().elapsed_mut() += 1;
+                self.bus.tick();
+                return Ok(());
+            }
 
-            let address = self.i.as_ref().unwrap().amode_fun;
-            if let Ok(amode_output) = address(self) {
-                self.i.as_mut().unwrap().amode_output = amode_output;
+            if self.inter.pending_reset {
+                self.begin_reset();
+            } else if self.inter.pending_nmi || self.inter.pending_irq {
+                self.begin_interrupt();
             } else {
-                panic!("Failed addressing");
+                self.begin_instruction()?;
             }
+        } else if let Some(&op) = self.micro.program().get(self.micro.step()) {
+            self.micro.advance();
+            let done = mos6502_micro_ops::perform(self, op)?;
+            debug_assert_eq!(
+                done,
+                self.time.residual() == 1,
+                "{:?} does not match the timing of {:?}",
+                op,
+                self.i
+            );
+        }
 
-            let execute = self.i.as_ref().unwrap().fun;
-            if let Err(_) = execute(self) {
-                panic!("Failed executing");
+        self.bus.tick();
+        self.time_mut().next();
+        let boundary = self.time.residual() == 0;
+        let irq_requested = self.irq_line.asserted() && !self.regset.irq_disabled();
+        self.inter.poll(irq_requested, boundary);
+
+        if self.bus_faults.is_empty() {
+            return Ok(());
+        }
+
+        let trap_rom = self.rom_write_policy == RomWritePolicy::Trap;
+        let traps = |fault: &BusFault| fault.kind != BusFaultKind::WriteToRom || trap_rom;
+        match self.bus_faults.iter().copied().find(traps) {
+            Some(fault) => {
+                self.bus_faults.retain(|fault| !traps(fault));
+                Err(match fault.kind {
+                    BusFaultKind::WriteToRom => CpuError::WriteToRom {
+                        address: fault.address,
+                        pc: fault.pc,
+                    },
+                    kind => CpuError::BusFault {
+                        address: fault.address,
+                        kind,
+                    },
+                })
             }
+            None => Ok(()),
         }
+    }
 
-        self.time_mut().next();
+    /// **begin_interrupt()** - The first cycle of an interrupt sequence.
+    /// The hardware forces a `brk` into the cpu, but without
+    /// incrementing the program counter and without setting the
+    /// **Break** flag. The vector is chosen later on, so an NMI
+    /// which arrives in the meantime _hijacks_ the sequence.
+    fn begin_interrupt(&mut self) {
+        let kind = if self.inter.pending_nmi { Nmi } else { Irq };
+        self.inter.pending_nmi = false;
+        self.inter.pending_irq = false;
+
+        let loaded_from = self.pc();
+        self.read_byte_as(loaded_from, AccessKind::Dummy);
+
+        let mut i = Instruction::decode_for(self.variant, 0x00);
+        i.loaded_from = loaded_from;
+        self.time.residual = i.time();
+        self.micro = MicroState::new_interrupt(kind);
+        self.i = Some(i);
+    }
+
+    /// **begin_instruction()** - The first cycle of an instruction.
+    /// Fetches and decodes the opcode and prepares the micro program
+    /// for the rest of the cycles.
+    fn begin_instruction(&mut self) -> Result<(), CpuError> {
+        let loaded_from = self.inc_pc();
+        let opcode = self.read_byte_as(loaded_from, AccessKind::Opcode);
+
+        let mut i = Instruction::decode_for(self.variant, opcode);
+        if i.illegal() {
+            match self.illegal_opcode_policy {
+                IllegalOpcodePolicy::Trap => {
+                    self.regset.set_prog_counter(loaded_from);
+                    return Err(CpuError::IllegalOpcode {
+                        opcode,
+                        address: loaded_from,
+                    });
+                }
+                // The micro program does the bus accesses, without the writes
+                IllegalOpcodePolicy::Nop => i.fun = InstructionFn::nop,
+                IllegalOpcodePolicy::Execute => {}
+            }
+        }
+
+        i.loaded_from = loaded_from;
+        self.time.residual = i.time();
+        self.micro = MicroState::new(mos6502_micro_ops::program(&i, self.variant));
+        self.i = Some(i);
+
+        // Instructions which take a single cycle are over right away
+        if self.micro.program().is_empty() {
+            let execute = self.i.as_ref().unwrap().fun;
+            execute.call(self)?;
+        }
+
+        Ok(())
     }
 
     /// **inthandle()** - Handles any interrupts of the cpu.
@@ -365,7 +1160,20 @@ impl Cpu {
     /// NMIs cannot be disabled.
     /// In order to allow IRQs, the flag `irq_disabled` in the status
     /// register has to be clear.
+    /// A cpu waiting after `wai` is woken up by any interrupt. If it is
+    /// a masked IRQ, execution simply continues after the `wai`.
+    /// The 65C02 also clears the **Decimal** flag before jumping to the handler.
+    ///
+    /// The interrupt is entered right away, regardless of the current
+    /// cycle. Devices should rather use `set_irq_line()` and
+    /// `set_nmi_line()`, which are polled by `clock_cycle()`.
     pub fn inthandle(&mut self, int: InterruptKind) -> bool {
+        match self.run_state {
+            RunState::Stopped => return false,
+            RunState::Waiting => self.run_state = RunState::Running,
+            RunState::Running => {}
+        }
+
         if int == Irq && self.regset().irq_disabled() {
             return false;
         }
@@ -380,76 +1188,267 @@ impl Cpu {
         let status = self.regset().status();
         self.stk_push(status);
 
+        if self.variant == CpuVariant::Cmos {
+            self.regset_mut().set_decimal_mode(false);
+        }
+
         let (next_address, time) = match int {
-            InterruptKind::Nmi => (0xFFFA, 8),
-            InterruptKind::Irq => (0xFFFE, 7),
+            InterruptKind::Nmi => (NMI_VECTOR, 8),
+            InterruptKind::Irq => (IRQ_VECTOR, 7),
         };
 
-        let new_pc = self.read_word(next_address);
-        *self.regset_mut().set_prog_counter(new_pc);
+        let new_pc = self.read_vector(next_address);
+        self.regset_mut().set_prog_counter(new_pc);
         *self.time_mut().residual_mut() = time;
+        self.micro = MicroState::default();
         true
     }
 
-    /// **reset()** - Performs a reset to the internal state of the cpu
-    /// Among the flags in the status register, only the *UNUSED* flag is set.
-    /// Reset takes some time, so 8 cycles are set to be remaining in the
-    /// internal "clock" of the cpu
+    /// **reset()** - Performs a warm reset of the cpu
     pub fn reset(&mut self) {
-        let mut regset = RegisterSet::new();
-        regset.set_status(0x00);
-        regset.set_prog_counter(0x8000);
-        regset.set_unused(true);
-        self.regset = regset;
-
-        self.time = Timings {
-            residual: 8,
-            elapsed: 0,
-        };
+        self.reset_with(ResetKind::Warm);
     }
 
-    /// **connect()** - Connects the cpu to a bus, providing a context
-    /// for read and write operations.
-    pub(crate) fn connect_to(&mut self, conn: Rc<RefCell<dyn CommunicationInterface>>) {
-        if let None = self.bus_conn {
-            self.bus_conn = Some(conn);
+    /// **reset_with()** - Resets the cpu
+    ///
+    /// The reset sequence itself takes the next 7 cycles -- the
+    /// stack pointer is decremented three times without anything
+    /// being written, the **Interrupt disable** flag is set and
+    /// the program counter is loaded from `RESET_VECTOR`. Whatever
+    /// the cpu has been doing is abandoned, even if it has been
+    /// stopped by `stp`.
+    pub fn reset_with(&mut self, kind: ResetKind) {
+        match kind {
+            ResetKind::Warm => {}
+            ResetKind::PowerOn => {
+                self.regset = RegisterSet {
+                    accumulator: 0,
+                    x_index: 0,
+                    y_index: 0,
+                    stk_ptr: 0,
+                    prog_counter: 0,
+                    status: 0,
+                };
+                self.time = Timings::default();
+            }
+            ResetKind::PowerOnRandom { seed } => {
+                // xorshift64 -- the seed must not be zero
+                let mut state = seed | 1;
+                let mut random = || {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                };
+                let [a, x, y, s, p, pcl, pch, _] = random().to_le_bytes();
+                self.regset = RegisterSet {
+                    accumulator: a,
+                    x_index: x,
+                    y_index: y,
+                    stk_ptr: s,
+                    prog_counter: Word::from_le_bytes([pcl, pch]),
+                    status: p,
+                };
+                self.time = Timings::default();
+            }
         }
+
+        self.regset.set_unused(true);
+        self.run_state = RunState::Running;
+        self.inter = InterruptHandling {
+            pending_reset: true,
+            ..InterruptHandling::default()
+        };
+        self.i = None;
+        self.micro = MicroState::default();
+        self.time.residual = 0;
+    }
+
+    /// **begin_reset()** - The first cycle of the reset sequence
+    fn begin_reset(&mut self) {
+        self.inter.pending_reset = false;
+
+        let loaded_from = self.pc();
+        self.read_byte_as(loaded_from, AccessKind::Dummy);
+
+        let mut i = Instruction::decode_for(self.variant, 0x00);
+        i.loaded_from = loaded_from;
+        self.time.residual = i.time();
+        self.micro = MicroState::new_reset();
+        self.i = Some(i);
     }
 }
 
-impl Cpu {
-    ///
-    /// TODO FIXME:
-    /// Consider returning Option<> or Result<> in order to
-    /// give better return "code" to the called whether the
-    /// value was actually 0 or an error occured. Same
-    /// think goes for `writ_byte()` and the other wrapper
-    /// functions.
+impl<B: Bus + ?Sized> Cpu<B> {
     ///
     /// **read_byte()** - Initiates a read request to the interface
-    /// **if one is present**
-    pub fn read_byte(&self, address: Address) -> Byte {
-        if let Some(bus) = &self.bus_conn {
-            if let Some(data) = (*bus.borrow()).read(address) {
-                return data;
+    /// **if one is present**. Gives 0 if the read fails -- see
+    /// `try_read_byte()` for telling the two apart. With a strict
+    /// bus an unmapped read is recorded as a fault.
+    pub fn read_byte(&mut self, address: Address) -> Byte {
+        self.read_byte_as(address, AccessKind::Data)
+    }
+
+    /// **read_byte_as()** - Reads a byte, reporting the access as
+    /// the given kind to the bus hooks
+    pub(crate) fn read_byte_as(&mut self, address: Address, kind: AccessKind) -> Byte {
+        let data = match self.bus.read(address) {
+            Some(data) => {
+                if self.strict_bus && !self.bus.is_mapped(address) {
+                    self.record_fault(address, data, BusFaultKind::UnmappedRead);
+                }
+                data
+            }
+            None => {
+                if self.strict_bus {
+                    self.record_fault(address, 0, BusFaultKind::UnmappedRead);
+                }
+                0
             }
+        };
+
+        if !self.bus_hooks.is_empty() {
+            self.call_bus_hooks(address, data, AccessDirection::Read, kind);
         }
-        0
+        data
     }
 
     ///
     /// **writ_byte()** - Initiates a write request to the interface
     /// **if one is present**
-    pub fn writ_byte(&self, address: Address, data: Byte) {
-        if let Some(bus) = &self.bus_conn {
-            return (*bus.borrow_mut()).write(address, data);
+    pub fn writ_byte(&mut self, address: Address, data: Byte) {
+        self.writ_byte_as(address, data, AccessKind::Data);
+    }
+
+    /// **writ_byte_as()** - Writes a byte, reporting the access as
+    /// the given kind to the bus hooks
+    pub(crate) fn writ_byte_as(&mut self, address: Address, data: Byte, kind: AccessKind) {
+        let protected =
+            self.rom_write_policy != RomWritePolicy::Ignore && self.bus.write_protected(address);
+        let unmapped = self.strict_bus && !self.bus.is_mapped(address);
+        self.bus.write(address, data);
+
+        if protected {
+            self.record_fault(address, data, BusFaultKind::WriteToRom);
+        }
+        if unmapped {
+            self.record_fault(address, data, BusFaultKind::UnmappedWrite);
+        }
+        if !self.bus_hooks.is_empty() {
+            self.call_bus_hooks(address, data, AccessDirection::Write, kind);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn call_bus_hooks(
+        &mut self,
+        address: Address,
+        data: Byte,
+        direction: AccessDirection,
+        kind: AccessKind,
+    ) {
+        let access = BusAccess {
+            address,
+            data,
+            direction,
+            kind,
+        };
+        for hook in &mut self.bus_hooks {
+            hook(&access);
+        }
+    }
+
+    /// **add_bus_hook()** - Installs a callback which is called after
+    /// each bus access of the cpu
+    pub fn add_bus_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&BusAccess) + Send + 'static,
+    {
+        self.bus_hooks.push(Box::new(hook));
+    }
+
+    /// **clear_bus_hooks()** - Removes all bus hooks
+    pub fn clear_bus_hooks(&mut self) {
+        self.bus_hooks.clear();
+    }
+
+    fn record_fault(&mut self, address: Address, data: Byte, kind: BusFaultKind) {
+        let pc = self.i.as_ref().map_or(self.pc(), |i| i.loaded_from);
+        self.bus_faults.push(BusFault {
+            address,
+            data,
+            pc,
+            kind,
+        });
+    }
+
+    ///
+    /// **try_read_byte()** - Reads a byte from the interface, failing
+    /// if there is no interface or nothing is mapped at the address
+    pub fn try_read_byte(&mut self, address: Address) -> Result<Byte, CpuError> {
+        if !self.bus.attached() {
+            return Err(CpuError::BusInterfaceMissing);
+        }
+
+        match self.bus.read(address) {
+            Some(data) if self.bus.is_mapped(address) => Ok(data),
+            _ => Err(CpuError::BusFault {
+                address,
+                kind: BusFaultKind::UnmappedRead,
+            }),
+        }
+    }
+
+    ///
+    /// **try_writ_byte()** - Writes a byte to the interface, failing
+    /// if there is no interface or nothing is mapped at the address.
+    /// A write to ROM is handled as by `writ_byte()`.
+    pub fn try_writ_byte(&mut self, address: Address, data: Byte) -> Result<(), CpuError> {
+        if !self.bus.attached() {
+            return Err(CpuError::BusInterfaceMissing);
+        }
+        if !self.bus.is_mapped(address) {
+            return Err(CpuError::BusFault {
+                address,
+                kind: BusFaultKind::UnmappedWrite,
+            });
         }
+
+        self.writ_byte(address, data);
+        Ok(())
+    }
+
+    ///
+    /// **try_read_word()** - Reads two sequential bytes from the
+    /// interface, failing as `try_read_byte()` does
+    pub fn try_read_word(&mut self, address: Address) -> Result<Word, CpuError> {
+        let lo = self.try_read_byte(address)?;
+        let hi = self.try_read_byte(address.wrapping_add(1))?;
+        Ok(Word::from_le_bytes([lo, hi]))
+    }
+
+    /// **peek()** - Reads a byte without any side effects. Neither
+    /// the bus hooks are called, nor are faults recorded.
+    pub fn peek(&self, address: Address) -> Byte {
+        self.bus.peek(address).unwrap_or(0)
+    }
+
+    /// **peek_bank()** - Reads from a given bank of the memory behind an
+    /// address, if the interface supports banking
+    pub fn peek_bank(&self, bank: usize, address: Address) -> Option<Byte> {
+        self.bus.peek_bank(bank, address)
+    }
+
+    /// **take_bus_faults()** - Returns the recorded bus faults and forgets them
+    pub fn take_bus_faults(&mut self) -> Vec<BusFault> {
+        std::mem::take(&mut self.bus_faults)
     }
 
     ///
     /// **read_word()** - Wrapper function for reading two sequential
     /// bytes from the interface **if one is present**.
-    pub fn read_word(&self, address: Address) -> Word {
+    pub fn read_word(&mut self, address: Address) -> Word {
         let lo = self.read_byte(address);
         let hi = self.read_byte(address + 1);
         Word::from_le_bytes([lo, hi])
@@ -458,22 +1457,23 @@ impl Cpu {
     ///
     /// **read_some()** - Reads sequence of bytes from the interface
     pub fn read_some(&self, address: Address, len: u16) -> Vec<Byte> {
-        if let Some(bus) = &self.bus_conn {
-            if let Some(result) = (*bus.borrow()).read_seq(address, len) {
-                return result;
-            }
-        }
-
-        vec![]
+        self.bus.read_seq(address, len).unwrap_or_default()
     }
 
     ///
     /// **fetch()** - Reads a byte from addressing the interface
     /// with the value of PC. After that the PC gets updated.
     #[inline]
-    fn fetch(&mut self) -> Byte {
+    pub(crate) fn fetch(&mut self) -> Byte {
         let pc = self.inc_pc();
-        self.read_byte(pc)
+        self.read_byte_as(pc, AccessKind::Operand)
+    }
+
+    /// **read_vector()** - Reads the address stored in an interrupt vector
+    pub(crate) fn read_vector(&mut self, vector: Address) -> Word {
+        let lo = self.read_byte_as(vector, AccessKind::Vector);
+        let hi = self.read_byte_as(vector.wrapping_add(1), AccessKind::Vector);
+        Word::from_le_bytes([lo, hi])
     }
 }
 
@@ -483,7 +1483,13 @@ impl Default for Cpu {
     }
 }
 
-pub trait CommunicationInterface {
+///
+/// Bus
+///
+/// Anything the cpu can be connected to -- it can be read from
+/// and written to.
+///
+pub trait Bus {
     /// **read()** - Read the value of a given address from the interface
     fn read(&self, address: Address) -> Option<Byte>;
 
@@ -493,6 +1499,124 @@ pub trait CommunicationInterface {
     /// **read_seq()** - Read sequental from `address` to `address + len`
     /// (or less if the limit is exceeded)
     fn read_seq(&self, address: Address, len: u16) -> Option<Vec<Byte>>;
+
+    /// **peek()** - Read the value of a given address without any side
+    /// effects, for debuggers and disassemblers. Interfaces whose reads
+    /// have side effects should override it.
+    fn peek(&self, address: Address) -> Option<Byte> {
+        self.read(address)
+    }
+
+    /// **peek_bank()** - Read from a given bank of the memory behind an
+    /// address, without any side effects. Interfaces without banking
+    /// do not support it.
+    fn peek_bank(&self, _bank: usize, _address: Address) -> Option<Byte> {
+        None
+    }
+
+    /// **write_protected()** - Whether writes to an address are ignored
+    fn write_protected(&self, _address: Address) -> bool {
+        false
+    }
+
+    /// **is_mapped()** - Whether anything answers at an address.
+    /// Reads from unmapped addresses give whatever the bus floats to.
+    fn is_mapped(&self, _address: Address) -> bool {
+        true
+    }
+
+    /// **connect_irq()** - Called when a cpu gets connected to the interface.
+    /// Devices which request interrupts should register their own
+    /// sources of the line here.
+    fn connect_irq(&mut self, _line: &IrqLine) {}
+
+    /// **attached()** - Whether there is anything behind the interface.
+    /// Only a `SharedBus` may be detached.
+    fn attached(&self) -> bool {
+        true
+    }
+
+    /// **tick()** - Called once for every cycle of the cpu, after its
+    /// bus access, so that devices run in lockstep with it
+    fn tick(&mut self) {}
+}
+
+///
+/// SharedBus
+///
+/// An adapter for buses which are shared between the cpu and the rest
+/// of the program. Reads from a detached `SharedBus` give nothing and
+/// writes to it are ignored.
+///
+/// Unlike the cpu which owns its bus, a cpu connected to a shared bus
+/// cannot be sent to another thread.
+///
+#[derive(Default)]
+pub struct SharedBus {
+    conn: Option<Rc<RefCell<dyn Bus>>>,
+}
+
+impl SharedBus {
+    pub fn new(conn: Rc<RefCell<dyn Bus>>) -> Self {
+        Self { conn: Some(conn) }
+    }
+
+    /// **conn()** - The shared bus behind the adapter, if attached
+    pub fn conn(&self) -> Option<&Rc<RefCell<dyn Bus>>> {
+        self.conn.as_ref()
+    }
+}
+
+impl Bus for SharedBus {
+    fn read(&self, address: Address) -> Option<Byte> {
+        self.conn.as_ref()?.borrow().read(address)
+    }
+
+    fn write(&mut self, address: Address, data: Byte) {
+        if let Some(conn) = &self.conn {
+            conn.borrow_mut().write(address, data);
+        }
+    }
+
+    fn read_seq(&self, address: Address, len: u16) -> Option<Vec<Byte>> {
+        self.conn.as_ref()?.borrow().read_seq(address, len)
+    }
+
+    fn peek(&self, address: Address) -> Option<Byte> {
+        self.conn.as_ref()?.borrow().peek(address)
+    }
+
+    fn peek_bank(&self, bank: usize, address: Address) -> Option<Byte> {
+        self.conn.as_ref()?.borrow().peek_bank(bank, address)
+    }
+
+    fn write_protected(&self, address: Address) -> bool {
+        self.conn
+            .as_ref()
+            .is_some_and(|conn| conn.borrow().write_protected(address))
+    }
+
+    fn is_mapped(&self, address: Address) -> bool {
+        self.conn
+            .as_ref()
+            .is_some_and(|conn| conn.borrow().is_mapped(address))
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        if let Some(conn) = &self.conn {
+            conn.borrow_mut().connect_irq(line);
+        }
+    }
+
+    fn attached(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    fn tick(&mut self) {
+        if let Some(conn) = &self.conn {
+            conn.borrow_mut().tick();
+        }
+    }
 }
 
 const RAM_SIZE: usize = 0xffff + 1;
@@ -504,14 +1628,20 @@ pub struct MainBus {
 }
 
 impl MainBus {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             mem: vec![0x00; RAM_SIZE],
         }
     }
 }
 
-impl CommunicationInterface for MainBus {
+impl Default for MainBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for MainBus {
     fn read(&self, address: Address) -> Option<Byte> {
         let addr = usize::from(address);
         if addr >= self.mem.len() {
@@ -539,7 +1669,7 @@ impl CommunicationInterface for MainBus {
             }
         }
 
-        if result.len() > 0 {
+        if !result.is_empty() {
             return Some(result);
         }
         None
@@ -547,33 +1677,39 @@ impl CommunicationInterface for MainBus {
 }
 
 ///
-/// **make_instr!** - Makes a new Instruction instance with
-/// given field values
+/// **make_instr!** - Makes the `OpcodeInfo` of an instruction
+/// with given field values
 ///
 #[macro_export]
 macro_rules! make_instr {
     ($p_amode: ident, $p_fun: ident, $p_time: expr, $p_mnemonic: literal, $p_size: expr) => {
-        Instruction {
+        OpcodeInfo {
+            opcode: 0x00,
+            mnemonic: $p_mnemonic,
             amode: $p_amode,
-            amode_fun: to_fun($p_amode),
-            fun: $p_fun,
-            time: $p_time,
-            mnemonic: String::from($p_mnemonic),
             size: $p_size,
-            amode_output: AddressingOutput::NotExecuted,
-            operand: None,
-            loaded_from: 0x0000,
+            cycles: $p_time,
+            page_cross_penalty: OpcodeInfo::has_page_cross_penalty(
+                $p_amode,
+                Operation::of(InstructionFn::$p_fun),
+            ),
+            illegal: false,
+            operation: Operation::of(InstructionFn::$p_fun),
+            fun: InstructionFn::$p_fun,
         }
     };
 }
 
 ///
-/// **make_illegal!** - Makes a new illegal instruction instance
-/// with given field values
+/// **make_illegal!** - Makes the `OpcodeInfo` of an illegal
+/// instruction with given field values
 ///
 macro_rules! make_illegal {
-    () => {
-        unimplemented!();
+    ($p_amode: ident, $p_fun: ident, $p_time: expr, $p_mnemonic: literal, $p_size: expr) => {
+        OpcodeInfo {
+            illegal: true,
+            ..make_instr!($p_amode, $p_fun, $p_time, $p_mnemonic, $p_size)
+        }
     };
 }
 
@@ -585,7 +1721,7 @@ pub enum AddressingOutput {
     NotExecuted,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AddressingMode {
     Imp,
     Imm,
@@ -599,6 +1735,9 @@ pub enum AddressingMode {
     Iny,
     Inx,
     Rel,
+    Izp,
+    Iax,
+    Zpr,
 }
 
 impl Display for AddressingMode {
@@ -607,6 +1746,128 @@ impl Display for AddressingMode {
     }
 }
 
+///
+/// Operation
+///
+/// What an instruction does with what it addresses. Together with
+/// the addressing mode it decides which bus accesses the instruction
+/// makes on each of its cycles.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Write,
+    ReadModifyWrite,
+    Branch,
+    Jump,
+    Call,
+    Return,
+    ReturnFromInterrupt,
+    Break,
+    Push,
+    Pull,
+    Wait,
+}
+
+impl Operation {
+    /// **of()** - The kind of operation the given instruction
+    /// function performs
+    pub(crate) const fn of(fun: InstructionFn) -> Self {
+        use InstructionFn::*;
+        use Operation::*;
+
+        match fun {
+            sta | stx | sty | stz | sax | ahx | tas | shx | shy => Write,
+            asl | lsr | rol | ror | inc | dec | slo | rla | sre | rra | dcp | isc | trb | tsb => {
+                ReadModifyWrite
+            }
+            rmb0 | rmb1 | rmb2 | rmb3 | rmb4 | rmb5 | rmb6 | rmb7 => ReadModifyWrite,
+            smb0 | smb1 | smb2 | smb3 | smb4 | smb5 | smb6 | smb7 => ReadModifyWrite,
+            bcc | bcs | beq | bmi | bne | bpl | bvc | bvs | bra => Branch,
+            bbr0 | bbr1 | bbr2 | bbr3 | bbr4 | bbr5 | bbr6 | bbr7 => Branch,
+            bbs0 | bbs1 | bbs2 | bbs3 | bbs4 | bbs5 | bbs6 | bbs7 => Branch,
+            jmp => Jump,
+            jsr => Call,
+            rts => Return,
+            rti => ReturnFromInterrupt,
+            brk => Break,
+            pha | php | phx | phy => Push,
+            pla | plp | plx | ply => Pull,
+            wai | stp => Wait,
+            _ => Read,
+        }
+    }
+}
+
+///
+/// OpcodeInfo
+///
+/// Everything about an opcode which is known before it is
+/// executed. There is one per opcode in `NMOS_OPCODES` and
+/// `CMOS_OPCODES`, and every decoded `Instruction` refers to
+/// one of them.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub amode: AddressingMode,
+
+    /// **size** - The number of bytes, the opcode included
+    pub size: u16,
+
+    /// **cycles** - The number of cycles, without any penalties
+    pub cycles: u8,
+
+    /// **page_cross_penalty** - Whether an extra cycle is taken
+    /// when the indexed address (or the branch target) is on
+    /// another page
+    pub page_cross_penalty: bool,
+
+    /// **illegal** - Whether the opcode is an undocumented one
+    pub illegal: bool,
+
+    /// **operation** - What the instruction does with what it addresses
+    pub operation: Operation,
+
+    pub(crate) fun: InstructionFn,
+}
+
+impl OpcodeInfo {
+    /// **has_page_cross_penalty()** - Only reads skip the cycle which
+    /// fixes the high byte of an indexed address on the same page
+    const fn has_page_cross_penalty(amode: AddressingMode, operation: Operation) -> bool {
+        use AddressingMode::*;
+
+        matches!(
+            (operation, amode),
+            (Operation::Read, Abx | Aby | Iny) | (Operation::Branch, Rel)
+        )
+    }
+
+    /// **table()** - Builds the table of all opcodes out of `decode`
+    const fn table(cmos: bool) -> [OpcodeInfo; 256] {
+        let mut table = [Instruction::decode_nmos(0x00); 256];
+        let mut opcode = 0;
+        while opcode < table.len() {
+            table[opcode] = if cmos {
+                Instruction::decode_cmos(opcode as Opcode)
+            } else {
+                Instruction::decode_nmos(opcode as Opcode)
+            };
+            table[opcode].opcode = opcode as Opcode;
+            opcode += 1;
+        }
+        table
+    }
+}
+
+/// The opcodes of the NMOS 6502, which the Ricoh 2A03 shares
+pub static NMOS_OPCODES: [OpcodeInfo; 256] = OpcodeInfo::table(false);
+
+/// The opcodes of the WDC 65C02
+pub static CMOS_OPCODES: [OpcodeInfo; 256] = OpcodeInfo::table(true);
+
 ///
 /// Instruction
 ///
@@ -619,15 +1880,16 @@ impl Display for AddressingMode {
 /// which might be a value (Fetched) or a address
 /// (Absolute Address).
 ///
+#[derive(Copy, Clone)]
 pub struct Instruction {
     ///
     /// Meta information
-    amode_fun: AddressingModeFn,
-    amode: AddressingMode,
+    info: &'static OpcodeInfo,
+
+    /// **fun** - The function which is executed. It is the one
+    /// of the opcode, unless the `IllegalOpcodePolicy` says
+    /// otherwise.
     fun: InstructionFn,
-    time: u8,
-    mnemonic: String,
-    size: u16,
 
     /// Instruction context
     ///
@@ -662,39 +1924,58 @@ impl Instruction {
         self.amode_output = amode_output;
     }
 
+    pub(crate) fn set_operand(&mut self, operand: Operand) {
+        self.operand = operand;
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_loaded_from(&mut self, loaded_from: Address) {
+        self.loaded_from = loaded_from;
+    }
+
     pub(crate) fn amode(&self) -> AddressingMode {
-        self.amode
+        self.info.amode
     }
 
-    pub(crate) fn operand(&self) -> Operand {
+    pub fn operand(&self) -> Operand {
         self.operand
     }
 
-    pub(crate) fn set_operand(&mut self, operand: Operand) {
-        self.operand = operand;
+    pub fn loaded_from(&self) -> Address {
+        self.loaded_from
     }
 
-    pub(crate) fn loaded_from(&self) -> Address {
-        self.loaded_from
+    /// **info()** - What is known about the opcode of this instruction
+    pub fn info(&self) -> &'static OpcodeInfo {
+        self.info
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.info.mnemonic
     }
 
-    pub(crate) fn mnemonic(&self) -> String {
-        self.mnemonic.clone()
+    pub fn size(&self) -> u16 {
+        self.info.size
     }
 
-    pub(crate) fn size(&self) -> u16 {
-        self.size
+    pub fn time(&self) -> u8 {
+        self.info.cycles
     }
-    pub(crate) fn time(&self) -> u8 {
-        self.time
+
+    pub fn opcode(&self) -> Opcode {
+        self.info.opcode
     }
 
-    pub(crate) fn fun(&self) -> InstructionFn {
-        self.fun
+    pub fn illegal(&self) -> bool {
+        self.info.illegal
     }
 
-    pub(crate) fn amode_fun(&self) -> AddressingModeFn {
-        self.amode_fun
+    pub fn operation(&self) -> Operation {
+        self.info.operation
+    }
+
+    pub(crate) fn fun(&self) -> InstructionFn {
+        self.fun
     }
 }
 
@@ -702,35 +1983,33 @@ impl Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
         use AddressingMode::*;
 
-        let details = match self.amode {
+        let details = match self.info.amode {
             Imm => ("#", ""),
-            Imp | Zp0 | Abs | Rel => ("", ""),
-            Ind => ("(", ")"),
+            Imp | Zp0 | Abs | Rel | Zpr => ("", ""),
+            Ind | Izp => ("(", ")"),
             Abx | Zpx => ("", ", X"),
             Aby | Zpy => ("", ", Y"),
             Iny => ("(", "), Y"),
-            Inx => ("(", ", X)"),
+            Inx | Iax => ("(", ", X)"),
         };
 
         let address = format!("{:#6x?}\t", self.loaded_from);
-        let operand = match self.operand {
-            Some(num) => format!("{:#4x?}", num),
-            None => String::new(),
+        let operand = match (self.info.amode, self.operand) {
+            (Zpr, Some(num)) => format!("{:#4x?}, {:#4x?}", num & 0x00FF, num >> 8),
+            (_, Some(num)) => format!("{:#4x?}", num),
+            (_, None) => String::new(),
         };
-        let addressing_mode = format!("\t; {}", self.amode);
+        let addressing_mode = format!("\t; {}", self.info.amode);
 
-        write!(
+        writeln!(
             f,
-            "{}",
-            format!(
-                "{address}{mnemonic}\t{prefix}{operand}{suffix}{addressing_mode}\n",
-                address = address,
-                mnemonic = self.mnemonic,
-                prefix = details.0,
-                operand = operand,
-                suffix = details.1,
-                addressing_mode = addressing_mode
-            )
+            "{address}{mnemonic}\t{prefix}{operand}{suffix}{addressing_mode}",
+            address = address,
+            mnemonic = self.info.mnemonic,
+            prefix = details.0,
+            operand = operand,
+            suffix = details.1,
+            addressing_mode = addressing_mode
         )
     }
 }
@@ -738,36 +2017,20 @@ impl Display for Instruction {
 impl Debug for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("Instruction")
-            .field("time", &self.time)
-            .field("mnemonic", &self.mnemonic)
-            .field("size", &self.size)
+            .field("time", &self.info.cycles)
+            .field("mnemonic", &self.info.mnemonic)
+            .field("size", &self.info.size)
             .finish()
     }
 }
 
 impl PartialEq for Instruction {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
-            && self.mnemonic == other.mnemonic
-            && self.size == other.size
-            && self.fun as usize == other.fun as usize
-            && self.amode_fun as usize == other.amode_fun as usize
-    }
-}
-
-impl Clone for Instruction {
-    fn clone(&self) -> Self {
-        Self {
-            amode_fun: self.amode_fun,
-            amode: self.amode,
-            fun: self.fun,
-            time: self.time,
-            mnemonic: self.mnemonic.clone(),
-            size: self.size,
-            operand: self.operand.clone(),
-            amode_output: self.amode_output,
-            loaded_from: self.loaded_from,
-        }
+        self.info.cycles == other.info.cycles
+            && self.info.mnemonic == other.info.mnemonic
+            && self.info.size == other.info.size
+            && self.fun == other.fun
+            && self.info.amode == other.info.amode
     }
 }
 
@@ -775,20 +2038,48 @@ pub type Operand = Option<Word>;
 pub type InstructionMeta = (Operand, AddressingOutput);
 
 impl Instruction {
+    /// **new()** - An instruction of the given opcode, which is not
+    /// loaded from anywhere yet
+    pub fn new(info: &'static OpcodeInfo) -> Self {
+        Self {
+            info,
+            fun: info.fun,
+            operand: None,
+            amode_output: AddressingOutput::NotExecuted,
+            loaded_from: 0x0000,
+        }
+    }
+
     ///
     /// **decode_by** - Match a given opcode value to its corresponding
-    /// instruction.
+    /// instruction on the NMOS 6502.
+    ///
+    #[cfg(test)]
+    pub(crate) fn decode_by(opcode: Byte) -> Instruction {
+        Instruction::new(&NMOS_OPCODES[usize::from(opcode)])
+    }
+
+    ///
+    /// **decode_for** - Match a given opcode value to its corresponding
+    /// instruction on the given cpu variant.
+    ///
+    pub(crate) fn decode_for(variant: CpuVariant, opcode: Byte) -> Instruction {
+        Instruction::new(&variant.opcodes()[usize::from(opcode)])
+    }
+
+    ///
+    /// **decode_nmos** - The entry of an opcode in `NMOS_OPCODES`.
     ///
     /// The table is filled according to [this](https://www.masswerk.at/6502/6502_instruction_set.html) resource.
     ///
-    /// **NB:** Illegal opcodes are not supported as of now
+    /// Undocumented opcodes are decoded with the addressing mode, size and timing
+    /// they have on the NMOS 6502 and are marked as `illegal`. Whether they are
+    /// actually executed is decided by the cpu's `IllegalOpcodePolicy`.
     ///
-    pub(crate) fn decode_by(opcode: Byte) -> Instruction {
-        // use crate::mos6502_addressing_modes::*;
-        // use crate::mos6502_intruction_set::*;
+    const fn decode_nmos(opcode: Opcode) -> OpcodeInfo {
         use AddressingMode::*;
 
-        return match opcode {
+        match opcode {
             // opcode => make_instr! (
             //              addr_mode,
             //              instruction,
@@ -798,173 +2089,420 @@ impl Instruction {
             //          )
             0x00 => make_instr!(Imp, brk, 7, "brk", 1),
             0x01 => make_instr!(Inx, ora, 6, "ora", 2),
+            0x02 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x03 => make_illegal!(Inx, slo, 8, "slo", 2),
+            0x04 => make_illegal!(Zp0, nop, 3, "nop", 2),
             0x05 => make_instr!(Zp0, ora, 3, "ora", 2),
             0x06 => make_instr!(Zp0, asl, 5, "asl", 2),
+            0x07 => make_illegal!(Zp0, slo, 5, "slo", 2),
             0x08 => make_instr!(Imp, php, 3, "php", 1),
             0x09 => make_instr!(Imm, ora, 2, "ora", 2),
             0x0A => make_instr!(Imp, asl, 2, "asl", 1),
+            0x0B => make_illegal!(Imm, anc, 2, "anc", 2),
+            0x0C => make_illegal!(Abs, nop, 4, "nop", 3),
             0x0D => make_instr!(Abs, ora, 4, "ora", 3),
             0x0E => make_instr!(Abs, asl, 6, "asl", 3),
+            0x0F => make_illegal!(Abs, slo, 6, "slo", 3),
 
             0x10 => make_instr!(Rel, bpl, 2 /* or 3 */, "bpl", 2),
             0x11 => make_instr!(Iny, ora, 5, "ora", 2),
+            0x12 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x13 => make_illegal!(Iny, slo, 8, "slo", 2),
+            0x14 => make_illegal!(Zpx, nop, 4, "nop", 2),
             0x15 => make_instr!(Zpx, ora, 4, "ora", 2),
             0x16 => make_instr!(Zpx, asl, 6, "asl", 2),
+            0x17 => make_illegal!(Zpx, slo, 6, "slo", 2),
             0x18 => make_instr!(Imp, clc, 2, "clc", 1),
             0x19 => make_instr!(Aby, ora, 4, "ora", 3),
+            0x1A => make_illegal!(Imp, nop, 2, "nop", 1),
+            0x1B => make_illegal!(Aby, slo, 7, "slo", 3),
+            0x1C => make_illegal!(Abx, nop, 4, "nop", 3),
             0x1D => make_instr!(Abx, ora, 4, "ora", 3),
             0x1E => make_instr!(Abx, asl, 7, "asl", 3),
+            0x1F => make_illegal!(Abx, slo, 7, "slo", 3),
 
             0x20 => make_instr!(Abs, jsr, 6, "jsr", 3),
             0x21 => make_instr!(Inx, and, 6, "and", 2),
+            0x22 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x23 => make_illegal!(Inx, rla, 8, "rla", 2),
             0x24 => make_instr!(Zp0, bit, 3, "bit", 2),
             0x25 => make_instr!(Zp0, and, 3, "and", 2),
             0x26 => make_instr!(Zp0, rol, 5, "rol", 2),
+            0x27 => make_illegal!(Zp0, rla, 5, "rla", 2),
             0x28 => make_instr!(Imp, plp, 4, "plp", 1),
             0x29 => make_instr!(Imm, and, 2, "and", 2),
             0x2A => make_instr!(Imp, rol, 2, "rol", 1),
+            0x2B => make_illegal!(Imm, anc, 2, "anc", 2),
             0x2C => make_instr!(Abs, bit, 4, "bit", 3),
             0x2D => make_instr!(Abs, and, 4, "and", 3),
             0x2E => make_instr!(Abs, rol, 6, "rol", 3),
+            0x2F => make_illegal!(Abs, rla, 6, "rla", 3),
 
             0x30 => make_instr!(Rel, bmi, 2 /* or 3 */, "bmi", 2),
             0x31 => make_instr!(Iny, and, 5, "and", 2),
+            0x32 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x33 => make_illegal!(Iny, rla, 8, "rla", 2),
+            0x34 => make_illegal!(Zpx, nop, 4, "nop", 2),
             0x35 => make_instr!(Zpx, and, 4, "and", 2),
             0x36 => make_instr!(Zpx, rol, 6, "rol", 2),
+            0x37 => make_illegal!(Zpx, rla, 6, "rla", 2),
             0x38 => make_instr!(Imp, sec, 2, "sec", 1),
             0x39 => make_instr!(Aby, and, 4, "and", 3),
+            0x3A => make_illegal!(Imp, nop, 2, "nop", 1),
+            0x3B => make_illegal!(Aby, rla, 7, "rla", 3),
+            0x3C => make_illegal!(Abx, nop, 4, "nop", 3),
             0x3D => make_instr!(Abx, and, 4, "and", 3),
             0x3E => make_instr!(Abx, rol, 7, "rol", 3),
+            0x3F => make_illegal!(Abx, rla, 7, "rla", 3),
 
             0x40 => make_instr!(Imp, rti, 6, "rti", 1),
             0x41 => make_instr!(Inx, eor, 6, "eor", 2),
+            0x42 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x43 => make_illegal!(Inx, sre, 8, "sre", 2),
+            0x44 => make_illegal!(Zp0, nop, 3, "nop", 2),
             0x45 => make_instr!(Zp0, eor, 3, "eor", 2),
             0x46 => make_instr!(Zp0, lsr, 5, "lsr", 2),
+            0x47 => make_illegal!(Zp0, sre, 5, "sre", 2),
             0x48 => make_instr!(Imp, pha, 3, "pha", 1),
             0x49 => make_instr!(Imm, eor, 2, "eor", 2),
             0x4A => make_instr!(Imp, lsr, 2, "lsr", 1),
+            0x4B => make_illegal!(Imm, alr, 2, "alr", 2),
             0x4C => make_instr!(Abs, jmp, 3, "jmp", 3),
             0x4D => make_instr!(Abs, eor, 4, "eor", 3),
             0x4E => make_instr!(Abs, lsr, 6, "lsr", 3),
+            0x4F => make_illegal!(Abs, sre, 6, "sre", 3),
 
             0x50 => make_instr!(Rel, bvc, 2 /* or 3 */, "bvc", 2),
             0x51 => make_instr!(Iny, eor, 5, "eor", 2),
+            0x52 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x53 => make_illegal!(Iny, sre, 8, "sre", 2),
+            0x54 => make_illegal!(Zpx, nop, 4, "nop", 2),
             0x55 => make_instr!(Zpx, eor, 4, "eor", 2),
             0x56 => make_instr!(Zpx, lsr, 6, "lsr", 2),
+            0x57 => make_illegal!(Zpx, sre, 6, "sre", 2),
             0x58 => make_instr!(Imp, cli, 2, "cli", 1),
             0x59 => make_instr!(Aby, eor, 4, "eor", 3),
+            0x5A => make_illegal!(Imp, nop, 2, "nop", 1),
+            0x5B => make_illegal!(Aby, sre, 7, "sre", 3),
+            0x5C => make_illegal!(Abx, nop, 4, "nop", 3),
             0x5D => make_instr!(Abx, eor, 4, "eor", 3),
             0x5E => make_instr!(Abx, lsr, 7, "lsr", 3),
+            0x5F => make_illegal!(Abx, sre, 7, "sre", 3),
 
             0x60 => make_instr!(Imp, rts, 6, "rts", 1),
             0x61 => make_instr!(Inx, adc, 6, "adc", 2),
+            0x62 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x63 => make_illegal!(Inx, rra, 8, "rra", 2),
+            0x64 => make_illegal!(Zp0, nop, 3, "nop", 2),
             0x65 => make_instr!(Zp0, adc, 3, "adc", 2),
             0x66 => make_instr!(Zp0, ror, 5, "ror", 2),
+            0x67 => make_illegal!(Zp0, rra, 5, "rra", 2),
             0x68 => make_instr!(Imp, pla, 4, "pla", 1),
             0x69 => make_instr!(Imm, adc, 2, "adc", 2),
             0x6A => make_instr!(Imp, ror, 2, "ror", 1),
+            0x6B => make_illegal!(Imm, arr, 2, "arr", 2),
             0x6C => make_instr!(Ind, jmp, 5, "jmp", 3),
             0x6D => make_instr!(Abs, adc, 4, "adc", 3),
-            0x6E => make_instr!(Abx, ror, 7, "ror", 3),
+            0x6E => make_instr!(Abs, ror, 6, "ror", 3),
+            0x6F => make_illegal!(Abs, rra, 6, "rra", 3),
 
             0x70 => make_instr!(Rel, bvs, 2 /* or 3 */, "bvs", 2),
             0x71 => make_instr!(Iny, adc, 5, "adc", 2),
+            0x72 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x73 => make_illegal!(Iny, rra, 8, "rra", 2),
+            0x74 => make_illegal!(Zpx, nop, 4, "nop", 2),
             0x75 => make_instr!(Zpx, adc, 4, "adc", 2),
             0x76 => make_instr!(Zpx, ror, 6, "ror", 2),
+            0x77 => make_illegal!(Zpx, rra, 6, "rra", 2),
             0x78 => make_instr!(Imp, sei, 2, "sei", 1),
             0x79 => make_instr!(Aby, adc, 4, "adc", 3),
+            0x7A => make_illegal!(Imp, nop, 2, "nop", 1),
+            0x7B => make_illegal!(Aby, rra, 7, "rra", 3),
+            0x7C => make_illegal!(Abx, nop, 4, "nop", 3),
             0x7D => make_instr!(Abx, adc, 4, "adc", 3),
-            0x7E => make_instr!(Abs, ror, 6, "ror", 6),
+            0x7E => make_instr!(Abx, ror, 7, "ror", 3),
+            0x7F => make_illegal!(Abx, rra, 7, "rra", 3),
 
+            0x80 => make_illegal!(Imm, nop, 2, "nop", 2),
             0x81 => make_instr!(Inx, sta, 6, "sta", 2),
+            0x82 => make_illegal!(Imm, nop, 2, "nop", 2),
+            0x83 => make_illegal!(Inx, sax, 6, "sax", 2),
             0x84 => make_instr!(Zp0, sty, 3, "sty", 2),
             0x85 => make_instr!(Zp0, sta, 3, "sta", 2),
             0x86 => make_instr!(Zp0, stx, 3, "stx", 2),
+            0x87 => make_illegal!(Zp0, sax, 3, "sax", 2),
             0x88 => make_instr!(Imp, dey, 2, "dey", 1),
+            0x89 => make_illegal!(Imm, nop, 2, "nop", 2),
             0x8A => make_instr!(Imp, txa, 2, "txa", 1),
+            0x8B => make_illegal!(Imm, xaa, 2, "xaa", 2),
             0x8C => make_instr!(Abs, sty, 4, "sty", 3),
             0x8D => make_instr!(Abs, sta, 4, "sta", 3),
             0x8E => make_instr!(Abs, stx, 4, "stx", 3),
+            0x8F => make_illegal!(Abs, sax, 4, "sax", 3),
 
             0x90 => make_instr!(Rel, bcc, 2 /* or 3 */, "bcc", 2),
             0x91 => make_instr!(Iny, sta, 6, "sta", 2),
+            0x92 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0x93 => make_illegal!(Iny, ahx, 6, "ahx", 2),
             0x94 => make_instr!(Zpx, sty, 4, "sty", 2),
             0x95 => make_instr!(Zpx, sta, 4, "sta", 2),
             0x96 => make_instr!(Zpy, stx, 4, "stx", 2),
+            0x97 => make_illegal!(Zpy, sax, 4, "sax", 2),
             0x98 => make_instr!(Imp, tya, 2, "tya", 1),
             0x99 => make_instr!(Aby, sta, 5, "sta", 3),
             0x9A => make_instr!(Imp, txs, 2, "txs", 1),
+            0x9B => make_illegal!(Aby, tas, 5, "tas", 3),
+            0x9C => make_illegal!(Abx, shy, 5, "shy", 3),
             0x9D => make_instr!(Abx, sta, 5, "sta", 3),
+            0x9E => make_illegal!(Aby, shx, 5, "shx", 3),
+            0x9F => make_illegal!(Aby, ahx, 5, "ahx", 3),
 
             0xA0 => make_instr!(Imm, ldy, 2, "ldy", 2),
             0xA1 => make_instr!(Inx, lda, 6, "lda", 2),
             0xA2 => make_instr!(Imm, ldx, 2, "ldx", 2),
+            0xA3 => make_illegal!(Inx, lax, 6, "lax", 2),
             0xA4 => make_instr!(Zp0, ldy, 3, "ldy", 2),
             0xA5 => make_instr!(Zp0, lda, 3, "lda", 2),
-            0xA6 => make_instr!(Zp0, ldx, 3, "lda", 2),
+            0xA6 => make_instr!(Zp0, ldx, 3, "ldx", 2),
+            0xA7 => make_illegal!(Zp0, lax, 3, "lax", 2),
             0xA8 => make_instr!(Imp, tay, 2, "tay", 1),
             0xA9 => make_instr!(Imm, lda, 2, "lda", 2),
             0xAA => make_instr!(Imp, tax, 2, "tax", 1),
+            0xAB => make_illegal!(Imm, lxa, 2, "lxa", 2),
             0xAC => make_instr!(Abs, ldy, 4, "ldy", 3),
             0xAD => make_instr!(Abs, lda, 4, "lda", 3),
             0xAE => make_instr!(Abs, ldx, 4, "ldx", 3),
+            0xAF => make_illegal!(Abs, lax, 4, "lax", 3),
 
             0xB0 => make_instr!(Rel, bcs, 2 /* or 3 */, "bcs", 2),
             0xB1 => make_instr!(Iny, lda, 5, "lda", 2),
+            0xB2 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0xB3 => make_illegal!(Iny, lax, 5, "lax", 2),
             0xB4 => make_instr!(Zpx, ldy, 4, "ldy", 2),
             0xB5 => make_instr!(Zpx, lda, 4, "lda", 2),
             0xB6 => make_instr!(Zpy, ldx, 4, "ldx", 2),
+            0xB7 => make_illegal!(Zpy, lax, 4, "lax", 2),
             0xB8 => make_instr!(Imp, clv, 2, "clv", 1),
             0xB9 => make_instr!(Aby, lda, 4, "lda", 3),
             0xBA => make_instr!(Imp, tsx, 2, "tsx", 1),
+            0xBB => make_illegal!(Aby, las, 4, "las", 3),
             0xBC => make_instr!(Abx, ldy, 4, "ldy", 3),
             0xBD => make_instr!(Abx, lda, 4, "lda", 3),
             0xBE => make_instr!(Aby, ldx, 4, "ldx", 3),
+            0xBF => make_illegal!(Aby, lax, 4, "lax", 3),
 
             0xC0 => make_instr!(Imm, cpy, 2, "cpy", 2),
             0xC1 => make_instr!(Inx, cmp, 6, "cmp", 2),
+            0xC2 => make_illegal!(Imm, nop, 2, "nop", 2),
+            0xC3 => make_illegal!(Inx, dcp, 8, "dcp", 2),
             0xC4 => make_instr!(Zp0, cpy, 3, "cpy", 2),
             0xC5 => make_instr!(Zp0, cmp, 3, "cmp", 2),
             0xC6 => make_instr!(Zp0, dec, 5, "dec", 2),
+            0xC7 => make_illegal!(Zp0, dcp, 5, "dcp", 2),
             0xC8 => make_instr!(Imp, iny, 2, "iny", 1),
             0xC9 => make_instr!(Imm, cmp, 2, "cmp", 2),
             0xCA => make_instr!(Imp, dex, 2, "dex", 1),
+            0xCB => make_illegal!(Imm, sbx, 2, "sbx", 2),
             0xCC => make_instr!(Abs, cpy, 4, "cpy", 3),
             0xCD => make_instr!(Abs, cmp, 4, "cmp", 3),
             0xCE => make_instr!(Abs, dec, 6, "dec", 3),
+            0xCF => make_illegal!(Abs, dcp, 6, "dcp", 3),
 
             0xD0 => make_instr!(Rel, bne, 2 /* or 3 */, "bne", 2),
             0xD1 => make_instr!(Iny, cmp, 5, "cmp", 2),
+            0xD2 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0xD3 => make_illegal!(Iny, dcp, 8, "dcp", 2),
+            0xD4 => make_illegal!(Zpx, nop, 4, "nop", 2),
             0xD5 => make_instr!(Zpx, cmp, 4, "cmp", 2),
             0xD6 => make_instr!(Zpx, dec, 6, "dec", 2),
+            0xD7 => make_illegal!(Zpx, dcp, 6, "dcp", 2),
             0xD8 => make_instr!(Imp, cld, 2, "cld", 1),
             0xD9 => make_instr!(Aby, cmp, 4, "cmp", 3),
+            0xDA => make_illegal!(Imp, nop, 2, "nop", 1),
+            0xDB => make_illegal!(Aby, dcp, 7, "dcp", 3),
+            0xDC => make_illegal!(Abx, nop, 4, "nop", 3),
             0xDD => make_instr!(Abx, cmp, 4, "cmp", 3),
             0xDE => make_instr!(Abx, dec, 7, "dec", 3),
+            0xDF => make_illegal!(Abx, dcp, 7, "dcp", 3),
 
             0xE0 => make_instr!(Imm, cpx, 2, "cpx", 2),
             0xE1 => make_instr!(Inx, sbc, 6, "sbc", 2),
+            0xE2 => make_illegal!(Imm, nop, 2, "nop", 2),
+            0xE3 => make_illegal!(Inx, isc, 8, "isc", 2),
             0xE4 => make_instr!(Zp0, cpx, 3, "cpx", 2),
             0xE5 => make_instr!(Zp0, sbc, 3, "sbc", 2),
             0xE6 => make_instr!(Zp0, inc, 5, "inc", 2),
+            0xE7 => make_illegal!(Zp0, isc, 5, "isc", 2),
             0xE8 => make_instr!(Imp, inx, 2, "inx", 1),
             0xE9 => make_instr!(Imm, sbc, 2, "sbc", 2),
             0xEA => make_instr!(Imp, nop, 2, "nop", 1),
+            0xEB => make_illegal!(Imm, sbc, 2, "sbc", 2),
             0xEC => make_instr!(Abs, cpx, 4, "cpx", 3),
             0xED => make_instr!(Abs, sbc, 4, "sbc", 3),
             0xEE => make_instr!(Abs, inc, 6, "inc", 3),
+            0xEF => make_illegal!(Abs, isc, 6, "isc", 3),
 
             0xF0 => make_instr!(Rel, beq, 2 /* or 3 */, "beq", 2),
             0xF1 => make_instr!(Iny, sbc, 5, "sbc", 2),
+            0xF2 => make_illegal!(Imp, jam, 2, "jam", 1),
+            0xF3 => make_illegal!(Iny, isc, 8, "isc", 2),
+            0xF4 => make_illegal!(Zpx, nop, 4, "nop", 2),
             0xF5 => make_instr!(Zpx, sbc, 4, "sbc", 2),
             0xF6 => make_instr!(Zpx, inc, 6, "inc", 2),
+            0xF7 => make_illegal!(Zpx, isc, 6, "isc", 2),
             0xF8 => make_instr!(Imp, sed, 2, "sed", 1),
             0xF9 => make_instr!(Aby, sbc, 4, "sbc", 3),
+            0xFA => make_illegal!(Imp, nop, 2, "nop", 1),
+            0xFB => make_illegal!(Aby, isc, 7, "isc", 3),
+            0xFC => make_illegal!(Abx, nop, 4, "nop", 3),
             0xFD => make_instr!(Abx, sbc, 4, "sbc", 3),
             0xFE => make_instr!(Abx, inc, 7, "inc", 3),
+            0xFF => make_illegal!(Abx, isc, 7, "isc", 3),
+        }
+    }
 
-            _ => make_illegal!(),
-        };
+    ///
+    /// **decode_cmos** - The entry of an opcode in `CMOS_OPCODES`.
+    ///
+    /// Only the opcodes which differ from the NMOS 6502 are listed here, the
+    /// rest are decoded by `decode_nmos`. The table is filled according to
+    /// [this](http://www.6502.org/tutorials/65c02opcodes.html) resource.
+    ///
+    /// None of the opcodes are illegal -- every one which is not part of the
+    /// instruction set is a `nop` with a fixed size and timing.
+    ///
+    const fn decode_cmos(opcode: Opcode) -> OpcodeInfo {
+        use AddressingMode::*;
+
+        match opcode {
+            0x02 => make_instr!(Imm, nop, 2, "nop", 2),
+            0x03 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x04 => make_instr!(Zp0, tsb, 5, "tsb", 2),
+            0x07 => make_instr!(Zp0, rmb0, 5, "rmb0", 2),
+            0x0B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x0C => make_instr!(Abs, tsb, 6, "tsb", 3),
+            0x0F => make_instr!(Zpr, bbr0, 5, "bbr0", 3),
+
+            0x12 => make_instr!(Izp, ora, 5, "ora", 2),
+            0x13 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x14 => make_instr!(Zp0, trb, 5, "trb", 2),
+            0x17 => make_instr!(Zp0, rmb1, 5, "rmb1", 2),
+            0x1A => make_instr!(Imp, inc, 2, "inc", 1),
+            0x1B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x1C => make_instr!(Abs, trb, 6, "trb", 3),
+            0x1E => make_instr!(Abx, asl, 6 /* or 7 */, "asl", 3),
+            0x1F => make_instr!(Zpr, bbr1, 5, "bbr1", 3),
+
+            0x22 => make_instr!(Imm, nop, 2, "nop", 2),
+            0x23 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x27 => make_instr!(Zp0, rmb2, 5, "rmb2", 2),
+            0x2B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x2F => make_instr!(Zpr, bbr2, 5, "bbr2", 3),
+
+            0x32 => make_instr!(Izp, and, 5, "and", 2),
+            0x33 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x34 => make_instr!(Zpx, bit, 4, "bit", 2),
+            0x37 => make_instr!(Zp0, rmb3, 5, "rmb3", 2),
+            0x3A => make_instr!(Imp, dec, 2, "dec", 1),
+            0x3B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x3C => make_instr!(Abx, bit, 4, "bit", 3),
+            0x3E => make_instr!(Abx, rol, 6 /* or 7 */, "rol", 3),
+            0x3F => make_instr!(Zpr, bbr3, 5, "bbr3", 3),
+
+            0x42 => make_instr!(Imm, nop, 2, "nop", 2),
+            0x43 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x44 => make_instr!(Zp0, nop, 3, "nop", 2),
+            0x47 => make_instr!(Zp0, rmb4, 5, "rmb4", 2),
+            0x4B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x4F => make_instr!(Zpr, bbr4, 5, "bbr4", 3),
+
+            0x52 => make_instr!(Izp, eor, 5, "eor", 2),
+            0x53 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x54 => make_instr!(Zpx, nop, 4, "nop", 2),
+            0x57 => make_instr!(Zp0, rmb5, 5, "rmb5", 2),
+            0x5A => make_instr!(Imp, phy, 3, "phy", 1),
+            0x5B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x5C => make_instr!(Abs, nop, 8, "nop", 3),
+            0x5E => make_instr!(Abx, lsr, 6 /* or 7 */, "lsr", 3),
+            0x5F => make_instr!(Zpr, bbr5, 5, "bbr5", 3),
+
+            0x62 => make_instr!(Imm, nop, 2, "nop", 2),
+            0x63 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x64 => make_instr!(Zp0, stz, 3, "stz", 2),
+            0x67 => make_instr!(Zp0, rmb6, 5, "rmb6", 2),
+            0x6B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x6C => make_instr!(Ind, jmp, 6, "jmp", 3),
+            0x6F => make_instr!(Zpr, bbr6, 5, "bbr6", 3),
+
+            0x72 => make_instr!(Izp, adc, 5, "adc", 2),
+            0x73 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x74 => make_instr!(Zpx, stz, 4, "stz", 2),
+            0x77 => make_instr!(Zp0, rmb7, 5, "rmb7", 2),
+            0x7A => make_instr!(Imp, ply, 4, "ply", 1),
+            0x7B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x7C => make_instr!(Iax, jmp, 6, "jmp", 3),
+            0x7E => make_instr!(Abx, ror, 6 /* or 7 */, "ror", 3),
+            0x7F => make_instr!(Zpr, bbr7, 5, "bbr7", 3),
+
+            0x80 => make_instr!(Rel, bra, 2 /* or 3 */, "bra", 2),
+            0x82 => make_instr!(Imm, nop, 2, "nop", 2),
+            0x83 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x87 => make_instr!(Zp0, smb0, 5, "smb0", 2),
+            0x89 => make_instr!(Imm, bit, 2, "bit", 2),
+            0x8B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x8F => make_instr!(Zpr, bbs0, 5, "bbs0", 3),
+
+            0x92 => make_instr!(Izp, sta, 5, "sta", 2),
+            0x93 => make_instr!(Imp, nop, 1, "nop", 1),
+            0x97 => make_instr!(Zp0, smb1, 5, "smb1", 2),
+            0x9B => make_instr!(Imp, nop, 1, "nop", 1),
+            0x9C => make_instr!(Abs, stz, 4, "stz", 3),
+            0x9E => make_instr!(Abx, stz, 5, "stz", 3),
+            0x9F => make_instr!(Zpr, bbs1, 5, "bbs1", 3),
+
+            0xA3 => make_instr!(Imp, nop, 1, "nop", 1),
+            0xA7 => make_instr!(Zp0, smb2, 5, "smb2", 2),
+            0xAB => make_instr!(Imp, nop, 1, "nop", 1),
+            0xAF => make_instr!(Zpr, bbs2, 5, "bbs2", 3),
+
+            0xB2 => make_instr!(Izp, lda, 5, "lda", 2),
+            0xB3 => make_instr!(Imp, nop, 1, "nop", 1),
+            0xB7 => make_instr!(Zp0, smb3, 5, "smb3", 2),
+            0xBB => make_instr!(Imp, nop, 1, "nop", 1),
+            0xBF => make_instr!(Zpr, bbs3, 5, "bbs3", 3),
+
+            0xC2 => make_instr!(Imm, nop, 2, "nop", 2),
+            0xC3 => make_instr!(Imp, nop, 1, "nop", 1),
+            0xC7 => make_instr!(Zp0, smb4, 5, "smb4", 2),
+            0xCB => make_instr!(Imp, wai, 3, "wai", 1),
+            0xCF => make_instr!(Zpr, bbs4, 5, "bbs4", 3),
+
+            0xD2 => make_instr!(Izp, cmp, 5, "cmp", 2),
+            0xD3 => make_instr!(Imp, nop, 1, "nop", 1),
+            0xD4 => make_instr!(Zpx, nop, 4, "nop", 2),
+            0xD7 => make_instr!(Zp0, smb5, 5, "smb5", 2),
+            0xDA => make_instr!(Imp, phx, 3, "phx", 1),
+            0xDB => make_instr!(Imp, stp, 3, "stp", 1),
+            0xDC => make_instr!(Abs, nop, 4, "nop", 3),
+            0xDF => make_instr!(Zpr, bbs5, 5, "bbs5", 3),
+
+            0xE2 => make_instr!(Imm, nop, 2, "nop", 2),
+            0xE3 => make_instr!(Imp, nop, 1, "nop", 1),
+            0xE7 => make_instr!(Zp0, smb6, 5, "smb6", 2),
+            0xEB => make_instr!(Imp, nop, 1, "nop", 1),
+            0xEF => make_instr!(Zpr, bbs6, 5, "bbs6", 3),
+
+            0xF2 => make_instr!(Izp, sbc, 5, "sbc", 2),
+            0xF3 => make_instr!(Imp, nop, 1, "nop", 1),
+            0xF4 => make_instr!(Zpx, nop, 4, "nop", 2),
+            0xF7 => make_instr!(Zp0, smb7, 5, "smb7", 2),
+            0xFA => make_instr!(Imp, plx, 4, "plx", 1),
+            0xFB => make_instr!(Imp, nop, 1, "nop", 1),
+            0xFC => make_instr!(Abs, nop, 4, "nop", 3),
+            0xFF => make_instr!(Zpr, bbs7, 5, "bbs7", 3),
+
+            _ => Instruction::decode_nmos(opcode),
+        }
     }
 
     pub fn load_address(&self) -> Address {
@@ -982,33 +2520,47 @@ pub struct Asm {
     code: Vec<Instruction>,
 }
 
+#[allow(clippy::result_unit_err)]
 impl Asm {
     pub fn new(code: Vec<Instruction>) -> Self {
         Self { code }
     }
 
-    pub fn from_addr_range(cpu: &mut Cpu, begin_address: Address, limit: u16) -> Asm {
-        let mut code: Vec<Instruction> = Vec::new();
-
-        let end_address = begin_address + limit;
-        let mut address = begin_address;
-        while address < end_address {
-            let opcode = cpu.read_byte(address);
-            let mut i = Instruction::decode_by(opcode);
-            load_operand(cpu, &mut i, address);
-            address += i.size;
-            code.push(i);
-        }
+    /// **from_addr_range()** - Disassembles `limit` bytes from an address.
+    /// The memory is peeked, so that disassembling has no effect on the
+    /// cpu or on the devices behind its bus.
+    pub fn from_addr_range<B: Bus + ?Sized>(
+        cpu: &mut Cpu<B>,
+        begin_address: Address,
+        limit: u16,
+    ) -> Asm {
+        let cpu = &*cpu;
+        disassemble(cpu.variant(), begin_address, limit, |address| {
+            Some(cpu.peek(address))
+        })
+        .unwrap_or_default()
+    }
 
-        Asm { code }
+    /// **from_bank_range()** - Same as `from_addr_range()`, but the
+    /// memory is read from the given bank, as if it was switched in.
+    /// Returns `None` if the bus does not support banking.
+    pub fn from_bank_range<B: Bus + ?Sized>(
+        cpu: &Cpu<B>,
+        bank: usize,
+        begin_address: Address,
+        limit: u16,
+    ) -> Option<Asm> {
+        disassemble(cpu.variant(), begin_address, limit, |address| {
+            cpu.peek_bank(bank, address)
+        })
     }
 
     pub fn code(&self) -> &Vec<Instruction> {
         &self.code
     }
 
-    pub fn stringify_range(
-        cpu: &mut Cpu,
+    pub fn stringify_range<B: Bus + ?Sized>(
+        cpu: &mut Cpu<B>,
         begin_address: Address,
         limit: u16,
     ) -> Result<String, ()> {
@@ -1016,13 +2568,13 @@ impl Asm {
         asm.stringify(true, true)
     }
 
-    pub fn stringify(&self, address_column: bool, addressing_mode: bool) -> Result<String, ()> {
+    pub fn stringify(&self, _address_column: bool, _addressing_mode: bool) -> Result<String, ()> {
         let mut res = String::new();
         for i in self.code.iter() {
             res += &i.to_string();
         }
 
-        if res.len() > 0 {
+        if !res.is_empty() {
             return Ok(res);
         }
 
@@ -1032,7 +2584,7 @@ impl Asm {
 
 const STACK_OFFSET: Address = 0x100;
 
-impl Cpu {
+impl<B: Bus + ?Sized> Cpu<B> {
     fn stk_ptr_inc(&mut self) -> u8 {
         let sptr = self.regset_mut().stk_ptr_mut();
         *sptr = sptr.wrapping_add(1);
@@ -1050,7 +2602,7 @@ impl Cpu {
     pub(crate) fn stk_push(&mut self, data: Byte) {
         let stk_ptr = self.stk_ptr_dec();
         let addr = STACK_OFFSET + Address::from(stk_ptr);
-        self.writ_byte(addr, data);
+        self.writ_byte_as(addr, data, AccessKind::Stack);
     }
 
     pub(crate) fn stk_doublepush(&mut self, data: Word) {
@@ -1063,19 +2615,18 @@ impl Cpu {
     pub(crate) fn stk_pop(&mut self) -> Byte {
         let stk_ptr = self.stk_ptr_inc();
         let addr = STACK_OFFSET + Address::from(stk_ptr);
-        let data = self.read_byte(addr);
-        data
+        self.read_byte_as(addr, AccessKind::Stack)
     }
 
     /// **disassemble()** - Given a beginning address, disassemble `limit` of bytes from memory
     /// matching them to Instruction instances.
     pub fn disassemble(&mut self, begin: Address, limit: Address) -> Option<Asm> {
-        if self.bus_conn.is_none() {
+        if !self.bus.attached() {
             return None;
         }
 
         let asm = Asm::from_addr_range(self, begin, limit);
-        if asm.code.len() > 0 {
+        if !asm.code.is_empty() {
             return Some(asm);
         }
 
@@ -1084,7 +2635,7 @@ impl Cpu {
 
     pub fn print_disassembly(&mut self, begin: Address, limit: Address) {
         if let Some(disassembly) = self.disassemble(begin, limit) {
-            for i in disassembly.code.iter() {}
+            for _i in disassembly.code.iter() {}
         }
     }
 
@@ -1092,12 +2643,12 @@ impl Cpu {
     /// starting from `begin` in memory.
     pub fn load_program(
         &mut self,
-        program: &Vec<Byte>,
+        program: &[Byte],
         begin: Address,
         limit: usize,
         start_it: bool,
     ) -> Result<Address, CpuError> {
-        if self.bus_conn.is_none() {
+        if !self.bus.attached() {
             return Err(CpuError::BusInterfaceMissing);
         }
 
@@ -1126,75 +2677,146 @@ impl Cpu {
     ) -> Result<Address, CpuError> {
         let mut program: Vec<Byte> = Vec::new();
         if let Ok(mut file) = File::open(filename) {
-            if let Ok(_) = file.read_to_end(&mut program) {
+            if file.read_to_end(&mut program).is_ok() {
                 return self.load_program(&program, begin, program.len(), start_it);
             }
         }
 
         Err(CpuError::FailedLoadingProgram)
     }
+
+    /// **load_image()** - Puts the segments of an image in memory. If
+    /// `start_it` is true and the image knows where it starts, the
+    /// program counter is set to its start. Returns the old program
+    /// counter, just like `load_program()`.
+    pub fn load_image(&mut self, image: &Image, start_it: bool) -> Result<Address, CpuError> {
+        if !self.bus.attached() {
+            return Err(CpuError::BusInterfaceMissing);
+        }
+
+        for segment in &image.segments {
+            for (offset, &data) in segment.data.iter().enumerate() {
+                self.writ_byte(segment.address.wrapping_add(offset as Address), data);
+            }
+        }
+
+        let saved_pc = self.pc();
+        if let (true, Some(start)) = (start_it, image.start) {
+            self.regset_mut().set_prog_counter(start);
+        }
+
+        Ok(saved_pc)
+    }
+
+    /// **load_ihex_file()** - Loads an Intel HEX file. See `load_image()`.
+    pub fn load_ihex_file(&mut self, filename: &str, start_it: bool) -> Result<Address, CpuError> {
+        let image = Image::from_ihex(&read_text(filename)?)?;
+        self.load_image(&image, start_it)
+    }
+
+    /// **load_srec_file()** - Loads a Motorola S-record file. See `load_image()`.
+    pub fn load_srec_file(&mut self, filename: &str, start_it: bool) -> Result<Address, CpuError> {
+        let image = Image::from_srec(&read_text(filename)?)?;
+        self.load_image(&image, start_it)
+    }
+
+    /// **load_prg_file()** - Loads a Commodore program file at the
+    /// address in its header. See `load_image()`.
+    pub fn load_prg_file(&mut self, filename: &str, start_it: bool) -> Result<Address, CpuError> {
+        let image = Image::from_prg(&read_bytes(filename)?)?;
+        self.load_image(&image, start_it)
+    }
+
+    /// **load_o65_file()** - Loads an o65 object with its text, data and
+    /// bss segments relocated one after another from `base`. Undefined
+    /// references are resolved against `symbols`. See `load_image()`.
+    pub fn load_o65_file(
+        &mut self,
+        filename: &str,
+        base: Address,
+        symbols: &HashMap<String, Address>,
+        start_it: bool,
+    ) -> Result<Address, CpuError> {
+        let o65 = O65::parse(&read_bytes(filename)?)?;
+        let image = o65.relocate(&o65.bases_at(base), symbols)?;
+        self.load_image(&image, start_it)
+    }
 }
 
-/// **load_operand_curr_i()** - This is called right before
-/// the addressing mode specifics are executed in order
-/// to fetch the required operand into the operand
-/// field in `i`.
-pub fn load_operand_curr_i(cpu: &mut Cpu) {
-    use AddressingMode::*;
+/// **read_text()** - Reads a text file for the loaders
+fn read_text(filename: &str) -> Result<String, CpuError> {
+    std::fs::read_to_string(filename).map_err(|_| CpuError::FailedLoadingProgram)
+}
 
-    if cpu.i.is_none() {
-        return;
+/// **read_bytes()** - Reads a binary file for the loaders
+fn read_bytes(filename: &str) -> Result<Vec<Byte>, CpuError> {
+    std::fs::read(filename).map_err(|_| CpuError::FailedLoadingProgram)
+}
+
+/// **disassemble()** - Decodes the instructions in a range of memory,
+/// as given by `read`
+fn disassemble<F>(variant: CpuVariant, begin_address: Address, limit: u16, read: F) -> Option<Asm>
+where
+    F: Fn(Address) -> Option<Byte>,
+{
+    let mut code: Vec<Instruction> = Vec::new();
+
+    let (mut next, end_address) = window(begin_address, limit);
+    while next < end_address {
+        let address = next as Address;
+        let mut i = Instruction::decode_for(variant, read(address)?);
+        i.operand = read_operand(i.amode(), address, &read)?;
+        i.loaded_from = address;
+        next += u32::from(i.size());
+        code.push(i);
     }
 
-    // The instruction is already loaded since we are looking at it
-    // The reason we are updating this value here and not beforehand
-    // is sa that a support could be provided for `load_operand()`
-    // also a.k.a so that we can load operands for instructions
-    // which are not the current one whici is being executed.
-    let loaded_from: Address = cpu.pc() - 1;
+    Some(Asm { code })
+}
+
+/// **window()** - The first and one past the last address of a range of
+/// `limit` bytes from `begin`, which ends at the top of the address space
+fn window(begin: Address, limit: u16) -> (u32, u32) {
+    let begin = u32::from(begin);
+    (begin, (begin + u32::from(limit)).min(0x10000))
+}
+
+/// **operand_size()** - The number of bytes following the opcode
+fn operand_size(amode: AddressingMode) -> u16 {
+    use AddressingMode::*;
 
-    let num_fetched = match cpu.i.as_ref().unwrap().amode {
+    match amode {
         Imp => 0,
-        Imm | Zp0 | Zpx | Zpy | Inx | Iny | Rel => 1,
-        Abs | Abx | Aby | Ind => 2,
-    };
+        Imm | Zp0 | Zpx | Zpy | Inx | Iny | Rel | Izp => 1,
+        Abs | Abx | Aby | Ind | Iax | Zpr => 2,
+    }
+}
 
-    let operand = match num_fetched {
+/// **read_operand()** - The operand of an instruction with the given
+/// addressing mode, whose opcode is at `address`
+fn read_operand<F>(amode: AddressingMode, address: Address, read: F) -> Option<Operand>
+where
+    F: Fn(Address) -> Option<Byte>,
+{
+    // The operand follows the opcode
+    let operand = match operand_size(amode) {
         0 => None,
-        1 => Some(Word::from(cpu.fetch())),
-        2 => {
-            let lo = cpu.fetch();
-            let hi = cpu.fetch();
+        1 => Some(Word::from(read(address.wrapping_add(1))?)),
+        _ => {
+            let lo = read(address.wrapping_add(1))?;
+            let hi = read(address.wrapping_add(2))?;
             Some(Word::from_le_bytes([lo, hi]))
         }
-        _ => unreachable!("Unknown number of bytes for operand"),
     };
-
-    let i = cpu.i.as_mut().unwrap();
-    i.loaded_from = loaded_from;
-    i.operand = operand;
+    Some(operand)
 }
 
 /// **load_operand()** - For any given instruction (only the addressing mode
 /// is actually of importance here, fetch any operands that the instruction
 /// requires taking into account that the address of the instruction in memory
-/// is also provided.
-pub fn load_operand(cpu: &mut Cpu, i: &mut Instruction, address: Address) {
-    // Store previous state
-    let saved_pc = cpu.pc();
-    // The instruction has already been fetched
-    // so if address is where the instruction is
-    // then address + 1 is where the operand is.
-    *cpu.regset_mut().prog_counter_mut() = address.wrapping_add(1);
-
-    let saved_i = cpu.i.clone();
-    cpu.i.replace(i.clone());
-
-    load_operand_curr_i(cpu);
-
-    i.clone_from(&cpu.i.as_ref().unwrap());
-
-    // Restore previous state
-    cpu.i.clone_from(&saved_i);
-    *cpu.regset_mut().prog_counter_mut() = saved_pc;
+/// is also provided. The memory is peeked, like when disassembling.
+pub fn load_operand<B: Bus + ?Sized>(cpu: &mut Cpu<B>, i: &mut Instruction, address: Address) {
+    let cpu = &*cpu;
+    i.operand = read_operand(i.amode(), address, |address| Some(cpu.peek(address))).flatten();
+    i.loaded_from = address;
 }
@@ -1,14 +1,13 @@
 use crate::mos6502::{
-    Address, AddressingMode::*, AddressingOutput::*, Byte, Cpu, CpuError, RegisterSet, Word,
-    BRK_VECTOR,
+    Address, AddressingMode::*, AddressingOutput::*, Bus, Byte, Cpu, CpuError, CpuVariant,
+    RegisterSet, RunState, Word, BRK_VECTOR,
 };
 
 ///
 /// Instruction set
 /// Legal MOS 6502 instructions
 ///
-
-fn verify_and_fetch(cpu: &mut Cpu) -> Result<Word, CpuError> {
+fn verify_and_fetch<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<Word, CpuError> {
     let i = cpu.i();
     if let Some(i) = i {
         return match i.amode_output() {
@@ -33,7 +32,7 @@ fn page_of(addr: Address) -> u8 {
 /// instructions.
 /// > Add 1 to cycles if branch occurs on same page.
 /// > Add 2 to cycles if branch occurs to different page.
-pub(crate) fn do_branch(cpu: &mut Cpu, next_address: Address) {
+pub(crate) fn do_branch<B: Bus + ?Sized>(cpu: &mut Cpu<B>, next_address: Address) {
     let prog_counter = cpu.regset().prog_counter();
     let cycles_inc = if page_of(next_address) == page_of(prog_counter) {
         1
@@ -53,6 +52,59 @@ pub(crate) fn do_compare(regs: &mut RegisterSet, lhs: Byte, rhs: Byte) {
     regs.set_negative(diff & 0x80 > 0);
 }
 
+/// Performs a binary-coded decimal addition the way the NMOS 6502 does.
+/// The result and the carry are the decimal ones, but the flags are not:
+/// **Zero** is taken from the binary sum, while **Negative** and **Overflow**
+/// come from the intermediate sum after the low nibble has been adjusted
+/// and before the high nibble is.
+///
+/// See [this](http://www.6502.org/tutorials/decimal_mode.html) tutorial,
+/// Appendix A for the exact sequence.
+pub(crate) fn do_add_decimal(regs: &mut RegisterSet, value: Byte) {
+    let accumulator = regs.accumulator();
+    let carry = u16::from(regs.carry());
+    let binary = u16::from(accumulator) + u16::from(value) + carry;
+
+    let mut lo = u16::from(accumulator & 0x0F) + u16::from(value & 0x0F) + carry;
+    if lo >= 0x0A {
+        lo = ((lo + 0x06) & 0x0F) + 0x10;
+    }
+
+    let mut sum = u16::from(accumulator & 0xF0) + u16::from(value & 0xF0) + lo;
+    let overflowed = !(accumulator ^ value) & (accumulator ^ sum as u8) & 0x80;
+    regs.set_zero(binary & 0x00FF == 0);
+    regs.set_negative((sum & 0x80) > 0);
+    regs.set_overflowed(overflowed > 0);
+
+    if sum >= 0xA0 {
+        sum += 0x60;
+    }
+
+    regs.set_carry(sum > 0xFF);
+    regs.set_accumulator((sum & 0x00FF) as u8);
+}
+
+/// Performs a binary-coded decimal subtraction the way the NMOS 6502 does.
+/// Only the value stored in the accumulator is affected -- all of the flags
+/// are the same as the ones of the binary subtraction, so they have to be
+/// set by the caller.
+pub(crate) fn do_sub_decimal(regs: &mut RegisterSet, value: Byte) -> Byte {
+    let accumulator = regs.accumulator();
+    let borrow = 1 - i16::from(regs.carry());
+
+    let mut lo = i16::from(accumulator & 0x0F) - i16::from(value & 0x0F) - borrow;
+    if lo < 0 {
+        lo = ((lo - 0x06) & 0x0F) - 0x10;
+    }
+
+    let mut diff = i16::from(accumulator & 0xF0) - i16::from(value & 0xF0) + lo;
+    if diff < 0 {
+        diff -= 0x60;
+    }
+
+    (diff & 0x00FF) as u8
+}
+
 /// Add Memory to Accumulator with Carry
 ///
 /// `A = A + M + C`
@@ -62,10 +114,26 @@ pub(crate) fn do_compare(regs: &mut RegisterSet, lhs: Byte, rhs: Byte) {
 /// **Zero:** If the result is 0\
 /// **Overflow:** If the result has made a _sign_ overflowed
 ///
-pub fn adc(cpu: &mut Cpu) -> Result<(), CpuError> {
-    let fetched = verify_and_fetch(cpu)?;
+/// When the **Decimal** flag is set, the operands are treated as packed
+/// binary-coded decimal numbers. See `do_add_decimal` for how the flags behave.
+/// The Ricoh 2A03 ignores the flag.
+pub fn adc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let decimal = cpu.decimal_active();
+    do_add(cpu.regset_mut(), fetched, decimal);
 
-    let regs = cpu.regset_mut();
+    Ok(())
+}
+
+/// The arithmetic behind `adc`. Shared with the undocumented
+/// instructions which add to the accumulator.
+pub(crate) fn do_add(regs: &mut RegisterSet, value: Byte, decimal: bool) {
+    if decimal {
+        do_add_decimal(regs, value);
+        return;
+    }
+
+    let fetched = u16::from(value);
     let accumulator_u16 = u16::from(regs.accumulator());
     let tmp: u16 = accumulator_u16 + fetched + u16::from(regs.carry());
     let accumulator = (tmp & 0x00FF) as u8;
@@ -76,15 +144,13 @@ pub fn adc(cpu: &mut Cpu) -> Result<(), CpuError> {
     regs.set_overflowed((!(accumulator_u16 ^ fetched) & (accumulator_u16 ^ tmp) & 0x80) > 0);
 
     regs.set_accumulator(accumulator);
-
-    Ok(())
 }
 
 /// Perform a bitwise "AND" with Memory with Accumulator
 ///
 /// **Zero:** If the resulting accumulator value is 0\
 /// **Negative:** If the resulting accumulator value is a negative number
-pub fn and(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn and<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let a = cpu.regset().accumulator() & fetched;
@@ -103,10 +169,9 @@ pub fn and(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the resulting value is 0\
 /// **Negative:** If the resulting value is a negative number\
 /// **Carry:** The highest bit of the value
-pub fn asl(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn asl<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched_u16 = verify_and_fetch(cpu)?;
     let fetched_shifted_u16 = fetched_u16 << 1;
-    let val = fetched_u16 as u8;
     let val_shifted = (fetched_shifted_u16 & 0x00FF) as u8;
 
     let ii = cpu.i();
@@ -129,7 +194,7 @@ pub fn asl(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on carry clear
-pub fn bcc(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bcc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -141,7 +206,7 @@ pub fn bcc(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on carry set
-pub fn bcs(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bcs<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -153,7 +218,7 @@ pub fn bcs(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on equal
-pub fn beq(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn beq<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -169,12 +234,18 @@ pub fn beq(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** Set to the result of logical "AND" between the accumulator and the memory value\
 /// **Negative:** Set to 7th bit of the memory value \
 /// **Overflowed:** Set to the 6th bit of the memory value
-pub fn bit(cpu: &mut Cpu) -> Result<(), CpuError> {
+///
+/// The immediate form (65C02 only) affects only the **Zero** flag.
+pub fn bit<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)?;
+    let immediate = cpu.i().unwrap().amode() == Imm;
 
     let regs = cpu.regset_mut();
     let a = regs.accumulator();
     regs.set_zero(u16::from(a) & fetched == 0);
+    if immediate {
+        return Ok(());
+    }
     regs.set_negative(fetched & (1 << 7) > 0);
     regs.set_overflowed(fetched & (1 << 6) > 0);
 
@@ -182,7 +253,7 @@ pub fn bit(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on negative result
-pub fn bmi(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bmi<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -194,7 +265,7 @@ pub fn bmi(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on not-equal
-pub fn bne(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bne<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -206,7 +277,7 @@ pub fn bne(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on positive result
-pub fn bpl(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bpl<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -220,7 +291,7 @@ pub fn bpl(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// Force break -- Software Interrup
 ///
 /// The value of the **Interrupt disabled** flag has to be 0 in order for this to execute
-pub fn brk(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn brk<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.inc_pc();
     cpu.regset_mut().set_irq_disabled(true);
 
@@ -230,15 +301,18 @@ pub fn brk(cpu: &mut Cpu) -> Result<(), CpuError> {
     let status = cpu.regset().status();
     cpu.stk_push(status);
     cpu.regset_mut().set_brk(false);
+    if cpu.variant() == CpuVariant::Cmos {
+        cpu.regset_mut().set_decimal_mode(false);
+    }
 
-    let new_pc = cpu.read_word(BRK_VECTOR);
+    let new_pc = cpu.read_vector(BRK_VECTOR);
     cpu.regset_mut().set_prog_counter(new_pc);
 
     Ok(())
 }
 
 /// Branch on overflow clear
-pub fn bvc(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bvc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -250,7 +324,7 @@ pub fn bvc(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Branch on overflow set
-pub fn bvs(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn bvs<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let next_address = verify_and_fetch(cpu)?;
     let regs = cpu.regset();
 
@@ -262,26 +336,26 @@ pub fn bvs(cpu: &mut Cpu) -> Result<(), CpuError> {
 }
 
 /// Clear carry flag
-pub fn clc(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn clc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_carry(false);
     Ok(())
 }
 
 /// Clear decimal mode flag
-pub fn cld(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn cld<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_decimal_mode(false);
     Ok(())
 }
 
 /// Clear interrupt disabled flag
-pub fn cli(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn cli<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_irq_disabled(false);
 
     Ok(())
 }
 
 /// Clear overflow flag
-pub fn clv(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn clv<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_overflowed(false);
 
     Ok(())
@@ -292,7 +366,7 @@ pub fn clv(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the values are equal \
 /// **Negative:** If the accumulator is smaller \
 /// **Carry:** If the accumulator is less or equal
-pub fn cmp(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn cmp<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
     let regs: &mut RegisterSet = cpu.regset_mut();
 
@@ -307,7 +381,7 @@ pub fn cmp(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the values are equal \
 /// **Negative:** If the X index is smaller \
 /// **Carry:** If the X index is less or equal
-pub fn cpx(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn cpx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
     let regs: &mut RegisterSet = cpu.regset_mut();
 
@@ -322,7 +396,7 @@ pub fn cpx(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the values are equal \
 /// **Negative:** If the Y index is smaller \
 /// **Carry:** If the Y index is less or equal
-pub fn cpy(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn cpy<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
     let regs = cpu.regset_mut();
 
@@ -334,19 +408,23 @@ pub fn cpy(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Decrement memory value by one
 ///
+/// The 65C02 can also decrement the accumulator.
+///
 /// **Zero:** If the resuling value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn dec(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn dec<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = (verify_and_fetch(cpu)? as u8).wrapping_sub(1);
     let amode_output = cpu.i().unwrap().amode_output();
 
-    let address = match amode_output {
-        Fetched { value: _, address } => address,
-        AbsoluteAddress(address) => address,
+    match amode_output {
+        Fetched { value: _, address } => cpu.writ_byte(address, fetched),
+        AbsoluteAddress(address) => cpu.writ_byte(address, fetched),
+        ValueOnly(_) => {
+            cpu.regset_mut().set_accumulator(fetched);
+        }
         _ => return Err(CpuError::BadAddressing),
-    };
+    }
 
-    cpu.writ_byte(address, fetched);
     let regs = cpu.regset_mut();
     regs.set_zero(fetched == 0);
     regs.set_negative(fetched & 0x80 > 0);
@@ -359,7 +437,7 @@ pub fn dec(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the resuling value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn dex(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn dex<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let x = cpu.regset().x_index().wrapping_sub(1);
     let regs = cpu.regset_mut();
 
@@ -376,14 +454,14 @@ pub fn dex(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the resuling value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn dey(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn dey<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let y = cpu.regset().y_index().wrapping_sub(1);
     let regs = cpu.regset_mut();
 
     regs.set_zero(y == 0);
     regs.set_negative(y & 0x80 > 0);
 
-    regs.set_x_index(y);
+    regs.set_y_index(y);
 
     Ok(())
 }
@@ -393,7 +471,7 @@ pub fn dey(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the resulting value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn eor(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn eor<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
     let a = cpu.regset().accumulator() ^ fetched;
     let regs = cpu.regset_mut();
@@ -409,19 +487,23 @@ pub fn eor(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Increment memory value by one
 ///
+/// The 65C02 can also increment the accumulator.
+///
 /// **Zero:** If the resuling value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn inc(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn inc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = (verify_and_fetch(cpu)? as u8).wrapping_add(1);
     let amode_output = cpu.i().unwrap().amode_output();
 
-    let address = match amode_output {
-        Fetched { value: _, address } => address,
-        AbsoluteAddress(address) => address,
+    match amode_output {
+        Fetched { value: _, address } => cpu.writ_byte(address, fetched),
+        AbsoluteAddress(address) => cpu.writ_byte(address, fetched),
+        ValueOnly(_) => {
+            cpu.regset_mut().set_accumulator(fetched);
+        }
         _ => return Err(CpuError::BadAddressing),
-    };
+    }
 
-    cpu.writ_byte(address, fetched);
     let regs = cpu.regset_mut();
     regs.set_zero(fetched == 0);
     regs.set_negative(fetched & 0x80 > 0);
@@ -434,7 +516,7 @@ pub fn inc(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the resuling value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn inx(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn inx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let x = cpu.regset().x_index().wrapping_add(1);
     let regs = cpu.regset_mut();
 
@@ -451,7 +533,7 @@ pub fn inx(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the resuling value is equal to 0 \
 /// **Negative:** If the resulting value is negative
-pub fn iny(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn iny<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let y = cpu.regset().y_index().wrapping_add(1);
     let regs = cpu.regset_mut();
 
@@ -466,7 +548,7 @@ pub fn iny(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Jump to a new location
 ///
-pub fn jmp(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn jmp<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let out = cpu.i().unwrap().amode_output();
 
     let next_address = match out {
@@ -484,13 +566,11 @@ pub fn jmp(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// Jump to subroutine
 ///
 /// Same as `jmp`, but the saves the return address first.
-pub fn jsr(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn jsr<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let return_address = cpu.pc() - 1;
     cpu.stk_doublepush(return_address);
 
-    jmp(cpu);
-
-    Ok(())
+    jmp(cpu)
 }
 
 ///
@@ -498,7 +578,7 @@ pub fn jsr(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the value is 0\
 /// **Negative:** If the value is negative
-pub fn lda(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn lda<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let regs = cpu.regset_mut();
@@ -515,7 +595,7 @@ pub fn lda(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the value is 0\
 /// **Negative:** If the value is negative
-pub fn ldx(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn ldx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let regs = cpu.regset_mut();
@@ -532,7 +612,7 @@ pub fn ldx(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the value is 0\
 /// **Negative:** If the value is negative
-pub fn ldy(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn ldy<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let regs = cpu.regset_mut();
@@ -550,10 +630,9 @@ pub fn ldy(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the resuling value is 0\
 /// **Negative:** Always gets set to 0 \
 /// **Carry:** Gets set to value of the lowest bit of the value before shifting
-pub fn lsr(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn lsr<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched_u16 = verify_and_fetch(cpu)?;
     let fetched_shifted_u16 = fetched_u16 >> 1;
-    let val = fetched_u16 as u8;
     let val_shifted = (fetched_shifted_u16 & 0x00FF) as u8;
 
     if let Some(i) = cpu.i() {
@@ -576,13 +655,13 @@ pub fn lsr(cpu: &mut Cpu) -> Result<(), CpuError> {
 
 ///
 /// No operation - do nothing
-pub fn nop(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn nop<B: Bus + ?Sized>(_cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     Ok(())
 }
 
 ///
 /// Perform a bitwise "OR" between the accumulator and a memory value
-pub fn ora(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn ora<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let a = cpu.regset().accumulator() | fetched;
@@ -598,7 +677,7 @@ pub fn ora(cpu: &mut Cpu) -> Result<(), CpuError> {
 
 ///
 /// Push the accumulator on the stack
-pub fn pha(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn pha<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.stk_push(cpu.regset().accumulator());
 
     Ok(())
@@ -608,7 +687,7 @@ pub fn pha(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// Push the status register on the stack
 ///
 /// Set the **Break** flag to true before that
-pub fn php(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn php<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     // Set Break flag and Unused flag to true before pushing
     let status = cpu.regset().status() | (1 << 3) | (1 << 4);
     cpu.stk_push(status);
@@ -621,7 +700,7 @@ pub fn php(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the pulled value is 0
 /// **Negative:** If the pulled value is negative
-pub fn pla(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn pla<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let a = cpu.stk_pop();
     let regs = cpu.regset_mut();
 
@@ -638,7 +717,7 @@ pub fn pla(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// on whether the **Break** flag should be cleared or not before the pulled value is assigned
 /// to the status. In the current implementation, no changes are made between acquiring the value
 /// from the stack and assigning it to the actual status register.
-pub fn plp(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn plp<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let p = cpu.stk_pop();
     let regs = cpu.regset_mut();
     regs.set_status(p);
@@ -651,7 +730,7 @@ pub fn plp(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the value is equal to 0 \
 /// **Negative:** If the value is negative \
 /// **Carry:** 7th bit of the value
-pub fn rol(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn rol<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let val = rol_inner(cpu, fetched);
@@ -666,11 +745,11 @@ pub fn rol(cpu: &mut Cpu) -> Result<(), CpuError> {
         _ => return Err(CpuError::BadAddressing),
     };
 
-    fn rol_inner(cpu: &mut Cpu, fetched: u8) -> u8 {
+    fn rol_inner<B: Bus + ?Sized>(cpu: &mut Cpu<B>, fetched: u8) -> u8 {
         let regs = cpu.regset_mut();
         let carry = regs.carry() as u8;
 
-        let val = fetched << 1 + carry;
+        let val = (fetched << 1) | carry;
 
         regs.set_zero(val == 0);
         regs.set_negative(val & 0x80 > 0);
@@ -687,7 +766,7 @@ pub fn rol(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Zero:** If the value is equal to 0 \
 /// **Negative:** If the value is negative \
 /// **Carry:** If the value is bigger than 255
-pub fn ror(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn ror<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let fetched = verify_and_fetch(cpu)? as u8;
 
     let val = ror_inner(cpu, fetched);
@@ -702,11 +781,11 @@ pub fn ror(cpu: &mut Cpu) -> Result<(), CpuError> {
         _ => return Err(CpuError::BadAddressing),
     };
 
-    fn ror_inner(cpu: &mut Cpu, fetched: u8) -> u8 {
+    fn ror_inner<B: Bus + ?Sized>(cpu: &mut Cpu<B>, fetched: u8) -> u8 {
         let regs = cpu.regset_mut();
         let carry = regs.carry() as u8;
 
-        let val = fetched >> 1 + carry << 7;
+        let val = (fetched >> 1) | (carry << 7);
 
         regs.set_zero(val == 0);
         regs.set_negative(val & 0x80 > 0);
@@ -722,7 +801,7 @@ pub fn ror(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// All values of the status registers **may be** altered since the status register is pulled
 /// from the stack.
-pub fn rti(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn rti<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let loaded_status = cpu.stk_pop();
     let lo = cpu.stk_pop();
     let hi = cpu.stk_pop();
@@ -741,7 +820,7 @@ pub fn rti(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Return from subroutine
 ///
-pub fn rts(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn rts<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let lo = cpu.stk_pop();
     let hi = cpu.stk_pop();
     let loaded_pc = Address::from_le_bytes([lo, hi]);
@@ -760,31 +839,45 @@ pub fn rts(cpu: &mut Cpu) -> Result<(), CpuError> {
 /// **Negative:** If the result is negative \
 /// **Carry:** If the result is bigger than 255 \
 /// **Overflowed:** If the result has overflowed by _sign_
-pub fn sbc(cpu: &mut Cpu) -> Result<(), CpuError> {
-    let fetched = verify_and_fetch(cpu)?;
-    let fetched_inverted = fetched ^ 0x00FF;
+///
+/// When the **Decimal** flag is set, the operands are treated as packed
+/// binary-coded decimal numbers. The flags are set as in binary mode.
+/// The Ricoh 2A03 ignores the flag.
+pub fn sbc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let decimal = cpu.decimal_active();
+    do_sub(cpu.regset_mut(), fetched, decimal);
+
+    Ok(())
+}
+
+/// The arithmetic behind `sbc`. Shared with the undocumented
+/// instructions which subtract from the accumulator.
+pub(crate) fn do_sub(regs: &mut RegisterSet, value: Byte, decimal: bool) {
+    let fetched_inverted = u16::from(value) ^ 0x00FF;
 
-    let regs = cpu.regset_mut();
     let accumulator_u16 = u16::from(regs.accumulator());
     let tmp: u16 = accumulator_u16 + fetched_inverted + u16::from(regs.carry());
-    let accumulator = (tmp & 0x00FF) as u8;
+    let accumulator = if decimal {
+        do_sub_decimal(regs, value)
+    } else {
+        (tmp & 0x00FF) as u8
+    };
 
     regs.set_carry(tmp > 0xFF);
-    regs.set_zero(accumulator == 0);
+    regs.set_zero(tmp & 0x00FF == 0);
     regs.set_negative((tmp & 0x80) > 0);
     regs.set_overflowed(
         (!(accumulator_u16 ^ fetched_inverted) & (accumulator_u16 ^ tmp) & 0x80) > 0,
     );
 
     regs.set_accumulator(accumulator);
-
-    Ok(())
 }
 
 ///
 /// Set carry flag to true
 ///
-pub fn sec(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn sec<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_carry(true);
     Ok(())
 }
@@ -792,7 +885,7 @@ pub fn sec(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Set decimal mode flag to true
 ///
-pub fn sed(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn sed<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_decimal_mode(true);
     Ok(())
 }
@@ -800,7 +893,7 @@ pub fn sed(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Set interrupt disabled flag to true
 ///
-pub fn sei(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn sei<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     cpu.regset_mut().set_irq_disabled(true);
     Ok(())
 }
@@ -808,7 +901,7 @@ pub fn sei(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Store accumulator in memory
 ///
-pub fn sta(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn sta<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let _ = verify_and_fetch(cpu)?;
 
     let a = cpu.regset().accumulator();
@@ -828,7 +921,7 @@ pub fn sta(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Store X index in memory
 ///
-pub fn stx(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn stx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let _ = verify_and_fetch(cpu)?;
 
     let x = cpu.regset().x_index();
@@ -848,7 +941,7 @@ pub fn stx(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// Store Y index in memory
 ///
-pub fn sty(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn sty<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let _ = verify_and_fetch(cpu)?;
 
     let y = cpu.regset().y_index();
@@ -870,7 +963,7 @@ pub fn sty(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the accumulator value is 0\
 /// **Negative:** If the accumulator value is negative
-pub fn tax(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn tax<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let regs = cpu.regset_mut();
     let a = regs.accumulator();
     regs.set_x_index(a);
@@ -885,7 +978,7 @@ pub fn tax(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the accumulator value is 0\
 /// **Negative:** If the accumulator value is negative
-pub fn tay(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn tay<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let regs = cpu.regset_mut();
     let a = regs.accumulator();
     regs.set_y_index(a);
@@ -900,7 +993,7 @@ pub fn tay(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the stack pointer is 0
 /// **Negative:** If the stack pointer is negative
-pub fn tsx(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn tsx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let regs = cpu.regset_mut();
     let stk_ptr = regs.stk_ptr();
     regs.set_x_index(stk_ptr);
@@ -915,7 +1008,7 @@ pub fn tsx(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the X index value is 0
 /// **Negative:** If the X index value is negative
-pub fn txa(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn txa<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let regs = cpu.regset_mut();
     let x = regs.x_index();
     regs.set_accumulator(x);
@@ -930,7 +1023,7 @@ pub fn txa(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the X index value is 0
 /// **Negative:** If the X index value is negative
-pub fn txs(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn txs<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let regs = cpu.regset_mut();
     let x = regs.x_index();
     regs.set_stk_ptr(x);
@@ -943,7 +1036,7 @@ pub fn txs(cpu: &mut Cpu) -> Result<(), CpuError> {
 ///
 /// **Zero:** If the Y index value is 0
 /// **Negative:** If the Y index value is negative
-pub fn tya(cpu: &mut Cpu) -> Result<(), CpuError> {
+pub fn tya<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
     let regs = cpu.regset_mut();
     let y = regs.y_index();
     regs.set_accumulator(y);
@@ -952,3 +1045,642 @@ pub fn tya(cpu: &mut Cpu) -> Result<(), CpuError> {
 
     Ok(())
 }
+
+//
+// Undocumented instructions
+//
+// The NMOS 6502 does not trap the opcodes which are left out of the
+// documented instruction set. Most of them execute two documented
+// instructions at once, because of the way the decoding logic is laid
+// out. The behaviour implemented here follows the
+// ["No More Secrets"](https://csdb.dk/release/?id=198357) document.
+//
+
+/// The address an instruction operating on memory has resolved to
+fn fetched_address<B: Bus + ?Sized>(cpu: &Cpu<B>) -> Result<Address, CpuError> {
+    let i = cpu.i().ok_or(CpuError::CurrentInstructionMissing)?;
+    match i.amode_output() {
+        Fetched { value: _, address } => Ok(address),
+        _ => Err(CpuError::BadAddressing),
+    }
+}
+
+#[inline]
+fn set_zero_negative(regs: &mut RegisterSet, value: Byte) {
+    regs.set_zero(value == 0);
+    regs.set_negative(value & 0x80 > 0);
+}
+
+/// Performs the store of `shx`, `shy`, `ahx` and `tas`. The stored value is
+/// "and"-ed with the high byte of the base address plus one, "or"-ed with the
+/// instruction's `magic`. When indexing crosses a page, the high byte of the
+/// target address gets replaced by the stored value as well.
+fn do_store_and_high<B: Bus + ?Sized>(
+    cpu: &mut Cpu<B>,
+    value: Byte,
+    index: Byte,
+    magic: Byte,
+) -> Result<(), CpuError> {
+    let address = fetched_address(cpu)?;
+    let base = address.wrapping_sub(Address::from(index));
+    let value = value & (page_of(base).wrapping_add(1) | magic);
+
+    let address = if page_of(base) != page_of(address) {
+        Address::from_le_bytes([address as u8, value])
+    } else {
+        address
+    };
+
+    cpu.writ_byte(address, value);
+    Ok(())
+}
+
+///
+/// Halt the cpu (also known as KIL)
+///
+/// The real hardware locks up until it is reset. This is emulated by
+/// executing the same instruction over and over again.
+pub fn jam<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let address = cpu
+        .i()
+        .ok_or(CpuError::CurrentInstructionMissing)?
+        .loaded_from();
+    cpu.regset_mut().set_prog_counter(address);
+
+    Ok(())
+}
+
+///
+/// Shift left a memory value, then "OR" it with the accumulator
+///
+/// `M = M << 1, A = A | M`
+///
+/// **Carry:** The highest bit of the memory value\
+/// **Zero:** If the resulting accumulator value is 0\
+/// **Negative:** If the resulting accumulator value is negative
+pub fn slo<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let shifted = fetched << 1;
+    cpu.writ_byte(address, shifted);
+
+    let regs = cpu.regset_mut();
+    let a = regs.accumulator() | shifted;
+    regs.set_accumulator(a);
+    regs.set_carry(fetched & 0x80 > 0);
+    set_zero_negative(regs, a);
+
+    Ok(())
+}
+
+///
+/// Rotate left a memory value, then "AND" it with the accumulator
+///
+/// `M = M rol 1, A = A & M`
+///
+/// **Carry:** The highest bit of the memory value\
+/// **Zero:** If the resulting accumulator value is 0\
+/// **Negative:** If the resulting accumulator value is negative
+pub fn rla<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let rotated = (fetched << 1) | cpu.regset().carry() as u8;
+    cpu.writ_byte(address, rotated);
+
+    let regs = cpu.regset_mut();
+    let a = regs.accumulator() & rotated;
+    regs.set_accumulator(a);
+    regs.set_carry(fetched & 0x80 > 0);
+    set_zero_negative(regs, a);
+
+    Ok(())
+}
+
+///
+/// Shift right a memory value, then "XOR" it with the accumulator
+///
+/// `M = M >> 1, A = A ^ M`
+///
+/// **Carry:** The lowest bit of the memory value\
+/// **Zero:** If the resulting accumulator value is 0\
+/// **Negative:** If the resulting accumulator value is negative
+pub fn sre<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let shifted = fetched >> 1;
+    cpu.writ_byte(address, shifted);
+
+    let regs = cpu.regset_mut();
+    let a = regs.accumulator() ^ shifted;
+    regs.set_accumulator(a);
+    regs.set_carry(fetched & 0x01 > 0);
+    set_zero_negative(regs, a);
+
+    Ok(())
+}
+
+///
+/// Rotate right a memory value, then add it to the accumulator with carry
+///
+/// `M = M ror 1, A = A + M + C`
+///
+/// The carry used for the addition is the one shifted out of the memory
+/// value. The flags are set as `adc` sets them, decimal mode included.
+pub fn rra<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let rotated = (fetched >> 1) | ((cpu.regset().carry() as u8) << 7);
+    cpu.writ_byte(address, rotated);
+
+    let decimal = cpu.decimal_active();
+    let regs = cpu.regset_mut();
+    regs.set_carry(fetched & 0x01 > 0);
+    do_add(regs, rotated, decimal);
+
+    Ok(())
+}
+
+///
+/// Store the "AND" of the accumulator and the X index in memory
+///
+/// `M = A & X`
+pub fn sax<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let address = fetched_address(cpu)?;
+    let regs = cpu.regset();
+    cpu.writ_byte(address, regs.accumulator() & regs.x_index());
+
+    Ok(())
+}
+
+///
+/// Load both the accumulator and the X index with a memory value
+///
+/// **Zero:** If the value is 0\
+/// **Negative:** If the value is negative
+pub fn lax<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+
+    let regs = cpu.regset_mut();
+    regs.set_accumulator(fetched);
+    regs.set_x_index(fetched);
+    set_zero_negative(regs, fetched);
+
+    Ok(())
+}
+
+///
+/// Decrement a memory value, then compare it with the accumulator
+///
+/// `M = M - 1, A - M`
+///
+/// The flags are set as `cmp` sets them.
+pub fn dcp<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = (verify_and_fetch(cpu)? as u8).wrapping_sub(1);
+    let address = fetched_address(cpu)?;
+    cpu.writ_byte(address, fetched);
+
+    let regs = cpu.regset_mut();
+    do_compare(regs, regs.accumulator(), fetched);
+
+    Ok(())
+}
+
+///
+/// Increment a memory value, then subtract it from the accumulator with borrow
+///
+/// `M = M + 1, A = A - M - (1 - C)`
+///
+/// The flags are set as `sbc` sets them, decimal mode included.
+pub fn isc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = (verify_and_fetch(cpu)? as u8).wrapping_add(1);
+    let address = fetched_address(cpu)?;
+    cpu.writ_byte(address, fetched);
+
+    let decimal = cpu.decimal_active();
+    do_sub(cpu.regset_mut(), fetched, decimal);
+
+    Ok(())
+}
+
+///
+/// "AND" the accumulator with an immediate value, then copy the negative flag into carry
+///
+/// **Zero:** If the resulting value is 0\
+/// **Negative:** If the resulting value is negative\
+/// **Carry:** Same as negative
+pub fn anc<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+
+    let regs = cpu.regset_mut();
+    let a = regs.accumulator() & fetched;
+    regs.set_accumulator(a);
+    set_zero_negative(regs, a);
+    regs.set_carry(a & 0x80 > 0);
+
+    Ok(())
+}
+
+///
+/// "AND" the accumulator with an immediate value, then shift it right
+///
+/// `A = (A & M) >> 1`
+///
+/// **Carry:** The lowest bit of the value before shifting\
+/// **Zero:** If the resulting value is 0\
+/// **Negative:** Always gets set to 0
+pub fn alr<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+
+    let regs = cpu.regset_mut();
+    let anded = regs.accumulator() & fetched;
+    let a = anded >> 1;
+    regs.set_accumulator(a);
+    regs.set_carry(anded & 0x01 > 0);
+    set_zero_negative(regs, a);
+
+    Ok(())
+}
+
+///
+/// "AND" the accumulator with an immediate value, then rotate it right
+///
+/// `A = (A & M) ror 1`
+///
+/// **Zero:** If the resulting value is 0\
+/// **Negative:** If the resulting value is negative\
+/// **Carry:** The 6th bit of the result\
+/// **Overflow:** The 6th bit of the result "XOR"-ed with the 5th one
+///
+/// In decimal mode, the result is additionally adjusted as if it was a
+/// packed binary-coded decimal number and the carry is set accordingly.
+pub fn arr<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let decimal = cpu.decimal_active();
+
+    let regs = cpu.regset_mut();
+    let anded = regs.accumulator() & fetched;
+    let carry = regs.carry() as u8;
+    let mut a = (anded >> 1) | (carry << 7);
+
+    set_zero_negative(regs, a);
+    if decimal {
+        regs.set_overflowed((a ^ anded) & 0x40 > 0);
+
+        if (anded & 0x0F) + (anded & 0x01) > 0x05 {
+            a = (a & 0xF0) | (a.wrapping_add(0x06) & 0x0F);
+        }
+
+        let adjust_hi = u16::from(anded & 0xF0) + u16::from(anded & 0x10) > 0x50;
+        if adjust_hi {
+            a = (a & 0x0F) | (a.wrapping_add(0x60) & 0xF0);
+        }
+        regs.set_carry(adjust_hi);
+    } else {
+        regs.set_carry(a & 0x40 > 0);
+        regs.set_overflowed(((a >> 6) ^ (a >> 5)) & 0x01 > 0);
+    }
+
+    regs.set_accumulator(a);
+
+    Ok(())
+}
+
+///
+/// Subtract an immediate value from the "AND" of the accumulator and the X
+/// index and store the result in the X index (also known as AXS)
+///
+/// `X = (A & X) - M`
+///
+/// The flags are set as `cmp` sets them. The **Decimal** flag has no effect.
+pub fn sbx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+
+    let regs = cpu.regset_mut();
+    let anded = regs.accumulator() & regs.x_index();
+    do_compare(regs, anded, fetched);
+    regs.set_x_index(anded.wrapping_sub(fetched));
+
+    Ok(())
+}
+
+///
+/// "AND" the X index and an immediate value into the accumulator (also known as ANE)
+///
+/// `A = (A | MAGIC) & X & M`
+///
+/// **Unstable:** `MAGIC` depends on the concrete chip and even on its
+/// temperature. It is the cpu's configurable `magic_constants().xaa()`.
+pub fn xaa<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let magic = cpu.magic_constants().xaa();
+
+    let regs = cpu.regset_mut();
+    let a = (regs.accumulator() | magic) & regs.x_index() & fetched;
+    regs.set_accumulator(a);
+    set_zero_negative(regs, a);
+
+    Ok(())
+}
+
+///
+/// Load both the accumulator and the X index with an immediate value
+/// "AND"-ed with the accumulator
+///
+/// `A = X = (A | MAGIC) & M`
+///
+/// **Unstable:** `MAGIC` is the cpu's configurable `magic_constants().lxa()`.
+pub fn lxa<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let magic = cpu.magic_constants().lxa();
+
+    let regs = cpu.regset_mut();
+    let a = (regs.accumulator() | magic) & fetched;
+    regs.set_accumulator(a);
+    regs.set_x_index(a);
+    set_zero_negative(regs, a);
+
+    Ok(())
+}
+
+///
+/// Store the "AND" of the accumulator, the X index and the high byte of the
+/// base address plus one in memory (also known as SHA)
+///
+/// `M = A & X & ((H + 1) | MAGIC)`
+///
+/// **Unstable:** `MAGIC` is the cpu's configurable `magic_constants().ahx()`.
+/// See also `do_store_and_high`.
+pub fn ahx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let regs = cpu.regset();
+    let value = regs.accumulator() & regs.x_index();
+    let magic = cpu.magic_constants().ahx();
+    do_store_and_high(cpu, value, regs.y_index(), magic)
+}
+
+///
+/// Transfer the "AND" of the accumulator and the X index to the stack pointer,
+/// then store it in memory as `ahx` does (also known as SHS)
+///
+/// `S = A & X, M = S & ((H + 1) | MAGIC)`
+///
+/// **Unstable:** `MAGIC` is the cpu's configurable `magic_constants().tas()`.
+/// See also `do_store_and_high`.
+pub fn tas<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let magic = cpu.magic_constants().tas();
+    let regs = cpu.regset_mut();
+    let value = regs.accumulator() & regs.x_index();
+    regs.set_stk_ptr(value);
+
+    let index = regs.y_index();
+    do_store_and_high(cpu, value, index, magic)
+}
+
+///
+/// Store the "AND" of the Y index and the high byte of the base address plus one in memory
+///
+/// `M = Y & ((H + 1) | MAGIC)`
+///
+/// **Unstable:** `MAGIC` is the cpu's configurable `magic_constants().shy()`.
+/// See also `do_store_and_high`.
+pub fn shy<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let regs = cpu.regset();
+    let magic = cpu.magic_constants().shy();
+    do_store_and_high(cpu, regs.y_index(), regs.x_index(), magic)
+}
+
+///
+/// Store the "AND" of the X index and the high byte of the base address plus one in memory
+///
+/// `M = X & ((H + 1) | MAGIC)`
+///
+/// **Unstable:** `MAGIC` is the cpu's configurable `magic_constants().shx()`.
+/// See also `do_store_and_high`.
+pub fn shx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let regs = cpu.regset();
+    let magic = cpu.magic_constants().shx();
+    do_store_and_high(cpu, regs.x_index(), regs.y_index(), magic)
+}
+
+///
+/// Load the accumulator, the X index and the stack pointer with the "AND"
+/// of a memory value and the stack pointer (also known as LAE)
+///
+/// `A = X = S = M & (S | MAGIC)`
+///
+/// **Zero:** If the value is 0\
+/// **Negative:** If the value is negative
+///
+/// **Unstable:** `MAGIC` is the cpu's configurable `magic_constants().las()`.
+pub fn las<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let magic = cpu.magic_constants().las();
+
+    let regs = cpu.regset_mut();
+    let value = (regs.stk_ptr() | magic) & fetched;
+    regs.set_accumulator(value);
+    regs.set_x_index(value);
+    regs.set_stk_ptr(value);
+    set_zero_negative(regs, value);
+
+    Ok(())
+}
+
+//
+// 65C02 instructions
+//
+// Instructions which are only available on the WDC 65C02. The behaviour
+// implemented here follows the W65C02S datasheet.
+//
+
+/// Branch always
+pub fn bra<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let next_address = verify_and_fetch(cpu)?;
+    do_branch(cpu, next_address);
+
+    Ok(())
+}
+
+///
+/// Push the X index on the stack
+pub fn phx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    cpu.stk_push(cpu.regset().x_index());
+
+    Ok(())
+}
+
+///
+/// Push the Y index on the stack
+pub fn phy<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    cpu.stk_push(cpu.regset().y_index());
+
+    Ok(())
+}
+
+///
+/// Pull the X index from the stack
+///
+/// **Zero:** If the pulled value is 0\
+/// **Negative:** If the pulled value is negative
+pub fn plx<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let x = cpu.stk_pop();
+    let regs = cpu.regset_mut();
+
+    regs.set_x_index(x);
+    set_zero_negative(regs, x);
+    Ok(())
+}
+
+///
+/// Pull the Y index from the stack
+///
+/// **Zero:** If the pulled value is 0\
+/// **Negative:** If the pulled value is negative
+pub fn ply<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let y = cpu.stk_pop();
+    let regs = cpu.regset_mut();
+
+    regs.set_y_index(y);
+    set_zero_negative(regs, y);
+    Ok(())
+}
+
+///
+/// Store zero in memory
+///
+pub fn stz<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let address = fetched_address(cpu)?;
+    cpu.writ_byte(address, 0);
+
+    Ok(())
+}
+
+///
+/// Test and reset memory bits with the accumulator
+///
+/// `M = M & !A`
+///
+/// **Zero:** If the logical "AND" between the accumulator and the memory value is 0
+pub fn trb<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let a = cpu.regset().accumulator();
+
+    cpu.writ_byte(address, fetched & !a);
+    cpu.regset_mut().set_zero(fetched & a == 0);
+
+    Ok(())
+}
+
+///
+/// Test and set memory bits with the accumulator
+///
+/// `M = M | A`
+///
+/// **Zero:** If the logical "AND" between the accumulator and the memory value is 0
+pub fn tsb<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let a = cpu.regset().accumulator();
+
+    cpu.writ_byte(address, fetched | a);
+    cpu.regset_mut().set_zero(fetched & a == 0);
+
+    Ok(())
+}
+
+/// Performs `rmb` and `smb` -- sets the given bit of a
+/// zero page value to `value`.
+fn do_modify_bit<B: Bus + ?Sized>(cpu: &mut Cpu<B>, bit: u8, value: bool) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let address = fetched_address(cpu)?;
+    let modified = (fetched & !(1 << bit)) | ((value as u8) << bit);
+    cpu.writ_byte(address, modified);
+
+    Ok(())
+}
+
+/// Performs `bbr` and `bbs` -- branches if the given bit of
+/// a zero page value is equal to `value`.
+fn do_branch_on_bit<B: Bus + ?Sized>(
+    cpu: &mut Cpu<B>,
+    bit: u8,
+    value: bool,
+) -> Result<(), CpuError> {
+    let fetched = verify_and_fetch(cpu)? as u8;
+    let next_address = fetched_address(cpu)?;
+
+    if (fetched & (1 << bit) != 0) == value {
+        do_branch(cpu, next_address);
+    }
+
+    Ok(())
+}
+
+///
+/// **bit_instr!** - Generates an instruction operating
+/// on a concrete bit of a zero page value.
+///
+macro_rules! bit_instr {
+    ($doc: literal, $name: ident, $helper: ident, $n: expr, $value: expr) => {
+        #[doc = $doc]
+        pub fn $name<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+            $helper(cpu, $n, $value)
+        }
+    };
+}
+
+bit_instr!("Reset memory bit 0", rmb0, do_modify_bit, 0, false);
+bit_instr!("Reset memory bit 1", rmb1, do_modify_bit, 1, false);
+bit_instr!("Reset memory bit 2", rmb2, do_modify_bit, 2, false);
+bit_instr!("Reset memory bit 3", rmb3, do_modify_bit, 3, false);
+bit_instr!("Reset memory bit 4", rmb4, do_modify_bit, 4, false);
+bit_instr!("Reset memory bit 5", rmb5, do_modify_bit, 5, false);
+bit_instr!("Reset memory bit 6", rmb6, do_modify_bit, 6, false);
+bit_instr!("Reset memory bit 7", rmb7, do_modify_bit, 7, false);
+
+bit_instr!("Set memory bit 0", smb0, do_modify_bit, 0, true);
+bit_instr!("Set memory bit 1", smb1, do_modify_bit, 1, true);
+bit_instr!("Set memory bit 2", smb2, do_modify_bit, 2, true);
+bit_instr!("Set memory bit 3", smb3, do_modify_bit, 3, true);
+bit_instr!("Set memory bit 4", smb4, do_modify_bit, 4, true);
+bit_instr!("Set memory bit 5", smb5, do_modify_bit, 5, true);
+bit_instr!("Set memory bit 6", smb6, do_modify_bit, 6, true);
+bit_instr!("Set memory bit 7", smb7, do_modify_bit, 7, true);
+
+bit_instr!("Branch on bit 0 reset", bbr0, do_branch_on_bit, 0, false);
+bit_instr!("Branch on bit 1 reset", bbr1, do_branch_on_bit, 1, false);
+bit_instr!("Branch on bit 2 reset", bbr2, do_branch_on_bit, 2, false);
+bit_instr!("Branch on bit 3 reset", bbr3, do_branch_on_bit, 3, false);
+bit_instr!("Branch on bit 4 reset", bbr4, do_branch_on_bit, 4, false);
+bit_instr!("Branch on bit 5 reset", bbr5, do_branch_on_bit, 5, false);
+bit_instr!("Branch on bit 6 reset", bbr6, do_branch_on_bit, 6, false);
+bit_instr!("Branch on bit 7 reset", bbr7, do_branch_on_bit, 7, false);
+
+bit_instr!("Branch on bit 0 set", bbs0, do_branch_on_bit, 0, true);
+bit_instr!("Branch on bit 1 set", bbs1, do_branch_on_bit, 1, true);
+bit_instr!("Branch on bit 2 set", bbs2, do_branch_on_bit, 2, true);
+bit_instr!("Branch on bit 3 set", bbs3, do_branch_on_bit, 3, true);
+bit_instr!("Branch on bit 4 set", bbs4, do_branch_on_bit, 4, true);
+bit_instr!("Branch on bit 5 set", bbs5, do_branch_on_bit, 5, true);
+bit_instr!("Branch on bit 6 set", bbs6, do_branch_on_bit, 6, true);
+bit_instr!("Branch on bit 7 set", bbs7, do_branch_on_bit, 7, true);
+
+///
+/// Wait for interrupt
+///
+/// The cpu sleeps until an interrupt is signalled.
+pub fn wai<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    cpu.set_run_state(RunState::Waiting);
+
+    Ok(())
+}
+
+///
+/// Stop the clock
+///
+/// The cpu sleeps until it is reset.
+pub fn stp<B: Bus + ?Sized>(cpu: &mut Cpu<B>) -> Result<(), CpuError> {
+    cpu.set_run_state(RunState::Stopped);
+
+    Ok(())
+}
@@ -0,0 +1,744 @@
+use crate::mos6502::{
+    AccessKind::{Dummy, Stack, Vector},
+    Address, AddressingMode,
+    AddressingMode::*,
+    AddressingOutput,
+    AddressingOutput::*,
+    Bus, Byte, Cpu, CpuError, CpuVariant, IllegalOpcodePolicy, Instruction, InterruptKind,
+    Operation, Word, BRK_VECTOR, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR,
+};
+
+//
+// Micro operations
+//
+// The 6502 accesses the bus on every single cycle -- either to read or to
+// write a byte. Some of these accesses are useless for the instruction
+// (the so called _dummy_ reads and writes) but are still visible to the
+// devices on the bus.
+//
+// Each instruction is split into a _micro program_ -- the sequence of
+// steps it performs after its opcode has been fetched, one step per cycle.
+// The instruction function itself is called on the last cycle, which is
+// also the one where it reads or writes the memory it addresses.
+//
+// The sequences follow the "6502 Instruction Timing" section of
+// [this](http://nesdev.org/6502_cpu.txt) document.
+//
+
+///
+/// MicroOp
+///
+/// A single cycle of an instruction. Values read along the way are
+/// kept in the latches of `MicroState` -- `address` is the effective
+/// address, `base` is the address before indexing and `data` is the
+/// last value of interest which was read.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MicroOp {
+    /// Fetch a zero page address
+    FetchZeroPage,
+    /// Fetch the low byte of an absolute address
+    FetchAddressLo,
+    /// Fetch the high byte of an absolute address
+    FetchAddressHi,
+    /// Fetch the high byte of an absolute address and add the index
+    /// to its low byte
+    FetchAddressHiIndexed,
+    /// Fetch the high byte of an absolute address and jump to it
+    FetchAddressHiJump,
+    /// Dummy read of the zero page address, then add the index to it
+    IndexZeroPage,
+    /// Dummy read of the last operand byte, then add the X index to the address
+    IndexAbsolute,
+    /// Dummy read of the last operand byte
+    DummyReadOperand,
+    /// Read the low byte of a pointer in the zero page
+    ReadPointerLo,
+    /// Read the high byte of a pointer in the zero page
+    ReadPointerHi,
+    /// Read the high byte of a pointer in the zero page and add the
+    /// Y index to the low byte of the address
+    ReadPointerHiIndexed,
+    /// Read the low byte of the jump target
+    ReadIndirectLo,
+    /// Read the high byte of the jump target and jump to it
+    ReadIndirectHiJump,
+    /// Read from the effective address and execute
+    ReadExecute,
+    /// Read from the effective address and execute, unless indexing has
+    /// crossed a page. The read is then a dummy one from the wrong page.
+    ReadIndexedExecute,
+    /// Dummy read from the address before its high byte is fixed
+    DummyReadUnfixed,
+    /// Read from the effective address and skip the next step, unless
+    /// indexing has crossed a page. The 65C02 then reads the last operand
+    /// byte again instead.
+    ReadIndexedData,
+    /// Read from the effective address
+    ReadData,
+    /// The NMOS cpus write the unmodified value back, the 65C02 reads it again
+    DummyWrite,
+    /// Execute an instruction which writes to the effective address
+    WriteExecute,
+    /// Dummy read of the next byte and execute
+    ImpliedExecute,
+    /// Fetch the immediate value and execute
+    ImmediateExecute,
+    /// Execute an instruction which does the stack access by itself
+    Execute,
+    /// Dummy read of the next byte
+    DummyReadPc,
+    /// Dummy read of the top of the stack
+    DummyReadStack,
+    /// Fetch the branch offset and execute
+    FetchOffsetExecute,
+    /// Dummy read while the branch target is calculated
+    BranchTaken,
+    /// Dummy read from the wrong page while the branch target is fixed
+    BranchPageFix,
+    /// Read the byte following `brk`, which is skipped
+    FetchPadding,
+    /// Push the high byte of the program counter
+    PushPch,
+    /// Push the low byte of the program counter
+    PushPcl,
+    /// Push the status register and pick the interrupt vector
+    PushStatus,
+    /// Read the low byte of the interrupt vector
+    ReadVectorLo,
+    /// Read the high byte of the interrupt vector and jump to it
+    ReadVectorHi,
+    /// Pull the status register
+    PullStatus,
+    /// Pull the low byte of the program counter
+    PullPcl,
+    /// Pull the high byte of the program counter and jump to it
+    PullPch,
+    /// Dummy read of the next byte, then increment the program counter
+    IncrementPc,
+}
+
+use MicroOp::*;
+
+///
+/// MicroState
+///
+/// The progress of the current instruction through its micro program.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct MicroState {
+    program: &'static [MicroOp],
+    step: usize,
+    address: Address,
+    base: Address,
+    data: Byte,
+    /// The hardware interrupt which is being serviced, if any
+    interrupt: Option<InterruptKind>,
+    /// Whether this is the reset sequence
+    reset: bool,
+}
+
+impl MicroState {
+    pub(crate) fn new(program: &'static [MicroOp]) -> Self {
+        Self {
+            program,
+            ..Self::default()
+        }
+    }
+
+    /// The state of an interrupt sequence
+    pub(crate) fn new_interrupt(kind: InterruptKind) -> Self {
+        Self {
+            program: INTERRUPT,
+            interrupt: Some(kind),
+            ..Self::default()
+        }
+    }
+
+    /// The state of the reset sequence. It is the same as the one
+    /// of an interrupt, except for the stack accesses being reads.
+    pub(crate) fn new_reset() -> Self {
+        Self {
+            program: INTERRUPT,
+            reset: true,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn program(&self) -> &'static [MicroOp] {
+        self.program
+    }
+
+    pub(crate) fn step(&self) -> usize {
+        self.step
+    }
+
+    pub(crate) fn advance(&mut self) {
+        self.step += 1;
+    }
+}
+
+//
+// Micro programs
+//
+
+const IMPLIED: &[MicroOp] = &[ImpliedExecute];
+const IMMEDIATE: &[MicroOp] = &[ImmediateExecute];
+
+const ZERO_PAGE_READ: &[MicroOp] = &[FetchZeroPage, ReadExecute];
+const ZERO_PAGE_WRITE: &[MicroOp] = &[FetchZeroPage, WriteExecute];
+const ZERO_PAGE_RMW: &[MicroOp] = &[FetchZeroPage, ReadData, DummyWrite, WriteExecute];
+
+const ZERO_PAGE_INDEXED_READ: &[MicroOp] = &[FetchZeroPage, IndexZeroPage, ReadExecute];
+const ZERO_PAGE_INDEXED_WRITE: &[MicroOp] = &[FetchZeroPage, IndexZeroPage, WriteExecute];
+const ZERO_PAGE_INDEXED_RMW: &[MicroOp] = &[
+    FetchZeroPage,
+    IndexZeroPage,
+    ReadData,
+    DummyWrite,
+    WriteExecute,
+];
+
+const ABSOLUTE_READ: &[MicroOp] = &[FetchAddressLo, FetchAddressHi, ReadExecute];
+const ABSOLUTE_WRITE: &[MicroOp] = &[FetchAddressLo, FetchAddressHi, WriteExecute];
+const ABSOLUTE_RMW: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHi,
+    ReadData,
+    DummyWrite,
+    WriteExecute,
+];
+
+const ABSOLUTE_INDEXED_READ: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHiIndexed,
+    ReadIndexedExecute,
+    ReadExecute,
+];
+const ABSOLUTE_INDEXED_WRITE: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHiIndexed,
+    DummyReadUnfixed,
+    WriteExecute,
+];
+const ABSOLUTE_INDEXED_RMW: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHiIndexed,
+    DummyReadUnfixed,
+    ReadData,
+    DummyWrite,
+    WriteExecute,
+];
+// The shifts and rotates of the 65C02 skip a cycle if no page is crossed
+const ABSOLUTE_INDEXED_SHIFT_CMOS: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHiIndexed,
+    ReadIndexedData,
+    ReadData,
+    DummyWrite,
+    WriteExecute,
+];
+
+const INDEXED_INDIRECT_READ: &[MicroOp] = &[
+    FetchZeroPage,
+    IndexZeroPage,
+    ReadPointerLo,
+    ReadPointerHi,
+    ReadExecute,
+];
+const INDEXED_INDIRECT_WRITE: &[MicroOp] = &[
+    FetchZeroPage,
+    IndexZeroPage,
+    ReadPointerLo,
+    ReadPointerHi,
+    WriteExecute,
+];
+const INDEXED_INDIRECT_RMW: &[MicroOp] = &[
+    FetchZeroPage,
+    IndexZeroPage,
+    ReadPointerLo,
+    ReadPointerHi,
+    ReadData,
+    DummyWrite,
+    WriteExecute,
+];
+
+const INDIRECT_INDEXED_READ: &[MicroOp] = &[
+    FetchZeroPage,
+    ReadPointerLo,
+    ReadPointerHiIndexed,
+    ReadIndexedExecute,
+    ReadExecute,
+];
+const INDIRECT_INDEXED_WRITE: &[MicroOp] = &[
+    FetchZeroPage,
+    ReadPointerLo,
+    ReadPointerHiIndexed,
+    DummyReadUnfixed,
+    WriteExecute,
+];
+const INDIRECT_INDEXED_RMW: &[MicroOp] = &[
+    FetchZeroPage,
+    ReadPointerLo,
+    ReadPointerHiIndexed,
+    DummyReadUnfixed,
+    ReadData,
+    DummyWrite,
+    WriteExecute,
+];
+
+const ZERO_PAGE_INDIRECT_READ: &[MicroOp] =
+    &[FetchZeroPage, ReadPointerLo, ReadPointerHi, ReadExecute];
+const ZERO_PAGE_INDIRECT_WRITE: &[MicroOp] =
+    &[FetchZeroPage, ReadPointerLo, ReadPointerHi, WriteExecute];
+
+const BRANCH: &[MicroOp] = &[FetchOffsetExecute, BranchTaken, BranchPageFix];
+// The zero page value is read twice
+const BRANCH_ON_BIT: &[MicroOp] = &[
+    FetchZeroPage,
+    ReadData,
+    ReadData,
+    FetchOffsetExecute,
+    BranchTaken,
+    BranchPageFix,
+];
+
+const JUMP_ABSOLUTE: &[MicroOp] = &[FetchAddressLo, FetchAddressHiJump];
+const JUMP_INDIRECT: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHi,
+    ReadIndirectLo,
+    ReadIndirectHiJump,
+];
+const JUMP_INDIRECT_CMOS: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHi,
+    DummyReadOperand,
+    ReadIndirectLo,
+    ReadIndirectHiJump,
+];
+const JUMP_INDEXED_INDIRECT: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHi,
+    IndexAbsolute,
+    ReadIndirectLo,
+    ReadIndirectHiJump,
+];
+
+const CALL: &[MicroOp] = &[
+    FetchAddressLo,
+    DummyReadStack,
+    PushPch,
+    PushPcl,
+    FetchAddressHiJump,
+];
+const RETURN: &[MicroOp] = &[DummyReadPc, DummyReadStack, PullPcl, PullPch, IncrementPc];
+const RETURN_FROM_INTERRUPT: &[MicroOp] =
+    &[DummyReadPc, DummyReadStack, PullStatus, PullPcl, PullPch];
+const BREAK: &[MicroOp] = &[
+    FetchPadding,
+    PushPch,
+    PushPcl,
+    PushStatus,
+    ReadVectorLo,
+    ReadVectorHi,
+];
+
+// The opcode fetch of the interrupt sequence is followed by one more
+// dummy read -- the program counter is never incremented
+const INTERRUPT: &[MicroOp] = &[
+    DummyReadPc,
+    PushPch,
+    PushPcl,
+    PushStatus,
+    ReadVectorLo,
+    ReadVectorHi,
+];
+
+const PUSH: &[MicroOp] = &[DummyReadPc, Execute];
+const PULL: &[MicroOp] = &[DummyReadPc, DummyReadStack, Execute];
+const WAIT: &[MicroOp] = &[DummyReadPc, ImpliedExecute];
+
+// The 8 cycle `nop` of the 65C02 (0x5C)
+const NOP_ABSOLUTE_LONG: &[MicroOp] = &[
+    FetchAddressLo,
+    FetchAddressHi,
+    ReadData,
+    ReadData,
+    ReadData,
+    ReadData,
+    ReadExecute,
+];
+
+/// **program()** - The micro program of an instruction on the given cpu variant
+pub(crate) fn program(i: &Instruction, variant: CpuVariant) -> &'static [MicroOp] {
+    use Operation::*;
+
+    match (i.operation(), i.amode()) {
+        (Break, _) => BREAK,
+        (Call, _) => CALL,
+        (Return, _) => RETURN,
+        (ReturnFromInterrupt, _) => RETURN_FROM_INTERRUPT,
+        (Push, _) => PUSH,
+        (Pull, _) => PULL,
+        (Wait, _) => WAIT,
+        (Jump, Abs) => JUMP_ABSOLUTE,
+        (Jump, Ind) if variant == CpuVariant::Cmos => JUMP_INDIRECT_CMOS,
+        (Jump, Ind) => JUMP_INDIRECT,
+        (Jump, Iax) => JUMP_INDEXED_INDIRECT,
+        (Branch, Rel) => BRANCH,
+        (Branch, Zpr) => BRANCH_ON_BIT,
+        (_, Imp) if i.time() == 1 => &[],
+        (_, Imp) => IMPLIED,
+        (_, Imm) => IMMEDIATE,
+        (Read, Abs) if variant == CpuVariant::Cmos && i.opcode() == 0x5C => NOP_ABSOLUTE_LONG,
+        (ReadModifyWrite, Abx)
+            if variant == CpuVariant::Cmos && matches!(i.opcode(), 0x1E | 0x3E | 0x5E | 0x7E) =>
+        {
+            ABSOLUTE_INDEXED_SHIFT_CMOS
+        }
+
+        (Read, Zp0) => ZERO_PAGE_READ,
+        (Write, Zp0) => ZERO_PAGE_WRITE,
+        (ReadModifyWrite, Zp0) => ZERO_PAGE_RMW,
+
+        (Read, Zpx | Zpy) => ZERO_PAGE_INDEXED_READ,
+        (Write, Zpx | Zpy) => ZERO_PAGE_INDEXED_WRITE,
+        (ReadModifyWrite, Zpx | Zpy) => ZERO_PAGE_INDEXED_RMW,
+
+        (Read, Abs) => ABSOLUTE_READ,
+        (Write, Abs) => ABSOLUTE_WRITE,
+        (ReadModifyWrite, Abs) => ABSOLUTE_RMW,
+
+        (Read, Abx | Aby) => ABSOLUTE_INDEXED_READ,
+        (Write, Abx | Aby) => ABSOLUTE_INDEXED_WRITE,
+        (ReadModifyWrite, Abx | Aby) => ABSOLUTE_INDEXED_RMW,
+
+        (Read, Inx) => INDEXED_INDIRECT_READ,
+        (Write, Inx) => INDEXED_INDIRECT_WRITE,
+        (ReadModifyWrite, Inx) => INDEXED_INDIRECT_RMW,
+
+        (Read, Iny) => INDIRECT_INDEXED_READ,
+        (Write, Iny) => INDIRECT_INDEXED_WRITE,
+        (ReadModifyWrite, Iny) => INDIRECT_INDEXED_RMW,
+
+        (Read, Izp) => ZERO_PAGE_INDIRECT_READ,
+        (Write, Izp) => ZERO_PAGE_INDIRECT_WRITE,
+
+        (operation, amode) => unreachable!("No micro program for {:?} in {}", operation, amode),
+    }
+}
+
+/// The index register used by the current addressing mode
+fn index<B: Bus + ?Sized>(cpu: &Cpu<B>, amode: AddressingMode) -> Byte {
+    match amode {
+        Zpy | Aby | Iny => cpu.regset().y_index(),
+        _ => cpu.regset().x_index(),
+    }
+}
+
+#[inline]
+fn page_base(address: Address) -> Address {
+    address & 0xFF00
+}
+
+fn current<B: Bus + ?Sized>(cpu: &Cpu<B>) -> Result<&Instruction, CpuError> {
+    cpu.i().ok_or(CpuError::CurrentInstructionMissing)
+}
+
+fn set_operand<B: Bus + ?Sized>(cpu: &mut Cpu<B>, operand: Word) -> Result<(), CpuError> {
+    let i = cpu.i_mut().ok_or(CpuError::CurrentInstructionMissing)?;
+    i.set_operand(Some(operand));
+    Ok(())
+}
+
+/// Whether the current instruction is an undocumented one which
+/// the cpu only goes through as a `nop`
+fn skipped<B: Bus + ?Sized>(cpu: &Cpu<B>) -> Result<bool, CpuError> {
+    let nop = cpu.illegal_opcode_policy() == IllegalOpcodePolicy::Nop;
+    Ok(nop && current(cpu)?.illegal())
+}
+
+/// Calls the instruction function with the given addressing output
+fn execute<B: Bus + ?Sized>(cpu: &mut Cpu<B>, output: AddressingOutput) -> Result<bool, CpuError> {
+    let i = cpu.i_mut().ok_or(CpuError::CurrentInstructionMissing)?;
+    i.set_amode_output(output);
+    let fun = i.fun();
+    fun.call(cpu)?;
+
+    Ok(true)
+}
+
+/// Pushes to the stack. During a reset the cpu is kept from
+/// writing, so the stack pointer only gets decremented.
+fn push<B: Bus + ?Sized>(cpu: &mut Cpu<B>, data: Byte) {
+    if cpu.micro().reset {
+        cpu.read_byte_as(0x100 | Address::from(cpu.regset().stk_ptr()), Stack);
+        let regs = cpu.regset_mut();
+        regs.set_stk_ptr(regs.stk_ptr().wrapping_sub(1));
+    } else {
+        cpu.stk_push(data);
+    }
+}
+
+/// **perform()** - Perform a single micro operation of the current instruction.
+/// Returns whether the instruction has completed.
+pub(crate) fn perform<B: Bus + ?Sized>(cpu: &mut Cpu<B>, op: MicroOp) -> Result<bool, CpuError> {
+    let amode = current(cpu)?.amode();
+    let MicroState {
+        address,
+        base,
+        data,
+        ..
+    } = *cpu.micro();
+
+    match op {
+        FetchZeroPage => {
+            let zp = cpu.fetch();
+            cpu.micro_mut().address = Address::from(zp);
+            set_operand(cpu, Word::from(zp))?;
+        }
+        FetchAddressLo => {
+            let lo = cpu.fetch();
+            cpu.micro_mut().address = Address::from(lo);
+            set_operand(cpu, Word::from(lo))?;
+        }
+        FetchAddressHi => {
+            let hi = cpu.fetch();
+            let address = Address::from_le_bytes([address as u8, hi]);
+            cpu.micro_mut().address = address;
+            set_operand(cpu, address)?;
+        }
+        FetchAddressHiIndexed => {
+            let hi = cpu.fetch();
+            let base = Address::from_le_bytes([address as u8, hi]);
+            let index = Address::from(index(cpu, amode));
+            let micro = cpu.micro_mut();
+            micro.base = base;
+            micro.address = base.wrapping_add(index);
+            set_operand(cpu, base)?;
+        }
+        FetchAddressHiJump => {
+            let hi = cpu.fetch();
+            let target = Address::from_le_bytes([address as u8, hi]);
+            set_operand(cpu, target)?;
+            cpu.regset_mut().set_prog_counter(target);
+            return Ok(true);
+        }
+        IndexZeroPage => {
+            cpu.read_byte_as(address, Dummy);
+            let indexed = (address as Byte).wrapping_add(index(cpu, amode));
+            cpu.micro_mut().address = Address::from(indexed);
+        }
+        IndexAbsolute => {
+            cpu.read_byte_as(cpu.pc().wrapping_sub(1), Dummy);
+            let x_index = Address::from(cpu.regset().x_index());
+            cpu.micro_mut().address = address.wrapping_add(x_index);
+        }
+        DummyReadOperand => {
+            cpu.read_byte_as(cpu.pc().wrapping_sub(1), Dummy);
+        }
+        ReadPointerLo => {
+            cpu.micro_mut().data = cpu.read_byte(address);
+        }
+        ReadPointerHi => {
+            let hi = cpu.read_byte((address + 1) & 0x00FF);
+            cpu.micro_mut().address = Address::from_le_bytes([data, hi]);
+        }
+        ReadPointerHiIndexed => {
+            let hi = cpu.read_byte((address + 1) & 0x00FF);
+            let base = Address::from_le_bytes([data, hi]);
+            let index = Address::from(index(cpu, amode));
+            let micro = cpu.micro_mut();
+            micro.base = base;
+            micro.address = base.wrapping_add(index);
+        }
+        ReadIndirectLo => {
+            cpu.micro_mut().data = cpu.read_byte(address);
+        }
+        ReadIndirectHiJump => {
+            // The NMOS cpus do not carry into the high byte of the pointer
+            let address_of_hi = if cpu.variant() == CpuVariant::Cmos {
+                address.wrapping_add(1)
+            } else {
+                page_base(address) | (address.wrapping_add(1) & 0x00FF)
+            };
+            let hi = cpu.read_byte(address_of_hi);
+            cpu.regset_mut()
+                .set_prog_counter(Address::from_le_bytes([data, hi]));
+            return Ok(true);
+        }
+        ReadExecute => {
+            let value = cpu.read_byte(address);
+            return execute(cpu, Fetched { value, address });
+        }
+        ReadIndexedExecute => {
+            if page_base(base) == page_base(address) {
+                let value = cpu.read_byte(address);
+                return execute(cpu, Fetched { value, address });
+            }
+
+            cpu.read_byte_as(page_base(base) | (address & 0x00FF), Dummy);
+            *cpu.time_mut().residual_mut() += 1;
+        }
+        DummyReadUnfixed => {
+            cpu.read_byte_as(page_base(base) | (address & 0x00FF), Dummy);
+        }
+        ReadIndexedData => {
+            if page_base(base) == page_base(address) {
+                let data = cpu.read_byte(address);
+                let micro = cpu.micro_mut();
+                micro.data = data;
+                micro.advance();
+            } else {
+                cpu.read_byte_as(cpu.pc().wrapping_sub(1), Dummy);
+                *cpu.time_mut().residual_mut() += 1;
+            }
+        }
+        ReadData => {
+            cpu.micro_mut().data = cpu.read_byte(address);
+        }
+        DummyWrite => {
+            if cpu.variant() == CpuVariant::Cmos {
+                cpu.read_byte_as(address, Dummy);
+            } else {
+                cpu.writ_byte_as(address, data, Dummy);
+            }
+        }
+        WriteExecute => {
+            // A skipped undocumented opcode leaves the memory as it was
+            if skipped(cpu)? {
+                if current(cpu)?.operation() == Operation::ReadModifyWrite {
+                    cpu.writ_byte(address, data);
+                } else {
+                    cpu.read_byte_as(address, Dummy);
+                }
+                return Ok(true);
+            }
+            return execute(
+                cpu,
+                Fetched {
+                    value: data,
+                    address,
+                },
+            );
+        }
+        ImpliedExecute => {
+            cpu.read_byte_as(cpu.pc(), Dummy);
+            let accumulator = cpu.regset().accumulator();
+            return execute(cpu, ValueOnly(accumulator));
+        }
+        ImmediateExecute => {
+            let value = cpu.fetch();
+            set_operand(cpu, Word::from(value))?;
+            return execute(cpu, ValueOnly(value));
+        }
+        Execute => {
+            let accumulator = cpu.regset().accumulator();
+            return execute(cpu, ValueOnly(accumulator));
+        }
+        DummyReadPc => {
+            cpu.read_byte_as(cpu.pc(), Dummy);
+        }
+        DummyReadStack => {
+            cpu.read_byte_as(0x100 | Address::from(cpu.regset().stk_ptr()), Dummy);
+        }
+        FetchOffsetExecute => {
+            let offset = cpu.fetch();
+            let pc = cpu.pc();
+            let target = pc.wrapping_add(offset as i8 as Address);
+            cpu.micro_mut().base = pc;
+
+            let output = if amode == Zpr {
+                set_operand(cpu, Word::from_le_bytes([address as u8, offset]))?;
+                Fetched {
+                    value: data,
+                    address: target,
+                }
+            } else {
+                set_operand(cpu, Word::from(offset))?;
+                AbsoluteAddress(target)
+            };
+
+            let residual = cpu.time().residual();
+            execute(cpu, output)?;
+            return Ok(cpu.time().residual() == residual);
+        }
+        BranchTaken => {
+            cpu.read_byte_as(base, Dummy);
+            return Ok(page_base(base) == page_base(cpu.pc()));
+        }
+        BranchPageFix => {
+            cpu.read_byte_as(page_base(base) | (cpu.pc() & 0x00FF), Dummy);
+            return Ok(true);
+        }
+        FetchPadding => {
+            cpu.fetch();
+        }
+        PushPch => {
+            let [_, hi] = cpu.pc().to_le_bytes();
+            push(cpu, hi);
+        }
+        PushPcl => {
+            let [lo, _] = cpu.pc().to_le_bytes();
+            push(cpu, lo);
+        }
+        PushStatus => {
+            // The **Break** flag is pushed set only by `brk`
+            let interrupt = cpu.micro().interrupt;
+            let brk = if interrupt.is_none() { 1 << 4 } else { 0 };
+            let status = (cpu.regset().status() & !(1 << 4)) | brk | (1 << 5);
+            push(cpu, status);
+
+            cpu.regset_mut().set_irq_disabled(true);
+            if cpu.variant() == CpuVariant::Cmos {
+                cpu.regset_mut().set_decimal_mode(false);
+            }
+
+            // A pending NMI hijacks both `brk` and IRQs, but not a reset
+            cpu.micro_mut().address = if cpu.micro().reset {
+                RESET_VECTOR
+            } else if cpu.interrupt_handles_mut().take_nmi() {
+                NMI_VECTOR
+            } else {
+                match interrupt {
+                    Some(InterruptKind::Nmi) => NMI_VECTOR,
+                    Some(InterruptKind::Irq) => IRQ_VECTOR,
+                    None => BRK_VECTOR,
+                }
+            };
+        }
+        ReadVectorLo => {
+            cpu.micro_mut().data = cpu.read_byte_as(address, Vector);
+        }
+        ReadVectorHi => {
+            let hi = cpu.read_byte_as(address.wrapping_add(1), Vector);
+            cpu.regset_mut()
+                .set_prog_counter(Address::from_le_bytes([data, hi]));
+            return Ok(true);
+        }
+        PullStatus => {
+            let status = cpu.stk_pop();
+            let regs = cpu.regset_mut();
+            regs.set_status(status);
+            regs.set_brk(false);
+            regs.set_unused(false);
+        }
+        PullPcl => {
+            cpu.micro_mut().data = cpu.stk_pop();
+        }
+        PullPch => {
+            let hi = cpu.stk_pop();
+            cpu.regset_mut()
+                .set_prog_counter(Address::from_le_bytes([data, hi]));
+            return Ok(current(cpu)?.operation() == Operation::ReturnFromInterrupt);
+        }
+        IncrementPc => {
+            let pc = cpu.inc_pc();
+            cpu.read_byte_as(pc, Dummy);
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
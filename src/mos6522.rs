@@ -0,0 +1,661 @@
+use crate::memory_map::Device;
+use crate::mos6502::{Address, Byte, IrqLine, IrqSource, Word};
+
+//
+// MOS 6522 VIA
+//
+// The Versatile Interface Adapter has two 8 bit ports, two 16 bit
+// timers, a shift register and four handshake lines. It occupies 16
+// registers and is ticked once per cpu cycle (phi2).
+//
+// The host side of the chip -- the pins -- is reached through the
+// `set_*()` methods for inputs and the getters for outputs.
+//
+
+/// The registers, by offset
+pub const ORB: Address = 0x0;
+pub const ORA: Address = 0x1;
+pub const DDRB: Address = 0x2;
+pub const DDRA: Address = 0x3;
+pub const T1C_L: Address = 0x4;
+pub const T1C_H: Address = 0x5;
+pub const T1L_L: Address = 0x6;
+pub const T1L_H: Address = 0x7;
+pub const T2C_L: Address = 0x8;
+pub const T2C_H: Address = 0x9;
+pub const SR: Address = 0xA;
+pub const ACR: Address = 0xB;
+pub const PCR: Address = 0xC;
+pub const IFR: Address = 0xD;
+pub const IER: Address = 0xE;
+pub const ORA_NH: Address = 0xF;
+
+/// The bits of IFR and IER
+pub const IRQ_CA2: Byte = 0x01;
+pub const IRQ_CA1: Byte = 0x02;
+pub const IRQ_SR: Byte = 0x04;
+pub const IRQ_CB2: Byte = 0x08;
+pub const IRQ_CB1: Byte = 0x10;
+pub const IRQ_T2: Byte = 0x20;
+pub const IRQ_T1: Byte = 0x40;
+pub const IRQ_ANY: Byte = 0x80;
+
+/// ACR bits
+const ACR_PA_LATCH: Byte = 0x01;
+const ACR_PB_LATCH: Byte = 0x02;
+const ACR_T2_PULSES: Byte = 0x20;
+const ACR_T1_FREE_RUN: Byte = 0x40;
+const ACR_T1_PB7: Byte = 0x80;
+
+///
+/// ShiftMode
+/// \
+/// The mode of the shift register, bits 4-2 of the ACR. The shift clock
+/// is put out on CB1 in all but the external modes, and the data goes
+/// through CB2. One bit is shifted per period of the clock, which is
+/// either one cycle (phi2) or twice N + 2 cycles, N being the low byte
+/// of the T2 latch.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftMode {
+    Disabled,
+    InT2,
+    InPhi2,
+    InExternal,
+    OutFreeRunning,
+    OutT2,
+    OutPhi2,
+    OutExternal,
+}
+
+impl ShiftMode {
+    fn from_acr(acr: Byte) -> Self {
+        match (acr >> 2) & 0x07 {
+            0 => ShiftMode::Disabled,
+            1 => ShiftMode::InT2,
+            2 => ShiftMode::InPhi2,
+            3 => ShiftMode::InExternal,
+            4 => ShiftMode::OutFreeRunning,
+            5 => ShiftMode::OutT2,
+            6 => ShiftMode::OutPhi2,
+            _ => ShiftMode::OutExternal,
+        }
+    }
+
+    fn shifts_out(self) -> bool {
+        matches!(
+            self,
+            ShiftMode::OutFreeRunning
+                | ShiftMode::OutT2
+                | ShiftMode::OutPhi2
+                | ShiftMode::OutExternal
+        )
+    }
+
+    fn uses_t2(self) -> bool {
+        matches!(
+            self,
+            ShiftMode::InT2 | ShiftMode::OutFreeRunning | ShiftMode::OutT2
+        )
+    }
+
+    fn external(self) -> bool {
+        matches!(self, ShiftMode::InExternal | ShiftMode::OutExternal)
+    }
+}
+
+///
+/// Control
+/// \
+/// The mode of CA2 or CB2, 3 bits of the PCR.\
+/// \
+/// **Input** - An interrupt input, set on the given edge. Unless it is
+/// `independent`, reading or writing the port clears its flag.\
+/// **Handshake** - Goes low when the port is accessed and high again
+/// on the active edge of CA1/CB1.\
+/// **Pulse** - Goes low for one cycle when the port is accessed.\
+/// **Low**, **High** - A fixed output.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Input { positive: bool, independent: bool },
+    Handshake,
+    Pulse,
+    Low,
+    High,
+}
+
+impl Control {
+    fn from_bits(bits: Byte) -> Self {
+        match bits & 0x07 {
+            0..=3 => Control::Input {
+                positive: bits & 0x02 != 0,
+                independent: bits & 0x01 != 0,
+            },
+            4 => Control::Handshake,
+            5 => Control::Pulse,
+            6 => Control::Low,
+            _ => Control::High,
+        }
+    }
+}
+
+///
+/// Via
+///
+/// A MOS 6522 Versatile Interface Adapter. Mount it into a `MemoryMap`
+/// as a device -- it decodes the lower 4 address lines.
+///
+#[derive(Debug, Default)]
+pub struct Via {
+    ora: Byte,
+    orb: Byte,
+    ddra: Byte,
+    ddrb: Byte,
+    ira_latch: Byte,
+    irb_latch: Byte,
+    acr: Byte,
+    pcr: Byte,
+    ifr: Byte,
+    ier: Byte,
+
+    t1_counter: Word,
+    t1_latch: Word,
+    /// Whether the next time-out sets the flag
+    t1_armed: bool,
+    /// Whether the counter is loaded from the latch on the next tick
+    t1_reload: bool,
+    pb7: bool,
+
+    t2_counter: Word,
+    t2_latch_lo: Byte,
+    t2_armed: bool,
+    t2_reload: bool,
+
+    sr: Byte,
+    sr_bits: u8,
+    sr_running: bool,
+    /// The cycles left until the shift clock changes
+    sr_divider: Word,
+
+    /// The input pins
+    port_a_in: Byte,
+    port_b_in: Byte,
+    ca1: bool,
+    ca2_in: bool,
+    cb1_in: bool,
+    cb2_in: bool,
+
+    /// The output levels of the handshake lines
+    ca2_out: bool,
+    cb1_out: bool,
+    cb2_out: bool,
+
+    irq: Option<IrqSource>,
+}
+
+impl Via {
+    pub fn new() -> Self {
+        Self {
+            port_a_in: 0xFF,
+            port_b_in: 0xFF,
+            ca1: true,
+            ca2_in: true,
+            cb1_in: true,
+            cb2_in: true,
+            ca2_out: true,
+            cb1_out: true,
+            cb2_out: true,
+            ..Self::default()
+        }
+    }
+
+    /// **reset()** - Pulls the RES pin. The ports, their handshakes and
+    /// interrupts are cleared, the timers and the shift register are not.
+    pub fn reset(&mut self) {
+        self.ora = 0;
+        self.orb = 0;
+        self.ddra = 0;
+        self.ddrb = 0;
+        self.acr = 0;
+        self.pcr = 0;
+        self.ifr = 0;
+        self.ier = 0;
+        self.sr_running = false;
+        self.ca2_out = true;
+        self.cb1_out = true;
+        self.cb2_out = true;
+        self.update_irq();
+    }
+
+    /// **port_a()** - The levels of the pins of port A
+    pub fn port_a(&self) -> Byte {
+        (self.ora & self.ddra) | (self.port_a_in & !self.ddra)
+    }
+
+    /// **port_b()** - The levels of the pins of port B. PB7 is driven
+    /// by timer 1 when the ACR tells so.
+    pub fn port_b(&self) -> Byte {
+        let pins = (self.orb & self.ddrb) | (self.port_b_in & !self.ddrb);
+        if self.acr & ACR_T1_PB7 != 0 {
+            (pins & 0x7F) | if self.pb7 { 0x80 } else { 0 }
+        } else {
+            pins
+        }
+    }
+
+    /// **set_port_a()** - Drives the pins of port A which are inputs
+    pub fn set_port_a(&mut self, data: Byte) {
+        self.port_a_in = data;
+    }
+
+    /// **set_port_b()** - Drives the pins of port B which are inputs.
+    /// Falling edges of PB6 are counted by timer 2 in pulse counting mode.
+    pub fn set_port_b(&mut self, data: Byte) {
+        let falling = self.port_b_in & !data & 0x40 != 0;
+        self.port_b_in = data;
+
+        if falling && self.acr & ACR_T2_PULSES != 0 && !self.t2_reload {
+            self.t2_counter = self.t2_counter.wrapping_sub(1);
+            if self.t2_counter == 0 && self.t2_armed {
+                self.t2_armed = false;
+                self.set_flags(IRQ_T2);
+            }
+        }
+    }
+
+    fn ca2_control(&self) -> Control {
+        Control::from_bits(self.pcr >> 1)
+    }
+
+    fn cb2_control(&self) -> Control {
+        Control::from_bits(self.pcr >> 5)
+    }
+
+    fn shift_mode(&self) -> ShiftMode {
+        ShiftMode::from_acr(self.acr)
+    }
+
+    /// **ca2()** - The level of CA2
+    pub fn ca2(&self) -> bool {
+        match self.ca2_control() {
+            Control::Input { .. } => self.ca2_in,
+            _ => self.ca2_out,
+        }
+    }
+
+    /// **cb1()** - The level of CB1, which is the shift clock output
+    /// when the shift register is clocked internally
+    pub fn cb1(&self) -> bool {
+        match self.shift_mode() {
+            ShiftMode::Disabled | ShiftMode::InExternal | ShiftMode::OutExternal => self.cb1_in,
+            _ => self.cb1_out,
+        }
+    }
+
+    /// **cb2()** - The level of CB2, which is the data output when
+    /// the shift register shifts out
+    pub fn cb2(&self) -> bool {
+        if self.shift_mode().shifts_out() {
+            return self.cb2_out;
+        }
+        match self.cb2_control() {
+            Control::Input { .. } => self.cb2_in,
+            _ => self.cb2_out,
+        }
+    }
+
+    /// **set_ca1()** - Drives CA1. Its active edge sets the flag, latches
+    /// port A and ends a CA2 handshake.
+    pub fn set_ca1(&mut self, level: bool) {
+        if level == self.ca1 {
+            return;
+        }
+        self.ca1 = level;
+
+        let positive = self.pcr & 0x01 != 0;
+        if level == positive {
+            if self.acr & ACR_PA_LATCH != 0 {
+                self.ira_latch = self.port_a();
+            }
+            if self.ca2_control() == Control::Handshake {
+                self.ca2_out = true;
+            }
+            self.set_flags(IRQ_CA1);
+        }
+    }
+
+    /// **set_ca2()** - Drives CA2, if it is an input
+    pub fn set_ca2(&mut self, level: bool) {
+        let previous = std::mem::replace(&mut self.ca2_in, level);
+        if let Control::Input { positive, .. } = self.ca2_control() {
+            if previous != level && level == positive {
+                self.set_flags(IRQ_CA2);
+            }
+        }
+    }
+
+    /// **set_cb1()** - Drives CB1. Its active edge sets the flag, latches
+    /// port B and ends a CB2 handshake. Its rising edge clocks the
+    /// shift register in the external modes.
+    pub fn set_cb1(&mut self, level: bool) {
+        if level == self.cb1_in {
+            return;
+        }
+        self.cb1_in = level;
+
+        if level && self.shift_mode().external() {
+            self.shift();
+        }
+
+        let positive = self.pcr & 0x10 != 0;
+        if level == positive {
+            if self.acr & ACR_PB_LATCH != 0 {
+                self.irb_latch = self.port_b();
+            }
+            if self.cb2_control() == Control::Handshake {
+                self.cb2_out = true;
+            }
+            self.set_flags(IRQ_CB1);
+        }
+    }
+
+    /// **set_cb2()** - Drives CB2, if it is an input. It is also
+    /// the data input of the shift register.
+    pub fn set_cb2(&mut self, level: bool) {
+        let previous = std::mem::replace(&mut self.cb2_in, level);
+        if let Control::Input { positive, .. } = self.cb2_control() {
+            if previous != level && level == positive && !self.shift_mode().shifts_out() {
+                self.set_flags(IRQ_CB2);
+            }
+        }
+    }
+
+    fn set_flags(&mut self, flags: Byte) {
+        self.ifr |= flags;
+        self.update_irq();
+    }
+
+    fn clear_flags(&mut self, flags: Byte) {
+        self.ifr &= !flags;
+        self.update_irq();
+    }
+
+    /// IFR bit 7 and the IRQ line follow the enabled flags
+    fn update_irq(&mut self) {
+        let asserted = self.ifr & self.ier & 0x7F != 0;
+        if asserted {
+            self.ifr |= IRQ_ANY;
+        } else {
+            self.ifr &= !IRQ_ANY;
+        }
+        if let Some(irq) = &self.irq {
+            irq.set(asserted);
+        }
+    }
+
+    /// Flags of CA2/CB2 which are cleared by accessing the port
+    fn dependent_flag(control: Control, flag: Byte) -> Byte {
+        match control {
+            Control::Input {
+                independent: true, ..
+            } => 0,
+            _ => flag,
+        }
+    }
+
+    /// **port_a_access()** - The side effects of reading or writing ORA
+    fn port_a_access(&mut self) {
+        let control = self.ca2_control();
+        if matches!(control, Control::Handshake | Control::Pulse) {
+            self.ca2_out = false;
+        }
+        self.clear_flags(IRQ_CA1 | Self::dependent_flag(control, IRQ_CA2));
+    }
+
+    /// **port_b_access()** - The side effects of reading or writing ORB.
+    /// Only writes start a CB2 handshake.
+    fn port_b_access(&mut self, write: bool) {
+        let control = self.cb2_control();
+        if write && matches!(control, Control::Handshake | Control::Pulse) {
+            self.cb2_out = false;
+        }
+        self.clear_flags(IRQ_CB1 | Self::dependent_flag(control, IRQ_CB2));
+    }
+
+    fn start_shifting(&mut self) {
+        self.sr_bits = 0;
+        self.sr_running = self.shift_mode() != ShiftMode::Disabled;
+        self.sr_divider = self.shift_half_period();
+        self.clear_flags(IRQ_SR);
+    }
+
+    fn shift_half_period(&self) -> Word {
+        if self.shift_mode().uses_t2() {
+            Word::from(self.t2_latch_lo) + 2
+        } else {
+            1
+        }
+    }
+
+    /// **shift()** - Shifts one bit in from CB2 or out to it. The
+    /// register rotates when shifting out.
+    fn shift(&mut self) {
+        let mode = self.shift_mode();
+        if !self.sr_running {
+            return;
+        }
+
+        if mode.shifts_out() {
+            self.cb2_out = self.sr & 0x80 != 0;
+            self.sr = self.sr.rotate_left(1);
+        } else {
+            self.sr = self.sr << 1 | Byte::from(self.cb2_in);
+        }
+
+        if mode != ShiftMode::OutFreeRunning {
+            self.sr_bits += 1;
+            if self.sr_bits == 8 {
+                self.sr_running = false;
+                self.set_flags(IRQ_SR);
+            }
+        }
+    }
+
+    fn tick_timers(&mut self) {
+        if self.t1_reload {
+            self.t1_counter = self.t1_latch;
+            self.t1_reload = false;
+        } else {
+            self.t1_counter = self.t1_counter.wrapping_sub(1);
+            if self.t1_counter == 0xFFFF {
+                let free_run = self.acr & ACR_T1_FREE_RUN != 0;
+                self.t1_reload = free_run;
+                if self.t1_armed {
+                    self.t1_armed = free_run;
+                    self.pb7 = !free_run || !self.pb7;
+                    self.set_flags(IRQ_T1);
+                }
+            }
+        }
+
+        if self.t2_reload {
+            self.t2_reload = false;
+        } else if self.acr & ACR_T2_PULSES == 0 {
+            self.t2_counter = self.t2_counter.wrapping_sub(1);
+            if self.t2_counter == 0xFFFF && self.t2_armed {
+                self.t2_armed = false;
+                self.set_flags(IRQ_T2);
+            }
+        }
+    }
+
+    fn tick_shift_register(&mut self) {
+        let mode = self.shift_mode();
+        if !self.sr_running || mode.external() {
+            return;
+        }
+
+        // CB1 pulses low and back high within every cycle at phi2,
+        // so it is seen high in between
+        if !mode.uses_t2() {
+            self.shift();
+            return;
+        }
+
+        self.sr_divider = self.sr_divider.saturating_sub(1);
+        if self.sr_divider == 0 {
+            self.sr_divider = self.shift_half_period();
+            self.cb1_out = !self.cb1_out;
+            if self.cb1_out {
+                self.shift();
+            }
+        }
+    }
+
+    /// The value of a register, without the side effects of reading it
+    fn register(&self, offset: Address) -> Byte {
+        let [t1_lo, t1_hi] = self.t1_counter.to_le_bytes();
+        let [t2_lo, t2_hi] = self.t2_counter.to_le_bytes();
+        let [t1_latch_lo, t1_latch_hi] = self.t1_latch.to_le_bytes();
+        match offset & 0x0F {
+            ORB => {
+                let input = if self.acr & ACR_PB_LATCH != 0 {
+                    self.irb_latch
+                } else {
+                    self.port_b()
+                };
+                (self.orb & self.ddrb) | (input & !self.ddrb)
+            }
+            ORA | ORA_NH => {
+                if self.acr & ACR_PA_LATCH != 0 {
+                    self.ira_latch
+                } else {
+                    self.port_a()
+                }
+            }
+            DDRB => self.ddrb,
+            DDRA => self.ddra,
+            T1C_L => t1_lo,
+            T1C_H => t1_hi,
+            T1L_L => t1_latch_lo,
+            T1L_H => t1_latch_hi,
+            T2C_L => t2_lo,
+            T2C_H => t2_hi,
+            SR => self.sr,
+            ACR => self.acr,
+            PCR => self.pcr,
+            IFR => self.ifr,
+            _ => self.ier | 0x80,
+        }
+    }
+
+    /// **set_pcr()** - The fixed levels of CA2 and CB2 follow the PCR
+    fn set_pcr(&mut self, data: Byte) {
+        self.pcr = data;
+        self.ca2_out = Self::fixed_level(self.ca2_control(), self.ca2_out);
+        self.cb2_out = Self::fixed_level(self.cb2_control(), self.cb2_out);
+    }
+
+    fn fixed_level(control: Control, level: bool) -> bool {
+        match control {
+            Control::Low => false,
+            Control::High | Control::Input { .. } => true,
+            _ => level,
+        }
+    }
+}
+
+impl Device for Via {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        let offset = offset & 0x0F;
+        let value = self.register(offset);
+        match offset {
+            ORB => self.port_b_access(false),
+            ORA => self.port_a_access(),
+            T1C_L => self.clear_flags(IRQ_T1),
+            T2C_L => self.clear_flags(IRQ_T2),
+            SR => self.start_shifting(),
+            _ => {}
+        }
+        Some(value)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        let [t1_latch_lo, t1_latch_hi] = self.t1_latch.to_le_bytes();
+        match offset & 0x0F {
+            ORB => {
+                self.orb = data;
+                self.port_b_access(true);
+            }
+            ORA => {
+                self.ora = data;
+                self.port_a_access();
+            }
+            DDRB => self.ddrb = data,
+            DDRA => self.ddra = data,
+            T1C_L | T1L_L => self.t1_latch = Word::from_le_bytes([data, t1_latch_hi]),
+            T1C_H => {
+                self.t1_latch = Word::from_le_bytes([t1_latch_lo, data]);
+                self.t1_reload = true;
+                self.t1_armed = true;
+                self.pb7 = false;
+                self.clear_flags(IRQ_T1);
+            }
+            T1L_H => {
+                self.t1_latch = Word::from_le_bytes([t1_latch_lo, data]);
+                self.clear_flags(IRQ_T1);
+            }
+            T2C_L => self.t2_latch_lo = data,
+            T2C_H => {
+                self.t2_counter = Word::from_le_bytes([self.t2_latch_lo, data]);
+                self.t2_reload = true;
+                self.t2_armed = true;
+                self.clear_flags(IRQ_T2);
+            }
+            SR => {
+                self.sr = data;
+                self.start_shifting();
+            }
+            ACR => {
+                self.acr = data;
+                if self.shift_mode() == ShiftMode::Disabled {
+                    self.sr_running = false;
+                }
+            }
+            PCR => self.set_pcr(data),
+            IFR => self.clear_flags(data & 0x7F),
+            IER => {
+                if data & 0x80 != 0 {
+                    self.ier |= data & 0x7F;
+                } else {
+                    self.ier &= !data;
+                }
+                self.update_irq();
+            }
+            _ => self.ora = data,
+        }
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        Some(self.register(offset))
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        self.irq = Some(line.source("6522"));
+        self.update_irq();
+    }
+
+    fn tick(&mut self) {
+        // A pulse on CA2/CB2 lasts a single cycle
+        if self.ca2_control() == Control::Pulse {
+            self.ca2_out = true;
+        }
+        if self.cb2_control() == Control::Pulse {
+            self.cb2_out = true;
+        }
+
+        self.tick_timers();
+        self.tick_shift_register();
+    }
+}
@@ -0,0 +1,268 @@
+use crate::memory_map::Device;
+use crate::mos6502::{Address, Byte, IrqLine, IrqSource};
+
+//
+// MOS 6532 RIOT
+//
+// The RAM-I/O-Timer has 128 bytes of RAM, two 8 bit ports, an 8 bit
+// interval timer and an edge detector on PA7. The RAM and the rest are
+// told apart by the RS pin, which is tied to A7 here: offsets 0x00-0x7F
+// are the RAM and 0x80-0xFF the registers. Machines which wire it
+// otherwise (e.g. the Atari 2600, with its RAM at 0x80) mirror the RAM.
+//
+// The registers are decoded from A4-A0, at 0x80 plus:
+//
+//        read                    write
+// 0x00   port A                  port A
+// 0x01   DDR A                   DDR A
+// 0x02   port B                  port B
+// 0x03   DDR B                   DDR B
+// 0x04   timer                   edge detect control (A0 positive, A1 IRQ)
+// 0x05   interrupt flags         -
+// 0x14   -                       timer /1    (0x1C with the IRQ enabled)
+// 0x15   -                       timer /8    (0x1D)
+// 0x16   -                       timer /64   (0x1E)
+// 0x17   -                       timer /1024 (0x1F)
+//
+// Reading the timer enables its IRQ if A3 is set and disables it otherwise.
+//
+
+pub const RAM_SIZE: usize = 128;
+
+/// The bits of the interrupt flag register
+pub const FLAG_TIMER: Byte = 0x80;
+pub const FLAG_PA7: Byte = 0x40;
+
+/// The intervals of the timer, selected by A1-A0
+const PRESCALERS: [u16; 4] = [1, 8, 64, 1024];
+
+///
+/// Riot
+///
+/// A MOS 6532 RAM-I/O-Timer. Mount it into a `MemoryMap` as a device
+/// over 256 bytes, or less if the registers are not needed.
+///
+#[derive(Debug)]
+pub struct Riot {
+    ram: [Byte; RAM_SIZE],
+    ora: Byte,
+    orb: Byte,
+    ddra: Byte,
+    ddrb: Byte,
+    port_a_in: Byte,
+    port_b_in: Byte,
+
+    timer: Byte,
+    /// The cycles per decrement, as programmed
+    interval: u16,
+    /// The cycles until the next decrement
+    prescaler: u16,
+    /// Set by a write of the timer, so that it starts counting on the next cycle
+    timer_written: bool,
+    timer_flag: bool,
+    timer_irq: bool,
+
+    pa7_flag: bool,
+    pa7_irq: bool,
+    pa7_positive: bool,
+    /// The level of PA7 the edge detector saw last
+    pa7: bool,
+
+    irq: Option<IrqSource>,
+}
+
+impl Default for Riot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Riot {
+    pub fn new() -> Self {
+        Self {
+            ram: [0x00; RAM_SIZE],
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            port_a_in: 0xFF,
+            port_b_in: 0xFF,
+            timer: 0,
+            interval: 1,
+            prescaler: 0,
+            timer_written: false,
+            timer_flag: false,
+            timer_irq: false,
+            pa7_flag: false,
+            pa7_irq: false,
+            pa7_positive: false,
+            pa7: true,
+            irq: None,
+        }
+    }
+
+    /// **reset()** - Pulls the RES pin. The ports become inputs and the
+    /// interrupts are disabled, the RAM and the timer are left alone.
+    pub fn reset(&mut self) {
+        self.ora = 0;
+        self.orb = 0;
+        self.ddra = 0;
+        self.ddrb = 0;
+        self.timer_irq = false;
+        self.pa7_irq = false;
+        self.pa7_positive = false;
+        self.pa7_flag = false;
+        self.pa7 = self.port_a() & 0x80 != 0;
+        self.update_irq();
+    }
+
+    /// **port_a()** - The levels of the pins of port A
+    pub fn port_a(&self) -> Byte {
+        (self.ora & self.ddra) | (self.port_a_in & !self.ddra)
+    }
+
+    /// **port_b()** - The levels of the pins of port B
+    pub fn port_b(&self) -> Byte {
+        (self.orb & self.ddrb) | (self.port_b_in & !self.ddrb)
+    }
+
+    /// **set_port_a()** - Drives the pins of port A which are inputs.
+    /// An edge on PA7 may set its flag.
+    pub fn set_port_a(&mut self, data: Byte) {
+        self.port_a_in = data;
+        self.detect_edge();
+    }
+
+    /// **set_port_b()** - Drives the pins of port B which are inputs
+    pub fn set_port_b(&mut self, data: Byte) {
+        self.port_b_in = data;
+    }
+
+    /// **timer()** - The value of the timer, without reading it
+    pub fn timer(&self) -> Byte {
+        self.timer
+    }
+
+    /// **flags()** - The interrupt flags, without reading them
+    pub fn flags(&self) -> Byte {
+        let mut flags = 0;
+        if self.timer_flag {
+            flags |= FLAG_TIMER;
+        }
+        if self.pa7_flag {
+            flags |= FLAG_PA7;
+        }
+        flags
+    }
+
+    /// The edge detector watches the pin, whoever drives it
+    fn detect_edge(&mut self) {
+        let level = self.port_a() & 0x80 != 0;
+        if level != self.pa7 {
+            self.pa7 = level;
+            if level == self.pa7_positive {
+                self.pa7_flag = true;
+                self.update_irq();
+            }
+        }
+    }
+
+    fn update_irq(&mut self) {
+        let asserted = (self.timer_flag && self.timer_irq) || (self.pa7_flag && self.pa7_irq);
+        if let Some(irq) = &self.irq {
+            irq.set(asserted);
+        }
+    }
+
+    fn write_timer(&mut self, offset: Address, data: Byte) {
+        self.timer = data;
+        self.interval = PRESCALERS[usize::from(offset & 0x03)];
+        self.prescaler = 0;
+        self.timer_written = true;
+        self.timer_irq = offset & 0x08 != 0;
+        self.timer_flag = false;
+        self.update_irq();
+    }
+
+    /// The value of a register, without the side effects of reading it
+    fn register(&self, offset: Address) -> Byte {
+        if offset & 0x80 == 0 {
+            return self.ram[usize::from(offset & 0x7F)];
+        }
+        match (offset & 0x04 != 0, offset & 0x03) {
+            (false, 0) => self.port_a(),
+            (false, 1) => self.ddra,
+            (false, 2) => self.port_b(),
+            (false, _) => self.ddrb,
+            (true, 0) | (true, 2) => self.timer,
+            (true, _) => self.flags(),
+        }
+    }
+}
+
+impl Device for Riot {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        let value = self.register(offset);
+        if offset & 0x84 == 0x84 {
+            if offset & 0x01 == 0 {
+                self.timer_irq = offset & 0x08 != 0;
+                self.timer_flag = false;
+            } else {
+                self.pa7_flag = false;
+            }
+            self.update_irq();
+        }
+        Some(value)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        if offset & 0x80 == 0 {
+            self.ram[usize::from(offset & 0x7F)] = data;
+            return;
+        }
+        match (offset & 0x04 != 0, offset & 0x10 != 0, offset & 0x03) {
+            (false, _, 0) => self.ora = data,
+            (false, _, 1) => self.ddra = data,
+            (false, _, 2) => self.orb = data,
+            (false, _, _) => self.ddrb = data,
+            (true, true, _) => self.write_timer(offset, data),
+            (true, false, _) => {
+                self.pa7_positive = offset & 0x01 != 0;
+                self.pa7_irq = offset & 0x02 != 0;
+                self.update_irq();
+            }
+        }
+        self.detect_edge();
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        Some(self.register(offset))
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        self.irq = Some(line.source("6532"));
+        self.update_irq();
+    }
+
+    /// The timer is decremented on the cycle after it is written and
+    /// then once every interval. Once it underflows it counts every cycle.
+    fn tick(&mut self) {
+        if self.timer_written {
+            self.timer_written = false;
+            return;
+        }
+
+        if self.prescaler > 0 {
+            self.prescaler -= 1;
+            return;
+        }
+
+        self.timer = self.timer.wrapping_sub(1);
+        if self.timer == 0xFF {
+            self.timer_flag = true;
+            self.interval = 1;
+            self.update_irq();
+        }
+        self.prescaler = self.interval - 1;
+    }
+}
@@ -0,0 +1,524 @@
+use crate::memory_map::Device;
+use crate::mos6502::{Address, Byte, IrqLine, IrqSource};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+//
+// MOS 6551 ACIA
+//
+// The Asynchronous Communications Interface Adapter is a UART with
+// four registers: the data register (the transmit one when written,
+// the receive one when read), the status, the command and the control
+// one. It is ticked once per cpu cycle and moves a character every
+// "character time" -- the bits of a frame divided by the baud rate.
+//
+// What is on the other end of the line is up to a `SerialBackend`.
+//
+
+/// The registers, by offset
+pub const DATA: Address = 0x0;
+pub const STATUS: Address = 0x1;
+pub const COMMAND: Address = 0x2;
+pub const CONTROL: Address = 0x3;
+
+/// The bits of the status register
+pub const STATUS_PARITY: Byte = 0x01;
+pub const STATUS_FRAMING: Byte = 0x02;
+pub const STATUS_OVERRUN: Byte = 0x04;
+pub const STATUS_RDRF: Byte = 0x08;
+pub const STATUS_TDRE: Byte = 0x10;
+pub const STATUS_IRQ: Byte = 0x80;
+
+/// The bits of the command register
+const COMMAND_DTR: Byte = 0x01;
+const COMMAND_RX_IRQ_OFF: Byte = 0x02;
+const COMMAND_TX_CONTROL: Byte = 0x0C;
+const COMMAND_ECHO: Byte = 0x10;
+const COMMAND_PARITY: Byte = 0x20;
+
+/// The rates selected by the lower nibble of the control register. The
+/// external clock is taken to be the usual 1.8432 MHz crystal, divided by 16.
+const BAUD_RATES: [f64; 16] = [
+    115_200.0, 50.0, 75.0, 109.92, 134.58, 150.0, 300.0, 600.0, 1200.0, 1800.0, 2400.0, 3600.0,
+    4800.0, 7200.0, 9600.0, 19200.0,
+];
+
+///
+/// SerialBackend
+///
+/// The other end of the serial line.
+///
+pub trait SerialBackend {
+    /// **receive()** - The next byte sent to the ACIA, if there is one. Never blocks.
+    fn receive(&mut self) -> Option<Byte>;
+
+    /// **transmit()** - Takes a byte sent by the ACIA
+    fn transmit(&mut self, data: Byte);
+
+    /// **ended()** - Whether the other end is gone and nothing
+    /// more is ever going to be received
+    fn ended(&self) -> bool {
+        false
+    }
+}
+
+///
+/// ByteQueue
+///
+/// A backend in memory: bytes pushed by the host are received in
+/// order and the transmitted ones are collected.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ByteQueue {
+    input: VecDeque<Byte>,
+    output: Vec<Byte>,
+}
+
+impl ByteQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// **push()** - Queues bytes to be received by the ACIA
+    pub fn push(&mut self, bytes: &[Byte]) {
+        self.input.extend(bytes);
+    }
+
+    /// **pending()** - The number of queued bytes not yet received
+    pub fn pending(&self) -> usize {
+        self.input.len()
+    }
+
+    /// **output()** - The bytes transmitted so far
+    pub fn output(&self) -> &[Byte] {
+        &self.output
+    }
+
+    /// **take_output()** - The bytes transmitted so far, which are forgotten
+    pub fn take_output(&mut self) -> Vec<Byte> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl SerialBackend for ByteQueue {
+    fn receive(&mut self) -> Option<Byte> {
+        self.input.pop_front()
+    }
+
+    fn transmit(&mut self, data: Byte) {
+        self.output.push(data);
+    }
+}
+
+///
+/// Stdio
+///
+/// A backend on the standard input and output of the host. Stdin is
+/// read by a thread of its own, so that the emulation never waits for it.
+/// There is only one such thread, which all the `Stdio`s share.
+///
+#[derive(Debug)]
+pub struct Stdio {
+    input: &'static Mutex<StdinInput>,
+}
+
+/// What the stdin thread has read and not yet been received
+#[derive(Debug, Default)]
+struct StdinInput {
+    bytes: VecDeque<Byte>,
+    ended: bool,
+}
+
+/// **stdin_input()** - The input of the stdin thread, which is
+/// started on the first call
+fn stdin_input() -> &'static Mutex<StdinInput> {
+    static INPUT: OnceLock<Mutex<StdinInput>> = OnceLock::new();
+    INPUT.get_or_init(|| {
+        thread::spawn(|| {
+            for byte in io::stdin().lock().bytes() {
+                match byte {
+                    Ok(byte) => stdin_input().lock().unwrap().bytes.push_back(byte),
+                    Err(_) => break,
+                }
+            }
+            stdin_input().lock().unwrap().ended = true;
+        });
+        Mutex::new(StdinInput::default())
+    })
+}
+
+impl Stdio {
+    pub fn new() -> Self {
+        Self {
+            input: stdin_input(),
+        }
+    }
+}
+
+impl Default for Stdio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialBackend for Stdio {
+    fn receive(&mut self) -> Option<Byte> {
+        self.input.lock().unwrap().bytes.pop_front()
+    }
+
+    fn transmit(&mut self, data: Byte) {
+        let mut stdout = io::stdout();
+        // A closed stdout is a line nobody listens to
+        let _ = stdout.write_all(&[data]).and_then(|_| stdout.flush());
+    }
+
+    /// Stdin is at its end and everything before it has been received
+    fn ended(&self) -> bool {
+        let input = self.input.lock().unwrap();
+        input.ended && input.bytes.is_empty()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod ffi {
+    use std::os::raw::{c_char, c_int};
+
+    pub const O_NOCTTY: c_int = 0o400;
+    pub const O_NONBLOCK: c_int = 0o4000;
+
+    extern "C" {
+        pub fn grantpt(fd: c_int) -> c_int;
+        pub fn unlockpt(fd: c_int) -> c_int;
+        pub fn ptsname_r(fd: c_int, buf: *mut c_char, buflen: usize) -> c_int;
+    }
+}
+
+///
+/// Pty
+///
+/// A backend on a Linux pseudo-terminal. Its slave side, whose path is
+/// given by `name()`, can be opened by `screen`, `minicom` and the like.
+///
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct Pty {
+    master: std::fs::File,
+    name: String,
+}
+
+#[cfg(target_os = "linux")]
+impl Pty {
+    /// **open()** - Allocates a new pseudo-terminal
+    pub fn open() -> io::Result<Self> {
+        use std::ffi::CStr;
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        let master = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(ffi::O_NOCTTY | ffi::O_NONBLOCK)
+            .open("/dev/ptmx")?;
+        let fd = master.as_raw_fd();
+
+        let mut name = [0; 128];
+        // SAFETY: `fd` is an open master and `name` outlives the calls
+        unsafe {
+            if ffi::grantpt(fd) != 0 || ffi::unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let error = ffi::ptsname_r(fd, name.as_mut_ptr(), name.len());
+            if error != 0 {
+                return Err(io::Error::from_raw_os_error(error));
+            }
+        }
+        // SAFETY: `ptsname_r()` succeeded, so `name` is nul terminated
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+
+        Ok(Self {
+            master,
+            name: name.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// **name()** - The path of the slave side, e.g. `/dev/pts/3`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SerialBackend for Pty {
+    fn receive(&mut self) -> Option<Byte> {
+        // Nothing waiting and no slave opened yet are both silence
+        let mut byte = [0];
+        match self.master.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+
+    fn transmit(&mut self, data: Byte) {
+        let _ = self.master.write_all(&[data]);
+    }
+}
+
+///
+/// Acia
+///
+/// A MOS 6551 Asynchronous Communications Interface Adapter. Mount it
+/// into a `MemoryMap` as a device -- it decodes the lower 2 address lines.
+/// Parity is not simulated, so neither parity nor framing errors occur.
+///
+#[derive(Debug)]
+pub struct Acia<S: SerialBackend> {
+    backend: S,
+    /// The frequency the cpu (and the ACIA with it) is clocked at
+    clock_hz: u64,
+
+    control: Byte,
+    command: Byte,
+    overrun: bool,
+    irq_flag: bool,
+
+    rdr: Byte,
+    rdrf: bool,
+    /// The cycles until the next character is received
+    rx_countdown: u64,
+
+    tdr: Option<Byte>,
+    /// The character being shifted out and the cycles until it is out
+    shifter: Option<Byte>,
+    tx_countdown: u64,
+
+    irq: Option<IrqSource>,
+}
+
+impl<S: SerialBackend> Acia<S> {
+    pub fn new(backend: S, clock_hz: u64) -> Self {
+        let mut acia = Self {
+            backend,
+            clock_hz,
+            control: 0,
+            command: 0,
+            overrun: false,
+            irq_flag: false,
+            rdr: 0,
+            rdrf: false,
+            rx_countdown: 0,
+            tdr: None,
+            shifter: None,
+            tx_countdown: 0,
+            irq: None,
+        };
+        acia.reset();
+        acia
+    }
+
+    /// **reset()** - Pulls the RES pin
+    pub fn reset(&mut self) {
+        self.control = 0x00;
+        self.command = COMMAND_RX_IRQ_OFF;
+        self.overrun = false;
+        self.rdrf = false;
+        self.tdr = None;
+        self.shifter = None;
+        self.rx_countdown = self.char_cycles();
+        self.set_irq(false);
+    }
+
+    /// **backend()** - The other end of the line
+    pub fn backend(&self) -> &S {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut S {
+        &mut self.backend
+    }
+
+    /// **baud()** - The rate selected by the control register
+    pub fn baud(&self) -> f64 {
+        BAUD_RATES[usize::from(self.control & 0x0F)]
+    }
+
+    /// **frame_bits()** - The bits of a character on the line: the start
+    /// bit, the data bits, the parity bit and the stop bits. 1.5 stop bits
+    /// (5 data bits without parity) are rounded to 2.
+    pub fn frame_bits(&self) -> u64 {
+        let data = u64::from(self.word_length());
+        let parity = u64::from(self.command & COMMAND_PARITY != 0);
+        let stop = if self.control & 0x80 != 0 && !(data == 8 && parity == 1) {
+            2
+        } else {
+            1
+        };
+        1 + data + parity + stop
+    }
+
+    /// **char_cycles()** - The cpu cycles a character takes on the line
+    pub fn char_cycles(&self) -> u64 {
+        let cycles = self.clock_hz as f64 * self.frame_bits() as f64 / self.baud();
+        (cycles.round() as u64).max(1)
+    }
+
+    fn word_length(&self) -> u8 {
+        8 - ((self.control >> 5) & 0x03)
+    }
+
+    fn word_mask(&self) -> Byte {
+        0xFF >> (8 - self.word_length())
+    }
+
+    fn ready(&self) -> bool {
+        self.command & COMMAND_DTR != 0
+    }
+
+    fn tx_control(&self) -> Byte {
+        (self.command & COMMAND_TX_CONTROL) >> 2
+    }
+
+    fn rx_irq_enabled(&self) -> bool {
+        self.ready() && self.command & COMMAND_RX_IRQ_OFF == 0
+    }
+
+    fn tx_irq_enabled(&self) -> bool {
+        self.ready() && self.tx_control() == 0b01
+    }
+
+    fn set_irq(&mut self, asserted: bool) {
+        self.irq_flag = asserted;
+        if let Some(irq) = &self.irq {
+            irq.set(asserted);
+        }
+    }
+
+    fn status(&self) -> Byte {
+        let mut status = 0;
+        if self.overrun {
+            status |= STATUS_OVERRUN;
+        }
+        if self.rdrf {
+            status |= STATUS_RDRF;
+        }
+        if self.tdr.is_none() {
+            status |= STATUS_TDRE;
+        }
+        if self.irq_flag {
+            status |= STATUS_IRQ;
+        }
+        status
+    }
+
+    /// The value of a register, without the side effects of reading it
+    fn register(&self, offset: Address) -> Byte {
+        match offset & 0x03 {
+            DATA => self.rdr,
+            STATUS => self.status(),
+            COMMAND => self.command,
+            _ => self.control,
+        }
+    }
+
+    fn tick_transmitter(&mut self) {
+        if let Some(data) = self.shifter {
+            self.tx_countdown -= 1;
+            if self.tx_countdown == 0 {
+                self.backend.transmit(data);
+                self.shifter = None;
+            }
+        }
+
+        // The transmitter is off while RTS is high
+        if self.shifter.is_none() && self.tx_control() != 0b00 {
+            if let Some(data) = self.tdr.take() {
+                self.shifter = Some(data & self.word_mask());
+                self.tx_countdown = self.char_cycles();
+                if self.tx_irq_enabled() {
+                    self.set_irq(true);
+                }
+            }
+        }
+    }
+
+    fn tick_receiver(&mut self) {
+        if !self.ready() {
+            self.rx_countdown = self.char_cycles();
+            return;
+        }
+        self.rx_countdown = self.rx_countdown.saturating_sub(1);
+        if self.rx_countdown > 0 {
+            return;
+        }
+        self.rx_countdown = self.char_cycles();
+
+        if let Some(data) = self.backend.receive() {
+            let data = data & self.word_mask();
+            if self.rdrf {
+                self.overrun = true;
+            } else {
+                self.rdr = data;
+                self.rdrf = true;
+            }
+            if self.command & COMMAND_ECHO != 0 && self.tx_control() == 0b00 {
+                self.backend.transmit(data);
+            }
+            if self.rx_irq_enabled() {
+                self.set_irq(true);
+            }
+        }
+    }
+}
+
+impl<S: SerialBackend> Device for Acia<S> {
+    fn read(&mut self, offset: Address) -> Option<Byte> {
+        let value = self.register(offset);
+        match offset & 0x03 {
+            DATA => {
+                self.rdrf = false;
+                self.overrun = false;
+            }
+            STATUS => self.set_irq(false),
+            _ => {}
+        }
+        Some(value)
+    }
+
+    fn write(&mut self, offset: Address, data: Byte) {
+        match offset & 0x03 {
+            DATA => self.tdr = Some(data),
+            STATUS => {
+                // A programmed reset
+                self.command &= 0xE0;
+                self.overrun = false;
+                self.set_irq(false);
+            }
+            COMMAND => {
+                self.command = data;
+                if self.tx_irq_enabled() && self.tdr.is_none() {
+                    self.set_irq(true);
+                }
+            }
+            _ => {
+                self.control = data;
+                self.rx_countdown = self.char_cycles();
+            }
+        }
+    }
+
+    fn peek(&self, offset: Address) -> Option<Byte> {
+        Some(self.register(offset))
+    }
+
+    fn connect_irq(&mut self, line: &IrqLine) {
+        self.irq = Some(line.source("6551"));
+        let asserted = self.irq_flag;
+        self.set_irq(asserted);
+    }
+
+    fn tick(&mut self) {
+        self.tick_transmitter();
+        self.tick_receiver();
+    }
+}
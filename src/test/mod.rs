@@ -1,3 +1,20 @@
+mod test_apple1;
+mod test_ines;
+mod test_loaders;
+mod test_mappers;
+mod test_mc6821;
+mod test_memory_map;
+// These tests predate the lints the crate is checked with
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::assertions_on_constants,
+    function_casts_as_integer,
+    unused_imports
+)]
 mod test_mos6502;
 mod test_mos6502_addressing_modes;
+#[allow(clippy::bool_assert_comparison, unused_imports)]
 mod test_mos6502_instruction_set;
+mod test_mos6522;
+mod test_mos6532;
+mod test_mos6551;
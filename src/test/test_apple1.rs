@@ -0,0 +1,164 @@
+#[cfg(test)]
+mod test {
+    use crate::apple1::*;
+    use crate::mos6502::*;
+
+    /// Runs the monitor until it has read every key and waits for more
+    fn settle(apple: &mut Apple1) {
+        for _ in 0..1000 {
+            apple.run_for(1000).unwrap();
+            if apple.pending_keys() == 0 && (0xFF29..=0xFF2C).contains(&apple.cpu().pc()) {
+                return;
+            }
+        }
+        panic!("the monitor did not settle");
+    }
+
+    fn booted() -> Apple1 {
+        let mut apple = Apple1::new(8);
+        settle(&mut apple);
+        apple
+    }
+
+    #[test]
+    fn test_apple1_boot() {
+        let apple = booted();
+        assert_eq!(apple.display().lines(), vec!["\\", ""]);
+        assert_eq!(apple.display().cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_apple1_ram() {
+        let mut apple = Apple1::new(4);
+        apple.cpu_mut().writ_byte(0x0FFF, 0x42);
+        assert_eq!(apple.cpu_mut().read_byte(0x0FFF), 0x42);
+        assert!(!apple.cpu().bus().is_mapped(0x1000));
+
+        let mut apple = Apple1::new(8);
+        apple.cpu_mut().writ_byte(0x1FFF, 0x42);
+        assert_eq!(apple.cpu_mut().read_byte(0x1FFF), 0x42);
+        assert_eq!(apple.cpu_mut().read_byte(0xFF00), WOZ_MONITOR[0]);
+        assert_eq!(apple.cpu_mut().read_word(0xFFFC), 0xFF00);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apple1_too_much_ram() {
+        Apple1::new(16);
+    }
+
+    #[test]
+    fn test_apple1_examine() {
+        let mut apple = booted();
+        apple.paste("ff00.ff0a");
+        settle(&mut apple);
+        assert_eq!(
+            apple.display().lines(),
+            vec![
+                "\\",
+                "FF00.FF0A",
+                "",
+                "FF00: D8 58 A0 7F 8C 12 D0 A9",
+                "FF08: A7 8D 11",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apple1_paste_and_run() {
+        let program = [
+            0xA9, 0xC8, // LDA #'H'
+            0x20, 0xEF, 0xFF, // JSR ECHO
+            0xA9, 0xC9, // LDA #'I'
+            0x20, 0xEF, 0xFF, // JSR ECHO
+            0x4C, 0x1F, 0xFF, // JMP GETLINE
+        ];
+        let mut apple = booted();
+        apple.paste_hex_dump(0x0300, &program);
+        settle(&mut apple);
+        for (offset, &data) in program.iter().enumerate() {
+            assert_eq!(apple.cpu_mut().read_byte(0x0300 + offset as Address), data);
+        }
+        assert_eq!(
+            apple.display().lines()[1..5],
+            [
+                "0300: A9 C8 20 EF FF A9 C9 20",
+                "",
+                "0300: 00", // What was there before
+                "0308: EF FF 4C 1F FF"
+            ]
+        );
+
+        apple.display_mut().take_rendered();
+        apple.paste("300R");
+        settle(&mut apple);
+        let lines = apple.display().lines();
+        assert_eq!(lines[lines.len() - 4..], ["300R", "", "0300: A9HI", ""]);
+    }
+
+    #[test]
+    fn test_apple1_keys() {
+        let mut apple = booted();
+        for &key in b"abc\x08\x7fd" {
+            apple.key(key);
+        }
+        settle(&mut apple);
+        assert_eq!(apple.display().lines()[1], "ABC__D");
+        assert_eq!(apple.display().cursor(), (1, 6));
+
+        // The monitor stored the line as it was typed, erased keys aside
+        assert_eq!(apple.cpu_mut().read_byte(INPUT_BUFFER), b'A' | 0x80);
+        assert_eq!(apple.cpu_mut().read_byte(INPUT_BUFFER + 1), b'D' | 0x80);
+
+        // Escape cancels the line
+        apple.key(0x1B);
+        settle(&mut apple);
+        assert_eq!(apple.display().lines()[1..], ["ABC__D\\", ""]);
+    }
+
+    #[test]
+    fn test_apple1_reset() {
+        let mut apple = booted();
+        apple.paste("0:12");
+        settle(&mut apple);
+        apple.reset();
+        settle(&mut apple);
+        assert_eq!(apple.cpu_mut().read_byte(0x0000), 0x12);
+        assert_eq!(apple.display().lines().last().unwrap(), "");
+        assert!(apple.display().text().ends_with("\\\n"));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut display = Display::new();
+        assert_eq!(display.take_rendered(), b"@\x08");
+
+        for &data in b"hello, world!\x07\x0D" {
+            display.put(data | 0x80);
+        }
+        assert_eq!(display.lines(), vec!["HELLO, WORLD!", ""]);
+        let rendered = display.take_rendered();
+        assert!(rendered.starts_with(b"H@\x08E@\x08L@\x08"));
+        assert!(rendered.ends_with(b"!@\x08 \x08\r\n@\x08"));
+
+        // Lines wrap at 40 columns
+        for _ in 0..COLUMNS + 2 {
+            display.put(b'*');
+        }
+        assert_eq!(display.lines()[1], "*".repeat(COLUMNS));
+        assert_eq!(display.lines()[2], "**");
+        assert_eq!(display.cursor(), (2, 2));
+
+        // And the screen scrolls after 24 rows
+        for row in 0..ROWS {
+            display.put(b'0' + (row % 10) as Byte);
+            display.put(0x0D);
+        }
+        let lines = display.lines();
+        assert_eq!(lines.len(), ROWS);
+        assert_eq!(lines[0], "1");
+        assert_eq!(lines[ROWS - 2], "3");
+        assert_eq!(display.cursor(), (ROWS - 1, 0));
+    }
+}
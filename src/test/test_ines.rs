@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod test {
+    use crate::ines::*;
+    use crate::mappers::Mirroring;
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+
+    /// An iNES image with `prg_banks` banks of 16K, each filled with its
+    /// own number, and the reset vector of the last one pointing to 0x8000
+    fn image(flags6: Byte, flags7: Byte, prg_banks: u8, chr_banks: u8) -> Vec<Byte> {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks, flags6, flags7];
+        bytes.resize(16, 0x00);
+        if flags6 & 0x04 != 0 {
+            bytes.extend((0..512).map(|i| i as Byte));
+        }
+        for bank in 0..prg_banks {
+            bytes.extend(vec![bank; 0x4000]);
+        }
+        let vectors = bytes.len() - 4;
+        bytes[vectors..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+        bytes.extend(vec![0xCC; usize::from(chr_banks) * 0x2000]);
+        bytes
+    }
+
+    fn rom_error(reason: INesError) -> Result<INes, CpuError> {
+        Err(CpuError::BadRom(reason))
+    }
+
+    #[test]
+    fn test_ines_header() {
+        let ines = INes::parse(&image(0x23, 0x10, 2, 1)).unwrap();
+        assert!(!ines.nes2);
+        assert_eq!(ines.mapper, 0x12);
+        assert_eq!(ines.mirroring, Mirroring::Vertical);
+        assert!(ines.battery);
+        assert_eq!(ines.trainer, None);
+        assert_eq!(ines.prg.len(), 0x8000);
+        assert_eq!(ines.chr, vec![0xCC; 0x2000]);
+        assert_eq!(ines.prg_ram_size, 0x2000);
+
+        let ines = INes::parse(&image(0x0C, 0x00, 1, 0)).unwrap();
+        assert_eq!(ines.mirroring, Mirroring::FourScreen);
+        assert!(!ines.battery);
+        assert_eq!(ines.trainer.as_ref().map(Vec::len), Some(512));
+        assert!(ines.chr.is_empty());
+
+        // Garbage at the end of an old header
+        let mut bytes = image(0x10, 0x40, 1, 0);
+        bytes[12..16].copy_from_slice(b"Dude");
+        assert_eq!(INes::parse(&bytes).unwrap().mapper, 0x01);
+    }
+
+    #[test]
+    fn test_nes2_header() {
+        let mut bytes = image(0x40, 0x58, 1, 0);
+        bytes[8] = 0x31;
+        bytes[10] = 0x70;
+        let ines = INes::parse(&bytes).unwrap();
+        assert!(ines.nes2);
+        assert_eq!(ines.mapper, 0x154);
+        assert_eq!(ines.submapper, 3);
+        assert_eq!(ines.prg_ram_size, 0x2000);
+
+        // 2^14 * 1 bytes of PRG ROM in exponent-multiplier notation
+        bytes[4] = 14 << 2;
+        bytes[9] = 0x0F;
+        assert_eq!(INes::parse(&bytes).unwrap().prg.len(), 0x4000);
+
+        bytes[4] = 63 << 2 | 3;
+        assert_eq!(INes::parse(&bytes), rom_error(INesError::SizeOverflow));
+    }
+
+    #[test]
+    fn test_ines_errors() {
+        assert_eq!(
+            INes::parse(b"NES\x1A"),
+            rom_error(INesError::Truncated {
+                expected: 16,
+                found: 4,
+            })
+        );
+
+        let mut bytes = image(0x00, 0x00, 1, 1);
+        bytes[3] = 0x00;
+        assert_eq!(INes::parse(&bytes), rom_error(INesError::BadMagic));
+
+        let bytes = image(0x00, 0x00, 0, 0);
+        assert_eq!(INes::parse(&bytes), rom_error(INesError::NoPrgRom));
+
+        let bytes = image(0x00, 0x00, 1, 1);
+        assert_eq!(
+            INes::parse(&bytes[..0x5000]),
+            rom_error(INesError::Truncated {
+                expected: 16 + 0x4000 + 0x2000,
+                found: 0x5000,
+            })
+        );
+
+        let ines = INes::parse(&image(0x40, 0x00, 1, 0)).unwrap();
+        assert_eq!(
+            ines.memory_map(MemoryMap::builder()).err(),
+            Some(CpuError::BadRom(INesError::UnsupportedMapper(4)))
+        );
+
+        let ines = INes::parse(&image(0x00, 0x00, 4, 0)).unwrap();
+        assert_eq!(
+            ines.memory_map(MemoryMap::builder()).err(),
+            Some(CpuError::BadRom(INesError::BadPrgSize(0x10000)))
+        );
+
+        let ines = INes::parse(&image(0x00, 0x00, 1, 0)).unwrap();
+        let builder = MemoryMap::builder().ram(0x1000..=0x27FF);
+        assert!(matches!(
+            ines.memory_map(builder).err(),
+            Some(CpuError::BadRom(INesError::MemoryMap(
+                MemoryMapError::Overlap { .. }
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_nrom_mirrored() {
+        let mut bytes = image(0x04, 0x00, 1, 0);
+        // lda #$42 ; sta $6000
+        let prg = 16 + 512;
+        bytes[prg..prg + 5].copy_from_slice(&[0xA9, 0x42, 0x8D, 0x00, 0x60]);
+
+        let mut cpu = INes::parse(&bytes).unwrap().cpu().unwrap();
+        assert_eq!(cpu.variant(), CpuVariant::Ricoh2A03);
+        assert_eq!(cpu.pc(), 0x8000);
+        assert_eq!(cpu.time().elapsed(), 7);
+        assert_eq!(cpu.regset().stk_ptr(), 0xFD);
+
+        assert_eq!(cpu.read_byte(0xC000), 0xA9);
+        assert_eq!(cpu.read_word(0xBFFC), cpu.read_word(0xFFFC));
+        assert_eq!(cpu.read_byte(0x7000), 0x00);
+        assert_eq!(cpu.read_byte(0x71FF), 0xFF);
+
+        for _ in 0..2 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0x6000), 0x42);
+        assert_eq!(cpu.read_byte(0x0800), cpu.read_byte(0x0000));
+    }
+
+    #[test]
+    fn test_mapped_prg() {
+        // UxRom with 4 banks
+        let ines = INes::parse(&image(0x20, 0x00, 4, 0)).unwrap();
+        let mut map = ines.memory_map(MemoryMap::builder()).unwrap();
+        assert_eq!(map.read(0x8000), Some(0));
+        assert_eq!(map.read(0xC000), Some(3));
+        map.write(0x8000, 2);
+        assert_eq!(map.read(0x8000), Some(2));
+        assert_eq!(map.read(0xFFFD), Some(0x80));
+    }
+}
@@ -0,0 +1,413 @@
+#[cfg(test)]
+mod test {
+    use crate::loaders::*;
+    use crate::mos6502::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    // clc ; adc #$02 ; sta $10 -- at 0x0200, with the reset vector pointing to it
+    const IHEX: &str = "\
+:0302000018690278
+:02020300851064
+:02FFFC00000201
+:0400000500000200F5
+:00000001FF
+";
+
+    const SREC: &str = "\
+S00600004844521B
+S106020018690274
+S1050203851060
+S105FFFC0002FD
+S5030003F9
+S9030200FA
+";
+
+    fn bad_record(line: usize, reason: RecordError) -> Result<Image, CpuError> {
+        Err(CpuError::BadRecord { line, reason })
+    }
+
+    fn expected_image() -> Image {
+        Image {
+            segments: vec![
+                Segment {
+                    address: 0x0200,
+                    data: vec![0x18, 0x69, 0x02, 0x85, 0x10],
+                },
+                Segment {
+                    address: 0xFFFC,
+                    data: vec![0x00, 0x02],
+                },
+            ],
+            start: Some(0x0200),
+        }
+    }
+
+    #[test]
+    fn test_ihex() {
+        assert_eq!(Image::from_ihex(IHEX), Ok(expected_image()));
+
+        // Extended segment address and start segment address
+        let text = ":020000020001FB\n:01000000EA15\n:0400000300010000F8\n:00000001FF\n";
+        let image = Image::from_ihex(text).unwrap();
+        assert_eq!(
+            image.segments,
+            vec![Segment {
+                address: 0x0010,
+                data: vec![0xEA],
+            }]
+        );
+        assert_eq!(image.start, Some(0x0010));
+    }
+
+    #[test]
+    fn test_ihex_errors() {
+        use RecordError::*;
+
+        let with_line = |line: &str| IHEX.replacen(":02020300851064", line, 1);
+        assert_eq!(
+            Image::from_ihex(&with_line(":02020300851065")),
+            bad_record(
+                2,
+                BadChecksum {
+                    expected: 0x64,
+                    found: 0x65,
+                }
+            )
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line("02020300851064")),
+            bad_record(2, MissingStartCode)
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":0202030085106")),
+            bad_record(2, Truncated)
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":020203008510G4")),
+            bad_record(2, BadDigit)
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":03020300851063")),
+            bad_record(
+                2,
+                LengthMismatch {
+                    expected: 3,
+                    found: 2,
+                }
+            )
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":00000006FA")),
+            bad_record(2, UnknownType(0x06))
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":0100000100FE")),
+            bad_record(2, Malformed)
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":020000020010EC")),
+            bad_record(3, AddressOutOfRange)
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":020000040001F9")),
+            bad_record(3, AddressOutOfRange)
+        );
+        assert_eq!(
+            Image::from_ihex(&with_line(":0400000310000000E9")),
+            bad_record(2, AddressOutOfRange)
+        );
+        assert_eq!(
+            Image::from_ihex(":02000004FFFFFC\n:01FFFF000001\n:00000001FF\n"),
+            bad_record(2, AddressOutOfRange)
+        );
+
+        assert_eq!(
+            Image::from_ihex(&IHEX.replace(":00000001FF\n", "")),
+            bad_record(4, MissingEnd)
+        );
+        assert_eq!(
+            Image::from_ihex(&format!("{}:02020300851064\n", IHEX)),
+            bad_record(6, DataAfterEnd)
+        );
+    }
+
+    #[test]
+    fn test_srec() {
+        assert_eq!(Image::from_srec(SREC), Ok(expected_image()));
+
+        let image = Image::from_srec("s804000300F8\n").unwrap();
+        assert!(image.segments.is_empty());
+        assert_eq!(image.start, Some(0x0300));
+    }
+
+    #[test]
+    fn test_srec_errors() {
+        use RecordError::*;
+
+        let with_line = |line: &str| SREC.replacen("S1050203851060", line, 1);
+        assert_eq!(
+            Image::from_srec(&with_line("S1050203851061")),
+            bad_record(
+                3,
+                BadChecksum {
+                    expected: 0x60,
+                    found: 0x61,
+                }
+            )
+        );
+        assert_eq!(
+            Image::from_srec(&with_line(":1050203851060")),
+            bad_record(3, MissingStartCode)
+        );
+        assert_eq!(
+            Image::from_srec(&with_line("S4050203851060")),
+            bad_record(3, UnknownType(4))
+        );
+        assert_eq!(
+            Image::from_srec(&with_line("S205010000EA0F")),
+            bad_record(3, AddressOutOfRange)
+        );
+        assert_eq!(
+            Image::from_srec(&with_line("S306FFFFFFFF00FD")),
+            bad_record(3, AddressOutOfRange)
+        );
+        assert_eq!(
+            Image::from_srec(&SREC.replace("S5030003F9", "S5030002FA")),
+            bad_record(
+                5,
+                RecordCountMismatch {
+                    expected: 2,
+                    found: 3,
+                }
+            )
+        );
+        assert_eq!(
+            Image::from_srec(&SREC.replace("S9030200FA\n", "")),
+            bad_record(5, MissingEnd)
+        );
+        assert_eq!(Image::from_srec(""), bad_record(1, MissingEnd));
+    }
+
+    #[test]
+    fn test_load_image() {
+        let mut cpu = Cpu::new_custompc(0x0400);
+        let image = Image::from_srec(SREC).unwrap();
+        assert_eq!(
+            cpu.load_image(&image, true),
+            Err(CpuError::BusInterfaceMissing)
+        );
+
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        assert_eq!(cpu.load_image(&image, false), Ok(0x0400));
+        assert_eq!(cpu.pc(), 0x0400);
+        assert_eq!(cpu.read_word(0xFFFC), 0x0200);
+
+        let path = std::env::temp_dir().join("m6502_test_load_image.hex");
+        std::fs::write(&path, IHEX).unwrap();
+        let loaded = cpu.load_ihex_file(path.to_str().unwrap(), true);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Ok(0x0400));
+        assert_eq!(cpu.pc(), 0x0200);
+
+        for _ in 0..3 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0x0010), 0x02);
+
+        assert_eq!(
+            cpu.load_srec_file("does/not/exist.s19", true),
+            Err(CpuError::FailedLoadingProgram)
+        );
+    }
+
+    #[test]
+    fn test_prg() {
+        let image = Image::from_prg(&[0x01, 0xC0, 0xA9, 0x01, 0x60]).unwrap();
+        assert_eq!(
+            image.segments,
+            vec![Segment {
+                address: 0xC001,
+                data: vec![0xA9, 0x01, 0x60],
+            }]
+        );
+        assert_eq!(image.start, Some(0xC001));
+
+        assert_eq!(
+            Image::from_prg(&[0x01]),
+            Err(CpuError::BadObject {
+                offset: 1,
+                reason: ObjectError::Truncated,
+            })
+        );
+        assert_eq!(
+            Image::from_prg(&[0xFF, 0xFF, 0xEA, 0xEA]),
+            Err(CpuError::BadObject {
+                offset: 2,
+                reason: ObjectError::AddressOutOfRange,
+            })
+        );
+    }
+
+    /// An object assembled for text at $1000, data at $2000, bss
+    /// at $2003 and zero page at $10:
+    ///
+    /// ```ignore
+    ///         .text
+    /// main:   lda label       ; AD 02 20
+    ///         jsr putc        ; 20 00 00
+    ///         lda #>label     ; A9 20
+    ///         lda #<label     ; A9 02
+    ///         sta var         ; 85 10
+    ///         rts             ; 60
+    ///         .data
+    ///         .word main      ; 00 10
+    /// label:  .byte $42
+    ///         .bss
+    ///         .dsb 4
+    ///         .zero
+    /// var:    .dsb 2
+    /// ```
+    fn o65() -> Vec<Byte> {
+        let mut bytes = vec![0x01, 0x00, b'o', b'6', b'5', 0x00];
+        // Mode, with cleared bss
+        bytes.extend_from_slice(&[0x00, 0x02]);
+        for word in &[0x1000u16, 13, 0x2000, 3, 0x2003, 4, 0x0010, 2, 0] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        // An option, then the end of them
+        bytes.extend_from_slice(&[0x04, 0x01, 0xAA, 0xBB, 0x00]);
+        bytes.extend_from_slice(&[
+            0xAD, 0x02, 0x20, 0x20, 0x00, 0x00, 0xA9, 0x20, 0xA9, 0x02, 0x85, 0x10, 0x60,
+        ]);
+        bytes.extend_from_slice(&[0x00, 0x10, 0x42]);
+        // Undefined references
+        bytes.extend_from_slice(&[0x01, 0x00, b'p', b'u', b't', b'c', 0x00]);
+        // Text relocations: word/data, word/putc, high/data, low/data, low/zero
+        bytes.extend_from_slice(&[
+            0x02, 0x83, 0x03, 0x80, 0x00, 0x00, 0x03, 0x43, 0x02, 0x02, 0x23, 0x02, 0x25, 0x00,
+        ]);
+        // Data relocations: word/text
+        bytes.extend_from_slice(&[0x01, 0x82, 0x00]);
+        // Exports
+        bytes.extend_from_slice(&[0x01, 0x00, b'm', b'a', b'i', b'n', 0x00, 0x02, 0x00, 0x10]);
+        bytes
+    }
+
+    fn putc() -> HashMap<String, Address> {
+        let mut symbols = HashMap::new();
+        symbols.insert(String::from("putc"), 0xFFD2);
+        symbols
+    }
+
+    #[test]
+    fn test_o65_relocation() {
+        let o65 = O65::parse(&o65()).unwrap();
+        assert_eq!(o65.undefined, vec![String::from("putc")]);
+        assert_eq!(o65.zero_len, 2);
+
+        let mut bases = o65.bases_at(0x3000);
+        assert_eq!(
+            bases,
+            O65Bases {
+                text: 0x3000,
+                data: 0x300D,
+                bss: 0x3010,
+                zero: 0x0010,
+            }
+        );
+        bases.zero = 0x0080;
+
+        let symbols = putc();
+        let image = o65.relocate(&bases, &symbols).unwrap();
+        assert_eq!(
+            image.segments,
+            vec![Segment {
+                address: 0x3000,
+                data: vec![
+                    0xAD, 0x0F, 0x30, 0x20, 0xD2, 0xFF, 0xA9, 0x30, 0xA9, 0x0F, 0x85, 0x80, 0x60,
+                    0x00, 0x30, 0x42, 0x00, 0x00, 0x00, 0x00,
+                ],
+            }]
+        );
+        assert_eq!(image.start, Some(0x3000));
+        assert_eq!(o65.symbol_table(&bases)["main"], 0x3000);
+
+        assert_eq!(
+            o65.relocate(&bases, &HashMap::new()),
+            Err(CpuError::BadObject {
+                offset: 56,
+                reason: ObjectError::UndefinedSymbol(0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_o65_errors() {
+        let bad_object = |offset, reason| Err(CpuError::BadObject { offset, reason });
+
+        let mut bytes = o65();
+        bytes[2] = b'O';
+        assert_eq!(O65::parse(&bytes), bad_object(0, ObjectError::BadMagic));
+
+        let mut bytes = o65();
+        bytes[7] = 0x20;
+        assert_eq!(
+            O65::parse(&bytes),
+            bad_object(6, ObjectError::UnsupportedMode(0x2000))
+        );
+
+        let bytes = o65();
+        assert_eq!(
+            O65::parse(&bytes[..40]),
+            bad_object(40, ObjectError::Truncated)
+        );
+
+        // The first text relocation
+        let mut bytes = o65();
+        bytes[55] = 0x87;
+        assert_eq!(
+            O65::parse(&bytes),
+            bad_object(55, ObjectError::BadSegment(0x07))
+        );
+        bytes[55] = 0xE3;
+        assert_eq!(
+            O65::parse(&bytes),
+            bad_object(55, ObjectError::BadRelocation(0xE3))
+        );
+        bytes[54] = 0x0D;
+        bytes[55] = 0x83;
+        assert_eq!(
+            O65::parse(&bytes),
+            bad_object(54, ObjectError::RelocationOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_load_object_files() {
+        let mut cpu = Cpu::default();
+        let dir = std::env::temp_dir();
+
+        let prg = dir.join("m6502_test_load.prg");
+        std::fs::write(&prg, [0x00, 0x02, 0xA9, 0x2A, 0x85, 0x10]).unwrap();
+        let loaded = cpu.load_prg_file(prg.to_str().unwrap(), true);
+        std::fs::remove_file(&prg).unwrap();
+        assert_eq!(loaded, Ok(0x0000));
+        assert_eq!(cpu.pc(), 0x0200);
+        for _ in 0..2 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0x0010), 0x2A);
+
+        let o65 = dir.join("m6502_test_load.o65");
+        std::fs::write(&o65, self::o65()).unwrap();
+        let symbols = putc();
+        let loaded = cpu.load_o65_file(o65.to_str().unwrap(), 0x0400, &symbols, true);
+        std::fs::remove_file(&o65).unwrap();
+        assert_eq!(loaded, Ok(0x0204));
+        assert_eq!(cpu.pc(), 0x0400);
+        assert_eq!(cpu.read_word(0x0404), 0xFFD2);
+        assert_eq!(cpu.read_word(0x040D), 0x0400);
+    }
+}
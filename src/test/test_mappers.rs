@@ -0,0 +1,292 @@
+#[cfg(test)]
+mod test {
+    use crate::mappers::*;
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    /// `count` banks of `size` bytes, each filled with its own number
+    fn numbered_banks(count: usize, size: usize) -> Vec<Byte> {
+        (0..count)
+            .flat_map(|bank| vec![bank as Byte; size])
+            .collect()
+    }
+
+    #[test]
+    fn test_banks() {
+        let mut banks = Banks::new(vec![1, 2, 3, 4, 5], 2);
+        assert_eq!(banks.count(), 3);
+        assert_eq!(banks.read(0, 1), 2);
+        assert_eq!(banks.read(2, 0), 5);
+        assert_eq!(banks.read(2, 1), 0);
+        assert_eq!(banks.read(4, 1), 4);
+
+        banks.write(3, 0, 9);
+        assert_eq!(banks.read(0, 0), 9);
+        assert_eq!(Banks::new(vec![], 16).count(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_banks_empty() {
+        Banks::new(vec![1, 2, 3], 0);
+    }
+
+    #[test]
+    fn test_bank_switcher() {
+        let mut switcher = BankSwitcher::with_8k_banks(numbered_banks(8, 0x2000));
+        assert_eq!(switcher.read(0x0000), Some(0));
+        assert_eq!(switcher.read(0x7FFF), Some(3));
+
+        switcher.write(0x2100, 6);
+        assert_eq!(switcher.bank(1), 6);
+        assert_eq!(switcher.read(0x2000), Some(6));
+        assert_eq!(switcher.read(0x0000), Some(0));
+        assert_eq!(switcher.peek_bank(5, 0x2000), Some(5));
+
+        let mut switcher = BankSwitcher::with_16k_banks(numbered_banks(4, 0x4000));
+        switcher.write(0x4000, 2);
+        assert_eq!(switcher.read(0x7FFF), Some(2));
+        assert_eq!(switcher.read(0x3FFF), Some(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bank_switcher_no_windows() {
+        BankSwitcher::new(vec![1, 2, 3], 2, 0);
+    }
+
+    #[test]
+    fn test_uxrom() {
+        let mut uxrom = UxRom::new(numbered_banks(8, 0x4000), vec![], Mirroring::Vertical);
+        assert_eq!(uxrom.read(0x0000), Some(0));
+        assert_eq!(uxrom.read(0x4000), Some(7));
+
+        uxrom.write(0x1234, 3);
+        assert_eq!(uxrom.bank(), 3);
+        assert_eq!(uxrom.read(0x3FFF), Some(3));
+        assert_eq!(uxrom.read(0x7FFF), Some(7));
+
+        // CHR RAM
+        uxrom.write_chr(0x0123, 0x42);
+        assert_eq!(uxrom.read_chr(0x0123), 0x42);
+        assert_eq!(uxrom.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_cnrom() {
+        let mut cnrom = CnRom::new(
+            numbered_banks(1, 0x4000),
+            numbered_banks(4, 0x2000),
+            Mirroring::Horizontal,
+        );
+        // 16K of PRG ROM are mirrored
+        assert_eq!(cnrom.read(0x0000), Some(0));
+        assert_eq!(cnrom.read(0x4000), Some(0));
+
+        assert_eq!(cnrom.read_chr(0x0000), 0);
+        cnrom.write(0x0000, 2);
+        assert_eq!(cnrom.chr_bank(), 2);
+        assert_eq!(cnrom.read_chr(0x1FFF), 2);
+
+        // CHR ROM
+        cnrom.write_chr(0x0000, 0x42);
+        assert_eq!(cnrom.read_chr(0x0000), 2);
+    }
+
+    fn mmc1_write(mmc1: &mut Mmc1, offset: Address, value: Byte) {
+        for bit in 0..5 {
+            mmc1.write(offset, (value >> bit) & 1);
+        }
+    }
+
+    #[test]
+    fn test_mmc1_prg() {
+        let mut mmc1 = Mmc1::new(numbered_banks(8, 0x4000), vec![]);
+        // Fixed last bank at 0xC000 after power on
+        assert_eq!(mmc1.read(0x0000), Some(0));
+        assert_eq!(mmc1.read(0x4000), Some(7));
+
+        mmc1_write(&mut mmc1, 0x6000, 5);
+        assert_eq!(mmc1.prg_bank(), 5);
+        assert_eq!(mmc1.read(0x0000), Some(5));
+        assert_eq!(mmc1.read(0x4000), Some(7));
+
+        // Fixed first bank at 0x8000
+        mmc1_write(&mut mmc1, 0x0000, 0b01000);
+        assert_eq!(mmc1.read(0x0000), Some(0));
+        assert_eq!(mmc1.read(0x4000), Some(5));
+
+        // 32K mode ignores the lowest bit of the bank
+        mmc1_write(&mut mmc1, 0x0000, 0b00000);
+        assert_eq!(mmc1.read(0x0000), Some(4));
+        assert_eq!(mmc1.read(0x4000), Some(5));
+    }
+
+    #[test]
+    fn test_mmc1_serial_reset() {
+        let mut mmc1 = Mmc1::new(numbered_banks(8, 0x4000), vec![]);
+        mmc1_write(&mut mmc1, 0x0000, 0b00010);
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+        assert_eq!(mmc1.control(), 0b00010);
+
+        // A reset in the middle of a serial write
+        mmc1.write(0x6000, 1);
+        mmc1.write(0x6000, 1);
+        mmc1.write(0x0000, 0x80);
+        assert_eq!(mmc1.control(), 0b01110);
+        mmc1_write(&mut mmc1, 0x6000, 2);
+        assert_eq!(mmc1.prg_bank(), 2);
+        assert_eq!(mmc1.read(0x0000), Some(2));
+    }
+
+    #[test]
+    fn test_mmc1_chr() {
+        let mut mmc1 = Mmc1::new(numbered_banks(2, 0x4000), numbered_banks(8, 0x1000));
+        // 8K mode
+        mmc1_write(&mut mmc1, 0x2000, 5);
+        assert_eq!(mmc1.read_chr(0x0000), 4);
+        assert_eq!(mmc1.read_chr(0x1000), 5);
+
+        // 4K mode
+        mmc1_write(&mut mmc1, 0x0000, 0b11111);
+        mmc1_write(&mut mmc1, 0x4000, 2);
+        assert_eq!(mmc1.mirroring(), Mirroring::Horizontal);
+        assert_eq!(mmc1.read_chr(0x0000), 5);
+        assert_eq!(mmc1.read_chr(0x1000), 2);
+    }
+
+    /// An I/O area which remembers the last write
+    #[derive(Default)]
+    struct Io {
+        last: Option<(Address, Byte)>,
+    }
+
+    impl Device for Io {
+        fn read(&mut self, offset: Address) -> Option<Byte> {
+            Some(offset as Byte)
+        }
+
+        fn write(&mut self, offset: Address, data: Byte) {
+            self.last = Some((offset, data));
+        }
+
+        fn peek(&self, offset: Address) -> Option<Byte> {
+            Some(offset as Byte)
+        }
+    }
+
+    #[test]
+    fn test_c64_pla() {
+        let io = Arc::new(Mutex::new(Io::default()));
+        let mut pla = C64Pla::new(vec![0xBA; 0x2000], vec![0xFE; 0x2000], vec![0xC4; 0x1000])
+            .with_io(io.clone());
+        assert_eq!(pla.config(), 0b111);
+        assert_eq!(pla.read(0xA000), Some(0xBA));
+        assert_eq!(pla.read(0xD020), Some(0x20));
+        assert_eq!(pla.read(0xFFFF), Some(0xFE));
+
+        // Writes to ROM go to the RAM underneath
+        pla.write(0xA000, 0x11);
+        pla.write(0xE000, 0x22);
+        pla.write(0xD020, 0x33);
+        assert_eq!(pla.read(0xA000), Some(0xBA));
+        assert_eq!(io.lock().unwrap().last, Some((0x0020, 0x33)));
+
+        // Character ROM instead of I/O
+        pla.write(0x0001, 0b011);
+        assert_eq!(pla.read(0xD000), Some(0xC4));
+
+        // Only the KERNAL
+        pla.write(0x0001, 0b110);
+        assert_eq!(pla.read(0xA000), Some(0x11));
+        assert_eq!(pla.read(0xE000), Some(0xFE));
+
+        // RAM everywhere
+        pla.write(0x0001, 0b100);
+        assert_eq!(pla.read(0xE000), Some(0x22));
+        assert_eq!(pla.read(0xD020), Some(0x00));
+
+        // The bits which are inputs read as set
+        pla.write(0x0000, 0b000);
+        assert_eq!(pla.config(), 0b111);
+        assert_eq!(pla.read(0xA000), Some(0xBA));
+
+        assert_eq!(pla.peek_bank(0b000, 0xA000), Some(0x11));
+        assert_eq!(pla.peek_bank(0b011, 0xD000), Some(0xC4));
+    }
+
+    #[test]
+    fn test_disassemble_bank() {
+        // Each bank starts with `lda #bank+1 ; sta $8000`
+        let mut prg = numbered_banks(4, 0x4000);
+        for bank in 0..4 {
+            let code = [0xA9, bank as Byte + 1, 0x8D, 0x00, 0x80];
+            prg[bank * 0x4000..bank * 0x4000 + 5].copy_from_slice(&code);
+        }
+        let uxrom = Arc::new(Mutex::new(UxRom::new(prg, vec![], Mirroring::Vertical)));
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x07FF)
+            .device(0x8000..=0xFFFF, uxrom.clone())
+            .build()
+            .unwrap();
+        let mut cpu = Cpu::new_custompc(0x8000);
+        cpu.connect_to(Rc::new(RefCell::new(map)));
+
+        let asm = Asm::from_bank_range(&cpu, 2, 0x8000, 5).unwrap();
+        assert_eq!(asm.code().len(), 2);
+        assert_eq!(asm.code()[0].operand(), Some(0x03));
+        assert_eq!(asm.code()[1].operand(), Some(0x8000));
+        assert_eq!(asm.code()[1].loaded_from(), 0x8002);
+        assert_eq!(uxrom.lock().unwrap().bank(), 0);
+
+        assert_eq!(
+            Asm::from_addr_range(&mut cpu, 0x8000, 5),
+            Asm::from_bank_range(&cpu, 0, 0x8000, 5).unwrap()
+        );
+
+        // Running the code of bank 0 switches to bank 1
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(uxrom.lock().unwrap().bank(), 1);
+        assert_eq!(cpu.read_byte(0x8001), 0x02);
+
+        let cpu = Cpu::default();
+        assert_eq!(Asm::from_bank_range(&cpu, 2, 0x8000, 5), None);
+    }
+
+    #[test]
+    fn test_disassemble_bank_full_window() {
+        // Bank 2 is `jam` all over, the fixed bank 3 is `slo ($03,x)`
+        // up to a `nop` and a `jmp` in the very last byte
+        let mut prg = numbered_banks(4, 0x4000);
+        let len = prg.len();
+        prg[len - 4] = 0xEA;
+        prg[len - 1] = 0x4C;
+        let uxrom = Arc::new(Mutex::new(UxRom::new(prg, vec![], Mirroring::Vertical)));
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x07FF)
+            .device(0x8000..=0xFFFF, uxrom)
+            .build()
+            .unwrap();
+        let mut cpu = Cpu::new_custompc(0x8000);
+        cpu.connect_to(Rc::new(RefCell::new(map)));
+
+        let asm = Asm::from_bank_range(&cpu, 2, 0x8000, 0x8000).unwrap();
+        let code = asm.code();
+        assert_eq!(code.len(), 0x4000 + 0x1FFE + 3);
+        assert_eq!(code[0].loaded_from(), 0x8000);
+        assert_eq!(code[0x4000].loaded_from(), 0xC000);
+
+        // The operand of the last instruction wraps around
+        let last = code.last().unwrap();
+        assert_eq!(last.loaded_from(), 0xFFFF);
+        assert_eq!(last.mnemonic(), "jmp");
+        assert_eq!(last.operand(), Some(0x0000));
+
+        let asm = Asm::from_addr_range(&mut cpu, 0xFFFC, 0xFFFF);
+        assert_eq!(asm.code().len(), 3);
+    }
+}
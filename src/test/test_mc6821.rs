@@ -0,0 +1,237 @@
+#[cfg(test)]
+mod test {
+    use crate::mc6821::*;
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+    use std::sync::{Arc, Mutex};
+
+    fn flags(pia: &Pia, control: Address) -> Byte {
+        pia.peek(control).unwrap() & (CR_C1_FLAG | CR_C2_FLAG)
+    }
+
+    #[test]
+    fn test_pia_registers() {
+        let mut pia = Pia::new();
+
+        // The DDRs are selected after a reset
+        pia.write(DATA_A, 0xF0);
+        pia.write(DATA_B, 0x0F);
+        assert_eq!(pia.read(DATA_A), Some(0xF0));
+        assert_eq!(pia.read(DATA_B), Some(0x0F));
+
+        pia.write(CRA, CR_DATA);
+        pia.write(CRB, CR_DATA);
+        pia.write(DATA_A, 0xAA);
+        pia.write(DATA_B, 0xAA);
+        pia.set_port_a(0x55);
+        pia.set_port_b(0x55);
+        assert_eq!(pia.read(DATA_A), Some(0xA5));
+        assert_eq!(pia.read(DATA_B), Some(0x5A));
+        assert_eq!(pia.port_a(), 0xA5);
+        assert_eq!(pia.port_b(), 0x5A);
+
+        // The flags are read only
+        pia.write(CRA, 0xFF);
+        assert_eq!(pia.read(CRA), Some(0x3F));
+
+        pia.reset();
+        assert_eq!(pia.read(CRA), Some(0x00));
+        assert_eq!(pia.read(DATA_A), Some(0x00));
+        assert_eq!(pia.port_a(), 0x55);
+    }
+
+    #[test]
+    fn test_pia_c1_edges() {
+        let line = IrqLine::new();
+        let mut pia = Pia::new();
+        pia.connect_irq(&line);
+
+        // A falling edge by default
+        pia.set_ca1(false);
+        assert_eq!(flags(&pia, CRA), CR_C1_FLAG);
+        assert!(!line.asserted());
+
+        // Reading the DDR leaves the flags alone, reading the data clears them
+        pia.read(DATA_A);
+        assert_eq!(flags(&pia, CRA), CR_C1_FLAG);
+        pia.write(CRA, CR_DATA | CR_C1_IRQ);
+        assert!(line.asserted());
+        pia.read(DATA_A);
+        assert_eq!(flags(&pia, CRA), 0x00);
+        assert!(!line.asserted());
+
+        // A rising edge on CB1
+        pia.write(CRB, CR_DATA | CR_C1_IRQ | CR_C1_RISING);
+        pia.set_cb1(false);
+        assert_eq!(flags(&pia, CRB), 0x00);
+        pia.set_cb1(true);
+        assert_eq!(flags(&pia, CRB), CR_C1_FLAG);
+        assert_eq!(line.asserting(), vec!["6821".to_string()]);
+        pia.read(DATA_B);
+        assert!(!line.asserted());
+    }
+
+    #[test]
+    fn test_pia_c2_inputs() {
+        let line = IrqLine::new();
+        let mut pia = Pia::new();
+        pia.connect_irq(&line);
+
+        // A falling edge, without an IRQ
+        pia.write(CRA, CR_DATA);
+        pia.set_ca2(false);
+        assert_eq!(flags(&pia, CRA), CR_C2_FLAG);
+        assert!(!line.asserted());
+        pia.read(DATA_A);
+
+        // A rising edge, with an IRQ
+        pia.write(CRA, CR_DATA | 0x18);
+        pia.set_ca2(true);
+        assert_eq!(flags(&pia, CRA), CR_C2_FLAG);
+        assert!(line.asserted());
+        pia.read(DATA_A);
+        assert!(!line.asserted());
+
+        pia.write(CRB, CR_DATA | 0x08);
+        pia.set_cb2(false);
+        assert_eq!(flags(&pia, CRB), CR_C2_FLAG);
+        assert!(line.asserted());
+
+        // An output does not set the flag
+        pia.read(DATA_B);
+        pia.write(CRB, CR_DATA | 0x38);
+        pia.set_cb2(true);
+        pia.set_cb2(false);
+        assert_eq!(flags(&pia, CRB), 0x00);
+
+        // Switching to an output clears a latched flag
+        pia.write(CRA, CR_DATA);
+        pia.set_ca2(true);
+        pia.set_ca2(false);
+        assert_eq!(flags(&pia, CRA), CR_C2_FLAG);
+        pia.write(CRA, CR_DATA | 0x30);
+        assert_eq!(flags(&pia, CRA), 0x00);
+        pia.write(CRA, CR_DATA);
+        assert_eq!(flags(&pia, CRA), 0x00);
+    }
+
+    #[test]
+    fn test_pia_c2_outputs() {
+        let mut pia = Pia::new();
+
+        // CA2 handshakes on reads, ended by CA1
+        pia.write(CRA, CR_DATA | 0x20);
+        assert!(pia.ca2());
+        pia.write(DATA_A, 0x00);
+        assert!(pia.ca2());
+        pia.read(DATA_A);
+        assert!(!pia.ca2());
+        pia.tick();
+        assert!(!pia.ca2());
+        pia.set_ca1(false);
+        assert!(pia.ca2());
+
+        // CB2 handshakes on writes, ended by CB1
+        pia.write(CRB, CR_DATA | 0x20);
+        pia.read(DATA_B);
+        assert!(pia.cb2());
+        pia.write(DATA_B, 0x00);
+        assert!(!pia.cb2());
+        pia.set_cb1(false);
+        assert!(pia.cb2());
+
+        // Pulses last one cycle
+        pia.write(CRA, CR_DATA | 0x28);
+        pia.read(DATA_A);
+        assert!(!pia.ca2());
+        pia.tick();
+        assert!(pia.ca2());
+
+        // Fixed levels
+        pia.write(CRB, CR_DATA | 0x30);
+        assert!(!pia.cb2());
+        pia.write(CRB, CR_DATA | 0x38);
+        assert!(pia.cb2());
+    }
+
+    #[test]
+    fn test_pia_keys_and_output() {
+        let mut pia = Pia::new();
+        pia.write(DATA_B, 0x7F);
+        pia.write(CRA, 0xA7);
+        pia.write(CRB, 0xA7);
+
+        pia.push_keys(b"AB");
+        assert_eq!(pia.pending_keys(), 2);
+        pia.tick();
+        assert_eq!(flags(&pia, CRA), CR_C1_FLAG);
+        assert_eq!(pia.pending_keys(), 1);
+
+        // The next key waits until the last one is read
+        pia.tick();
+        assert_eq!(pia.read(DATA_A), Some(b'A'));
+        assert_eq!(flags(&pia, CRA), 0x00);
+        pia.tick();
+        assert_eq!(flags(&pia, CRA), CR_C1_FLAG);
+        assert_eq!(pia.read(DATA_A), Some(b'B'));
+        pia.tick();
+        assert_eq!(flags(&pia, CRA), 0x00);
+        assert_eq!(pia.pending_keys(), 0);
+
+        // Only what is written to the data register is output
+        pia.write(DATA_B, b'O' | 0x80);
+        pia.write(DATA_B, b'K' | 0x80);
+        pia.write(CRB, 0x00);
+        pia.write(DATA_B, 0x7F);
+        assert_eq!(pia.output(), &[b'O' | 0x80, b'K' | 0x80]);
+        assert_eq!(pia.take_output(), vec![b'O' | 0x80, b'K' | 0x80]);
+        assert!(pia.output().is_empty());
+    }
+
+    #[test]
+    fn test_pia_echoes_cpu() {
+        let mut rom = vec![0xEA; 0x1000];
+        rom[..0x20].copy_from_slice(&[
+            0xA9, 0x7F, // LDA #$7F
+            0x8D, 0x12, 0xD0, // STA $D012
+            0xA9, 0xA7, // LDA #$A7
+            0x8D, 0x11, 0xD0, // STA $D011
+            0x8D, 0x13, 0xD0, // STA $D013
+            0xAD, 0x11, 0xD0, // LDA $D011
+            0x10, 0xFB, // BPL $F00D
+            0xAD, 0x10, 0xD0, // LDA $D010
+            0x2C, 0x12, 0xD0, // BIT $D012
+            0x30, 0xFB, // BMI $F015
+            0x8D, 0x12, 0xD0, // STA $D012
+            0x4C, 0x0D, 0xF0, // JMP $F00D
+        ]);
+        rom[0xFFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF0]);
+
+        let pia = Arc::new(Mutex::new(Pia::new()));
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x0FFF)
+            .device(0xD010..=0xD013, pia.clone())
+            .rom(0xF000..=0xFFFF, &rom)
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::with_bus(map);
+        cpu.reset_with(ResetKind::PowerOn);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+
+        // PB7 tells that the display is busy
+        pia.lock().unwrap().set_port_b(0x80);
+        pia.lock().unwrap().push_keys(b"HI\r");
+        for _ in 0..100 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert!(pia.lock().unwrap().output().is_empty());
+
+        pia.lock().unwrap().set_port_b(0x00);
+        while pia.lock().unwrap().output().len() < 3 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+            assert!(cpu.time().elapsed() < 1000);
+        }
+        assert_eq!(pia.lock().unwrap().output(), b"HI\r");
+    }
+}
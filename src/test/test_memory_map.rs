@@ -0,0 +1,507 @@
+#[cfg(test)]
+mod test {
+    use crate::mc6821::Pia;
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    /// A device with 4 registers which counts the reads of its last one
+    #[derive(Default)]
+    struct Latch {
+        regs: [Byte; 4],
+        reads: Byte,
+        irq: Option<IrqSource>,
+    }
+
+    impl Device for Latch {
+        fn read(&mut self, offset: Address) -> Option<Byte> {
+            if offset == 3 {
+                self.reads += 1;
+                return Some(self.reads);
+            }
+            Some(self.regs[usize::from(offset)])
+        }
+
+        fn write(&mut self, offset: Address, data: Byte) {
+            self.regs[usize::from(offset)] = data;
+            if let Some(irq) = &self.irq {
+                irq.set(offset == 0 && data != 0);
+            }
+        }
+
+        fn peek(&self, offset: Address) -> Option<Byte> {
+            Some(self.regs[usize::from(offset)])
+        }
+
+        fn connect_irq(&mut self, line: &IrqLine) {
+            self.irq = Some(line.source("latch"));
+        }
+    }
+
+    #[test]
+    fn test_ram_masked() {
+        let mut map = MemoryMap::builder()
+            .ram(0x0000..=0x1FFF)
+            .mask(0x07FF)
+            .build()
+            .unwrap();
+
+        map.write(0x0012, 0xAB);
+        assert_eq!(map.read(0x0812), Some(0xAB));
+        assert_eq!(map.read(0x1012), Some(0xAB));
+        assert_eq!(map.read(0x1812), Some(0xAB));
+
+        map.write(0x1FFF, 0xCD);
+        assert_eq!(map.read(0x07FF), Some(0xCD));
+    }
+
+    #[test]
+    fn test_rom() {
+        let mut rom = vec![0xEA; 0x4000];
+        rom[0] = 0x01;
+        rom[0x3FFF] = 0x02;
+        let mut map = MemoryMap::builder()
+            .rom(0x8000..=0xFFFF, &rom)
+            .mask(0x3FFF)
+            .build()
+            .unwrap();
+
+        assert_eq!(map.read(0x8000), Some(0x01));
+        assert_eq!(map.read(0xC000), Some(0x01));
+        assert_eq!(map.read(0xFFFF), Some(0x02));
+
+        map.write(0x8000, 0xFF);
+        assert_eq!(map.read(0x8000), Some(0x01));
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut map = MemoryMap::builder()
+            .ram(0x0000..=0x00FF)
+            .mirror(0x4000..=0x4FFF, 0x0080)
+            .mask(0x000F)
+            .build()
+            .unwrap();
+
+        map.write(0x0085, 0x42);
+        assert_eq!(map.read(0x4005), Some(0x42));
+        assert_eq!(map.read(0x4FF5), Some(0x42));
+
+        map.write(0x4001, 0x24);
+        assert_eq!(map.read(0x0081), Some(0x24));
+    }
+
+    #[test]
+    fn test_device() {
+        let latch = Arc::new(Mutex::new(Latch::default()));
+        let mut map = MemoryMap::builder()
+            .device(0xD010..=0xD01F, latch.clone())
+            .mask(0x0003)
+            .build()
+            .unwrap();
+
+        map.write(0xD011, 0x11);
+        assert_eq!(latch.lock().unwrap().regs[1], 0x11);
+        assert_eq!(map.read(0xD015), Some(0x11));
+
+        // Reads have side effects, peeks do not
+        assert_eq!(map.read(0xD013), Some(1));
+        assert_eq!(map.read(0xD017), Some(2));
+        assert_eq!(map.read_seq(0xD010, 4), Some(vec![0x00, 0x11, 0x00, 0x00]));
+        assert_eq!(latch.lock().unwrap().reads, 2);
+    }
+
+    #[test]
+    fn test_open_bus() {
+        let mut map = MemoryMap::builder().ram(0x0000..=0x00FF).build().unwrap();
+        assert!(map.is_mapped(0x00FF));
+        assert!(!map.is_mapped(0x0100));
+
+        map.write(0x0010, 0x5A);
+        assert_eq!(map.read(0x0010), Some(0x5A));
+        assert_eq!(map.read(0x8000), Some(0x5A));
+        map.write(0x8000, 0x77);
+        assert_eq!(map.read(0x8000), Some(0x77));
+        assert_eq!(map.read(0x0010), Some(0x5A));
+
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x00FF)
+            .open_bus(OpenBus::Fixed(0xFF))
+            .build()
+            .unwrap();
+        assert_eq!(map.read(0x0010), Some(0x00));
+        assert_eq!(map.read(0x8000), Some(0xFF));
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let overlap = MemoryMap::builder()
+            .ram(0x0000..=0x07FF)
+            .rom(0xC000..=0xFFFF, &[])
+            .ram(0x0400..=0x0FFF)
+            .build();
+        assert_eq!(
+            overlap.err(),
+            Some(MemoryMapError::Overlap {
+                first: (0x0000, 0x07FF),
+                second: (0x0400, 0x0FFF),
+            })
+        );
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = MemoryMap::builder().ram(0x0100..=0x00FF).build();
+        assert_eq!(
+            empty.err(),
+            Some(MemoryMapError::EmptyRange {
+                start: 0x0100,
+                end: 0x00FF,
+            })
+        );
+
+        let mirror_loop = MemoryMap::builder()
+            .mirror(0x1000..=0x1FFF, 0x2000)
+            .mirror(0x2000..=0x2FFF, 0x1000)
+            .build();
+        assert_eq!(
+            mirror_loop.err(),
+            Some(MemoryMapError::MirrorLoop {
+                start: 0x1000,
+                end: 0x1FFF,
+            })
+        );
+    }
+
+    #[test]
+    fn test_cpu_on_memory_map() {
+        // lda $D013 ; sta $0810 ; lda #1 ; sta $D010 ; nop ; nop
+        let mut rom = vec![
+            0xAD, 0x13, 0xD0, 0x8D, 0x10, 0x08, 0xA9, 0x01, 0x8D, 0x10, 0xD0, 0xEA, 0xEA,
+        ];
+        rom.resize(0x1000, 0xEA);
+        rom[0x0FFC] = 0x00;
+        rom[0x0FFD] = 0xF0;
+        rom[0x0FFE] = 0x00;
+        rom[0x0FFF] = 0x02;
+
+        let latch = Arc::new(Mutex::new(Latch::default()));
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x1FFF)
+            .mask(0x07FF)
+            .device(0xD010..=0xD013, latch)
+            .rom(0xF000..=0xFFFF, &rom)
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::new();
+        cpu.connect_to(Rc::new(RefCell::new(map)));
+        cpu.reset_with(ResetKind::PowerOn);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0xF000);
+        cpu.regset_mut().set_irq_disabled(false);
+
+        for _ in 0..4 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0x0010), 1);
+        assert_eq!(cpu.irq_sources_asserting(), vec!["latch".to_string()]);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x0200);
+    }
+    #[test]
+    fn test_read_only() {
+        let mut map = MemoryMap::builder()
+            .ram(0x0000..=0x00FF)
+            .ram(0x0100..=0x01FF)
+            .read_only()
+            .rom(0xFF00..=0xFFFF, &[0x11; 0x100])
+            .build()
+            .unwrap();
+        assert!(!map.write_protected(0x0000));
+        assert!(map.write_protected(0x0100));
+        assert!(map.write_protected(0xFF00));
+        assert!(!map.write_protected(0x8000));
+
+        map.write(0x0100, 0x22);
+        assert_eq!(map.read(0x0100), Some(0x00));
+
+        assert!(map.set_read_only(0x01FF, false));
+        map.write(0x0100, 0x22);
+        assert_eq!(map.read(0x0100), Some(0x22));
+
+        assert!(!map.set_read_only(0x8000, true));
+    }
+
+    #[test]
+    fn test_rom_file() {
+        let path = std::env::temp_dir().join("m6502_test_rom_file.bin");
+        std::fs::write(&path, [0x4C, 0x00, 0xFF]).unwrap();
+
+        let map = MemoryMap::builder()
+            .rom_file(0xFF00..=0xFFFF, &path)
+            .unwrap()
+            .open_bus(OpenBus::Fixed(0xFF))
+            .build()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(map.read_seq(0xFF00, 4), Some(vec![0x4C, 0x00, 0xFF, 0xFF]));
+        assert!(MemoryMap::builder()
+            .rom_file(0xFF00..=0xFFFF, "/nonexistent/rom.bin")
+            .is_err());
+    }
+
+    fn setup_rom_writes(policy: RomWritePolicy) -> Cpu {
+        // lda #$AA ; sta $F100 ; sta $0010 ; nop
+        let mut rom = vec![0xA9, 0xAA, 0x8D, 0x00, 0xF1, 0x85, 0x10, 0xEA];
+        rom.resize(0x1000, 0xEA);
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x07FF)
+            .rom(0xF000..=0xFFFF, &rom)
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::new_custompc(0xF000);
+        cpu.connect_to(Rc::new(RefCell::new(map)));
+        cpu.set_rom_write_policy(policy);
+        cpu
+    }
+
+    #[test]
+    fn test_rom_write_ignored() {
+        let mut cpu = setup_rom_writes(RomWritePolicy::Ignore);
+        for _ in 0..3 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0xF100), 0xEA);
+        assert_eq!(cpu.read_byte(0x0010), 0xAA);
+        assert!(cpu.bus_faults().is_empty());
+    }
+
+    #[test]
+    fn test_rom_write_recorded() {
+        let mut cpu = setup_rom_writes(RomWritePolicy::Record);
+        for _ in 0..3 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0xF100), 0xEA);
+        assert_eq!(
+            cpu.take_bus_faults(),
+            vec![BusFault {
+                address: 0xF100,
+                data: 0xAA,
+                pc: 0xF002,
+                kind: BusFaultKind::WriteToRom,
+            }]
+        );
+        assert!(cpu.bus_faults().is_empty());
+    }
+
+    #[test]
+    fn test_rom_write_trapped() {
+        let mut cpu = setup_rom_writes(RomWritePolicy::Trap);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(
+            cpu.full_instruction(),
+            Err(CpuError::WriteToRom {
+                address: 0xF100,
+                pc: 0xF002,
+            })
+        );
+        assert_eq!(cpu.pc(), 0xF005);
+        assert_eq!(cpu.time().elapsed(), 2 + 4);
+
+        // The execution may go on
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.read_byte(0x0010), 0xAA);
+    }
+
+    fn setup_unmapped(strict: bool) -> Cpu {
+        // lda $4000 ; sta $4001 ; sta $10
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x07FF)
+            .open_bus(OpenBus::Fixed(0x5A))
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::new_custompc(0x0200);
+        cpu.connect_to(Rc::new(RefCell::new(map)));
+        cpu.load_program(
+            &[0xAD, 0x00, 0x40, 0x8D, 0x01, 0x40, 0x85, 0x10],
+            0x0200,
+            8,
+            false,
+        )
+        .unwrap();
+        cpu.set_strict_bus(strict);
+        cpu
+    }
+
+    #[test]
+    fn test_fallible_accesses() {
+        let mut cpu = setup_unmapped(false);
+        assert_eq!(cpu.try_read_byte(0x0200), Ok(0xAD));
+        assert_eq!(cpu.try_read_word(0x0201), Ok(0x4000));
+        assert_eq!(cpu.try_writ_byte(0x0010, 0x42), Ok(()));
+        assert_eq!(cpu.read_byte(0x0010), 0x42);
+
+        let unmapped_read = CpuError::BusFault {
+            address: 0x4000,
+            kind: BusFaultKind::UnmappedRead,
+        };
+        assert_eq!(cpu.try_read_byte(0x4000), Err(unmapped_read));
+        assert_eq!(
+            cpu.try_read_word(0x07FF),
+            Err(CpuError::BusFault {
+                address: 0x0800,
+                kind: BusFaultKind::UnmappedRead,
+            })
+        );
+        assert_eq!(
+            cpu.try_writ_byte(0x4000, 0x42),
+            Err(CpuError::BusFault {
+                address: 0x4000,
+                kind: BusFaultKind::UnmappedWrite,
+            })
+        );
+
+        // The infallible ones give the open bus
+        assert_eq!(cpu.read_byte(0x4000), 0x5A);
+        assert!(cpu.bus_faults().is_empty());
+
+        let mut cpu = Cpu::new();
+        assert_eq!(
+            cpu.try_read_byte(0x0000),
+            Err(CpuError::BusInterfaceMissing)
+        );
+        assert_eq!(
+            cpu.try_writ_byte(0x0000, 0x42),
+            Err(CpuError::BusInterfaceMissing)
+        );
+    }
+
+    #[test]
+    fn test_unmapped_access_ignored() {
+        let mut cpu = setup_unmapped(false);
+        for _ in 0..3 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.read_byte(0x0010), 0x5A);
+        assert!(cpu.bus_faults().is_empty());
+    }
+
+    #[test]
+    fn test_unmapped_access_strict() {
+        let mut cpu = setup_unmapped(true);
+        assert_eq!(
+            cpu.full_instruction(),
+            Err(CpuError::BusFault {
+                address: 0x4000,
+                kind: BusFaultKind::UnmappedRead,
+            })
+        );
+        // Stopped on the last cycle, right after the read
+        assert_eq!(cpu.time().elapsed(), 4);
+        assert_eq!(cpu.pc(), 0x0203);
+        assert!(cpu.bus_faults().is_empty());
+
+        assert_eq!(
+            cpu.full_instruction(),
+            Err(CpuError::BusFault {
+                address: 0x4001,
+                kind: BusFaultKind::UnmappedWrite,
+            })
+        );
+
+        // The execution may go on
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.read_byte(0x0010), 0x5A);
+
+        let mut cpu = Cpu::new();
+        cpu.set_strict_bus(true);
+        assert_eq!(cpu.clock_cycle(), Err(CpuError::BusInterfaceMissing));
+        assert_eq!(cpu.time().elapsed(), 0);
+    }
+
+    /// A map with a PIA and a latch, followed by unmapped memory
+    fn devices_map() -> (MemoryMap, Arc<Mutex<Pia>>, Arc<Mutex<Latch>>) {
+        let pia = Arc::new(Mutex::new(Pia::new()));
+        // A falling edge on CA1 sets its flag, reading port A clears it
+        {
+            let mut pia = pia.lock().unwrap();
+            pia.write(0x01, 0x04);
+            pia.set_ca1(true);
+            pia.set_ca1(false);
+        }
+        let latch = Arc::new(Mutex::new(Latch::default()));
+        // lda $D013
+        latch.lock().unwrap().regs = [0xAD, 0x13, 0xD0, 0x00];
+
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x07FF)
+            .device(0xD00C..=0xD00F, pia.clone())
+            .device(0xD010..=0xD013, latch.clone())
+            .build()
+            .unwrap();
+        (map, pia, latch)
+    }
+
+    fn assert_disassembled_quietly<B: Bus + ?Sized>(
+        cpu: &mut Cpu<B>,
+        pia: &Arc<Mutex<Pia>>,
+        latch: &Arc<Mutex<Latch>>,
+    ) {
+        let hooked = Arc::new(Mutex::new(0));
+        let count = hooked.clone();
+        cpu.add_bus_hook(move |_| *count.lock().unwrap() += 1);
+        cpu.set_strict_bus(true);
+
+        Asm::from_addr_range(cpu, 0xD00C, 4);
+        let asm = Asm::from_addr_range(cpu, 0xD010, 8);
+        assert_eq!(asm.code()[0].operand(), Some(0xD013));
+
+        assert_eq!(*hooked.lock().unwrap(), 0);
+        assert!(cpu.bus_faults().is_empty());
+        assert_eq!(pia.lock().unwrap().peek(0x01).unwrap() & 0x80, 0x80);
+        assert_eq!(latch.lock().unwrap().reads, 0);
+    }
+
+    #[test]
+    fn test_disassemble_no_side_effects() {
+        let (map, pia, latch) = devices_map();
+        let mut cpu = Cpu::with_bus(map);
+        assert_disassembled_quietly(&mut cpu, &pia, &latch);
+
+        let (map, pia, latch) = devices_map();
+        let mut cpu = Cpu::new();
+        cpu.connect_to(Rc::new(RefCell::new(map)));
+        assert_disassembled_quietly(&mut cpu, &pia, &latch);
+
+        // Unlike running code which reads port A
+        cpu.load_program(&[0xAD, 0x0C, 0xD0], 0x0200, 3, true)
+            .unwrap();
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(pia.lock().unwrap().peek(0x01).unwrap() & 0x80, 0x00);
+    }
+
+    #[test]
+    fn test_disassemble_unmapped_strict() {
+        let mut cpu = setup_unmapped(true);
+        cpu.writ_byte(0x07FE, 0xAD);
+
+        // Disassembling past the end of the RAM is no fault
+        let asm = Asm::from_addr_range(&mut cpu, 0x07FE, 4);
+        assert_eq!(asm.code()[0].operand(), Some(0x5A00));
+        let mut i = Instruction::decode_by(0xAD);
+        load_operand(&mut cpu, &mut i, 0x07FF);
+        assert_eq!(i.operand(), Some(0x5A5A));
+        assert!(cpu.bus_faults().is_empty());
+
+        // sta $10
+        cpu.regset_mut().set_prog_counter(0x0206);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+    }
+}
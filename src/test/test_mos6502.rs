@@ -51,6 +51,124 @@ mod test {
         assert_eq!(retval2, 0);
     }
 
+    #[test]
+    fn test_cpu_owns_bus() {
+        let mut bus = MainBus::new();
+        // lda #$2A ; sta $10
+        bus.mem[0x0200..0x0204].copy_from_slice(&[0xA9, 0x2A, 0x85, 0x10]);
+        let mut cpu = Cpu::with_bus(bus);
+        cpu.regset_mut().set_prog_counter(0x0200);
+        assert!(cpu.bus().attached());
+
+        let worker = std::thread::spawn(move || {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+            assert_eq!(cpu.full_instruction(), Ok(()));
+            cpu
+        });
+        let cpu = worker.join().unwrap();
+        assert_eq!(cpu.time().elapsed(), 2 + 3);
+        assert_eq!(cpu.into_bus().mem[0x10], 0x2A);
+    }
+
+    #[test]
+    fn test_cpu_with_memory_map_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<crate::memory_map::MemoryMap>();
+        assert_send::<Cpu<crate::memory_map::MemoryMap>>();
+    }
+
+    #[test]
+    fn test_bus_hooks() {
+        use std::sync::{Arc, Mutex};
+        use AccessDirection::*;
+        use AccessKind::*;
+
+        let mut bus = MainBus::new();
+        // inc $10 ; pha ; brk
+        bus.mem[0x0200..0x0204].copy_from_slice(&[0xE6, 0x10, 0x48, 0x00]);
+        bus.mem[0x0010] = 0x41;
+        bus.mem[0xFFFE..=0xFFFF].copy_from_slice(&[0x00, 0x03]);
+        let mut cpu = Cpu::with_bus(bus);
+        cpu.regset_mut().set_prog_counter(0x0200);
+        cpu.regset_mut().set_accumulator(0x99);
+
+        let accesses = Arc::new(Mutex::new(Vec::new()));
+        let log = accesses.clone();
+        cpu.add_bus_hook(move |access| log.lock().unwrap().push(*access));
+
+        let access = |address, data, direction, kind| BusAccess {
+            address,
+            data,
+            direction,
+            kind,
+        };
+        for _ in 0..2 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(
+            *accesses.lock().unwrap(),
+            vec![
+                access(0x0200, 0xE6, Read, Opcode),
+                access(0x0201, 0x10, Read, Operand),
+                access(0x0010, 0x41, Read, Data),
+                access(0x0010, 0x41, Write, Dummy),
+                access(0x0010, 0x42, Write, Data),
+                access(0x0202, 0x48, Read, Opcode),
+                access(0x0203, 0x00, Read, Dummy),
+                access(0x01FD, 0x99, Write, Stack),
+            ]
+        );
+
+        accesses.lock().unwrap().clear();
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        let kinds: Vec<_> = accesses
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|access| (access.direction, access.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Read, Opcode),
+                (Read, Operand),
+                (Write, Stack),
+                (Write, Stack),
+                (Write, Stack),
+                (Read, Vector),
+                (Read, Vector),
+            ]
+        );
+        assert_eq!(cpu.pc(), 0x0300);
+
+        cpu.clear_bus_hooks();
+        accesses.lock().unwrap().clear();
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert!(accesses.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cpu_shared_bus() {
+        let bus = Rc::new(RefCell::new(MainBus::new()));
+        let mut cpu = Cpu::new_variant(CpuVariant::Cmos);
+        assert!(!cpu.bus().attached());
+        assert_eq!(
+            cpu.load_program(&[0xEA], 0, 1, false),
+            Err(CpuError::BusInterfaceMissing)
+        );
+        cpu.writ_byte(0x0010, 0x42);
+        assert_eq!(cpu.read_byte(0x0010), 0);
+
+        cpu.connect_to(bus.clone());
+        assert!(cpu.bus().attached());
+        cpu.writ_byte(0x0010, 0x42);
+        assert_eq!(bus.borrow().mem[0x0010], 0x42);
+        assert_eq!(cpu.variant(), CpuVariant::Cmos);
+
+        let mut cpu = Cpu::with_bus_variant(CpuVariant::Ricoh2A03, SharedBus::new(bus));
+        assert_eq!(cpu.read_byte(0x0010), 0x42);
+    }
+
     #[test]
     fn test_stk_operations() {
         let mut cpu = Cpu::new_connected(Some(Rc::new(RefCell::new(MainBus::new()))));
@@ -104,16 +222,13 @@ mod test {
 
     #[test]
     fn test_decode_by_correct() {
-        use crate::mos6502_addressing_modes::relative_am;
-        use crate::mos6502_instruction_set::bpl;
-
         let i = Instruction::decode_by(0x10);
 
         assert_eq!(i.mnemonic(), "bpl".to_string());
         assert_eq!(i.time(), 2);
         assert_eq!(i.size(), 2);
-        assert_eq!(i.fun() as usize, bpl as usize);
-        assert_eq!(i.amode_fun() as usize, relative_am as usize);
+        assert_eq!(i.fun(), InstructionFn::bpl);
+        assert_eq!(i.amode(), AddressingMode::Rel);
     }
 
     #[test]
@@ -183,11 +298,11 @@ mod test {
         let success = cpu.inthandle(Irq);
         // irq cycles
         for _ in 0..7 {
-            cpu.clock_cycle();
+            assert_eq!(cpu.clock_cycle(), Ok(()));
         }
         // "lda #10" cycles
         for _ in 0..2 {
-            cpu.clock_cycle();
+            assert_eq!(cpu.clock_cycle(), Ok(()));
         }
 
         assert_eq!(success, true);
@@ -227,11 +342,11 @@ mod test {
         let success = cpu.inthandle(InterruptKind::Nmi);
         // irq cycles
         for _ in 0..8 {
-            cpu.clock_cycle();
+            assert_eq!(cpu.clock_cycle(), Ok(()));
         }
         // "lda #10" cycles
         for _ in 0..2 {
-            cpu.clock_cycle();
+            assert_eq!(cpu.clock_cycle(), Ok(()));
         }
 
         assert_eq!(success, true);
@@ -240,6 +355,159 @@ mod test {
         assert_eq!(cpu.regset().irq_disabled(), true);
     }
 
+    #[test]
+    fn test_decode_by_illegal() {
+        let i = Instruction::decode_by(0x1C);
+
+        assert!(i.illegal());
+        assert_eq!(i.opcode(), 0x1C);
+        assert_eq!(i.mnemonic(), "nop".to_string());
+        assert_eq!(i.time(), 4);
+        assert_eq!(i.size(), 3);
+        assert!(!Instruction::decode_by(0xEA).illegal());
+    }
+
+    #[test]
+    fn test_illegal_opcode_trap() {
+        let mut cpu = Cpu::new_custompc(0x1000);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.writ_byte(0x1000, 0x02);
+
+        let res = cpu.clock_cycle();
+
+        assert_eq!(cpu.illegal_opcode_policy(), IllegalOpcodePolicy::Trap);
+        assert_eq!(
+            res,
+            Err(CpuError::IllegalOpcode {
+                opcode: 0x02,
+                address: 0x1000
+            })
+        );
+        assert_eq!(cpu.pc(), 0x1000);
+        assert_eq!(cpu.time().elapsed(), 0);
+        assert_eq!(cpu.full_instruction(), res);
+    }
+
+    #[test]
+    fn test_illegal_opcode_nop() {
+        let mut cpu = Cpu::new_custompc(0x1000);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Nop);
+        // slo $1234 ; lax #$10 ; jam
+        let prog: Vec<Byte> = vec![0x0F, 0x34, 0x12, 0xAB, 0x10, 0x02];
+        let _ = cpu.load_program(&prog, 0x1000, prog.len(), true);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1003);
+        assert_eq!(cpu.time().elapsed(), 6);
+        assert_eq!(cpu.read_byte(0x1234), 0x00);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1005);
+        assert_eq!(cpu.time().elapsed(), 8);
+        assert_eq!(cpu.regset().accumulator(), 0x00);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1006);
+    }
+
+    #[test]
+    fn test_illegal_opcode_nop_bus_accesses() {
+        use std::sync::{Arc, Mutex};
+        use AccessDirection::*;
+        use AccessKind::*;
+
+        let mut bus = MainBus::new();
+        // slo $10 ; sax $20
+        bus.mem[0x0200..0x0204].copy_from_slice(&[0x07, 0x10, 0x87, 0x20]);
+        bus.mem[0x0010] = 0x41;
+        bus.mem[0x0020] = 0x55;
+        let mut cpu = Cpu::with_bus(bus);
+        cpu.regset_mut().set_prog_counter(0x0200);
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Nop);
+
+        let accesses = Arc::new(Mutex::new(Vec::new()));
+        let log = accesses.clone();
+        cpu.add_bus_hook(move |access| log.lock().unwrap().push(*access));
+
+        let access = |address, data, direction, kind| BusAccess {
+            address,
+            data,
+            direction,
+            kind,
+        };
+        for _ in 0..2 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.time().elapsed(), 8);
+        assert_eq!(
+            *accesses.lock().unwrap(),
+            vec![
+                access(0x0200, 0x07, Read, Opcode),
+                access(0x0201, 0x10, Read, Operand),
+                access(0x0010, 0x41, Read, Data),
+                access(0x0010, 0x41, Write, Dummy),
+                access(0x0010, 0x41, Write, Data),
+                access(0x0202, 0x87, Read, Opcode),
+                access(0x0203, 0x20, Read, Operand),
+                access(0x0020, 0x55, Read, Dummy),
+            ]
+        );
+        assert_eq!(cpu.read_byte(0x0010), 0x41);
+        assert_eq!(cpu.read_byte(0x0020), 0x55);
+        assert_eq!(cpu.regset().accumulator(), 0x00);
+    }
+
+    #[test]
+    fn test_illegal_opcode_execute_nop() {
+        let mut cpu = Cpu::new_custompc(0x1000);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Execute);
+        cpu.writ_byte(0x1000, 0x14);
+        cpu.writ_byte(0x1001, 0x10);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1002);
+        assert_eq!(cpu.time().elapsed(), 4);
+    }
+
+    #[test]
+    fn test_illegal_opcode_execute() {
+        let mut cpu = Cpu::new_custompc(0x1000);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Execute);
+        // lax $10 ; dcp $10 ; jam
+        let prog: Vec<Byte> = vec![0xA7, 0x10, 0xC7, 0x10, 0x02];
+        let _ = cpu.load_program(&prog, 0x1000, prog.len(), true);
+        cpu.writ_byte(0x10, 0x42);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.regset().accumulator(), 0x42);
+        assert_eq!(cpu.regset().x_index(), 0x42);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.read_byte(0x10), 0x41);
+        assert!(cpu.regset().carry());
+        assert_eq!(cpu.time().elapsed(), 8);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1004);
+    }
+
+    #[test]
+    fn test_disassemble_illegal() {
+        let mut cpu = Cpu::new_custompc(0x1000);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.writ_byte(0x1000, 0xA7);
+        cpu.writ_byte(0x1001, 0x10);
+
+        let str_res = Asm::stringify_range(&mut cpu, 0x1000, 1);
+
+        let expected_str = String::from("0x1000\tlax\t0x10\t; Zp0\n");
+        assert_eq!(str_res.ok(), Some(expected_str));
+    }
+
     fn setup(custom_pc: Word, connect: bool, opcode: Opcode, operand: Option<Word>) -> Cpu {
         let mut cpu = Cpu::new_custompc(custom_pc);
 
@@ -255,11 +523,11 @@ mod test {
     #[test]
     fn test_prepare_operands_zero() {
         let mut cpu = setup(0x0001, true, 0x00, None);
-        cpu.i_decode_and_set(0x8, None);
+        let mut i = Instruction::decode_by(0x8);
 
-        load_operand_curr_i(&mut cpu);
+        load_operand(&mut cpu, &mut i, 0x0);
 
-        let i = cpu.i().unwrap();
+        assert_eq!(cpu.pc(), 0x01);
         assert_eq!(i.operand(), None);
         assert_eq!(i.loaded_from(), 0x0);
     }
@@ -267,12 +535,11 @@ mod test {
     #[test]
     fn test_prepare_operands_one() {
         let mut cpu = setup(0x001, true, 0x00, None);
-        cpu.i_decode_and_set(0xa9, None);
+        let mut i = Instruction::decode_by(0xa9);
 
-        load_operand_curr_i(&mut cpu);
+        load_operand(&mut cpu, &mut i, 0x0);
 
-        assert_eq!(cpu.pc(), 0x02);
-        let i = cpu.i().unwrap();
+        assert_eq!(cpu.pc(), 0x01);
         assert_eq!(i.operand().is_some(), true);
         assert_eq!(i.operand().unwrap(), 0x0);
         assert_eq!(i.loaded_from(), 0x0);
@@ -281,14 +548,13 @@ mod test {
     #[test]
     fn test_prepare_operands_two() {
         let mut cpu = setup(0x001, true, 0x00, None);
-        cpu.i_decode_and_set(0xad, None);
+        let mut i = Instruction::decode_by(0xad);
         cpu.writ_byte(0x1, 0x10);
         cpu.writ_byte(0x2, 0x11);
 
-        load_operand_curr_i(&mut cpu);
+        load_operand(&mut cpu, &mut i, 0x0);
 
-        assert_eq!(cpu.pc(), 0x03);
-        let i = cpu.i().unwrap();
+        assert_eq!(cpu.pc(), 0x01);
         assert_eq!(i.operand().is_some(), true);
         assert_eq!(i.operand().unwrap(), 0x1110);
         assert_eq!(i.loaded_from(), 0x0);
@@ -510,4 +776,750 @@ mod test {
         );
         assert_eq!(str_res.ok(), Some(expected_str));
     }
+
+    fn setup_cmos(custom_pc: Word) -> Cpu {
+        let mut cpu = Cpu::new_variant(CpuVariant::Cmos);
+        cpu.regset_mut().set_prog_counter(custom_pc);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+
+        cpu
+    }
+
+    #[test]
+    fn test_opcode_tables() {
+        for (table, variant) in [
+            (&NMOS_OPCODES, CpuVariant::Nmos),
+            (&CMOS_OPCODES, CpuVariant::Cmos),
+        ] {
+            for (opcode, info) in table.iter().enumerate() {
+                assert_eq!(usize::from(info.opcode), opcode);
+                let i = Instruction::decode_for(variant, opcode as Opcode);
+                assert!(std::ptr::eq(i.info(), info));
+            }
+        }
+        assert!(std::ptr::eq(CpuVariant::Ricoh2A03.opcodes(), &NMOS_OPCODES));
+
+        let lda_abx = &NMOS_OPCODES[0xBD];
+        assert_eq!(
+            (
+                lda_abx.mnemonic,
+                lda_abx.amode,
+                lda_abx.size,
+                lda_abx.cycles
+            ),
+            ("lda", AddressingMode::Abx, 3, 4)
+        );
+        assert!(lda_abx.page_cross_penalty);
+        assert!(NMOS_OPCODES[0xD0].page_cross_penalty);
+        assert!(NMOS_OPCODES[0xBF].page_cross_penalty);
+        assert!(!NMOS_OPCODES[0x9D].page_cross_penalty);
+        assert!(!NMOS_OPCODES[0xFE].page_cross_penalty);
+        assert!(!NMOS_OPCODES[0xAD].page_cross_penalty);
+        assert_eq!(CMOS_OPCODES[0xB2].mnemonic, "lda");
+        assert!(!CMOS_OPCODES[0xB2].page_cross_penalty);
+
+        // The disassembler decodes through the same table
+        let mut cpu = Cpu::default();
+        assert_eq!(
+            cpu.load_program(&[0xBD, 0x34, 0x12], 0x0000, 3, false),
+            Ok(0x0000)
+        );
+        let asm = Asm::from_addr_range(&mut cpu, 0x0000, 3);
+        assert!(std::ptr::eq(asm.code()[0].info(), lda_abx));
+    }
+
+    #[test]
+    fn test_decode_cmos_no_illegal() {
+        for opcode in 0x00..=0xFF {
+            let i = Instruction::decode_for(CpuVariant::Cmos, opcode);
+            assert!(!i.illegal(), "{:#04x} is illegal", opcode);
+            assert_eq!(i.opcode(), opcode);
+
+            // Only the timings of a few documented instructions differ
+            let nmos = Instruction::decode_by(opcode);
+            let retimed = matches!(opcode, 0x1E | 0x3E | 0x5E | 0x6C | 0x7E);
+            if !nmos.illegal() && !retimed {
+                assert_eq!(i, nmos, "{:#04x} differs from the nmos one", opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_cmos_nops() {
+        let nop_sizes = |size: u16, time: u8| {
+            (0x00..=0xFF)
+                .map(|opcode| Instruction::decode_for(CpuVariant::Cmos, opcode))
+                .filter(|i| i.mnemonic() == "nop" && i.size() == size && i.time() == time)
+                .count()
+        };
+
+        assert_eq!(nop_sizes(1, 1), 30);
+        assert_eq!(nop_sizes(1, 2), 1);
+        assert_eq!(nop_sizes(2, 2), 7);
+        assert_eq!(nop_sizes(2, 3), 1);
+        assert_eq!(nop_sizes(2, 4), 3);
+        assert_eq!(nop_sizes(3, 4), 2);
+        assert_eq!(nop_sizes(3, 8), 1);
+    }
+
+    #[test]
+    fn test_cmos_variant() {
+        assert_eq!(Cpu::new().variant(), CpuVariant::Nmos);
+        assert_eq!(setup_cmos(0x1000).variant(), CpuVariant::Cmos);
+    }
+
+    #[test]
+    fn test_cmos_program() {
+        let mut cpu = setup_cmos(0x1000);
+        // lda #$01 ; sta ($10) ; smb3 $20 ; bbs3 $20, +2 ; jam ; jam ; stz $20 ; stp
+        let prog: Vec<Byte> = vec![
+            0xA9, 0x01, 0x92, 0x10, 0xB7, 0x20, 0xBF, 0x20, 0x02, 0x02, 0x02, 0x64, 0x20, 0xDB,
+        ];
+        let _ = cpu.load_program(&prog, 0x1000, prog.len(), true);
+        cpu.writ_byte(0x10, 0x00);
+        cpu.writ_byte(0x11, 0x30);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.read_byte(0x3000), 0x01);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.read_byte(0x20), 0x08);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x100B);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.read_byte(0x20), 0x00);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.run_state(), RunState::Stopped);
+        assert_eq!(cpu.pc(), 0x100E);
+        assert_eq!(cpu.time().elapsed(), 2 + 5 + 5 + 6 + 3 + 3);
+    }
+
+    #[test]
+    fn test_cmos_jmp_indirect_x() {
+        let mut cpu = setup_cmos(0x1000);
+        // jmp ($2000, X)
+        let prog: Vec<Byte> = vec![0x7C, 0x00, 0x20];
+        let _ = cpu.load_program(&prog, 0x1000, prog.len(), true);
+        cpu.regset_mut().set_x_index(0x04);
+        cpu.writ_byte(0x2004, 0x34);
+        cpu.writ_byte(0x2005, 0x12);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1234);
+        assert_eq!(cpu.time().elapsed(), 6);
+    }
+
+    #[test]
+    fn test_cmos_wai_irq() {
+        let mut cpu = setup_cmos(0x1000);
+        cpu.writ_byte(0x1000, 0xCB);
+        cpu.writ_byte(0xFFFE, 0x00);
+        cpu.writ_byte(0xFFFF, 0x20);
+        cpu.regset_mut().set_irq_disabled(false);
+        cpu.regset_mut().set_decimal_mode(true);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.run_state(), RunState::Waiting);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x1001);
+        assert_eq!(cpu.time().elapsed(), 4);
+
+        assert!(cpu.inthandle(Irq));
+        assert_eq!(cpu.run_state(), RunState::Running);
+        assert_eq!(cpu.pc(), 0x2000);
+        assert!(!cpu.regset().decimal_mode());
+        assert_eq!(cpu.stk_pop() & (1 << 3), 1 << 3);
+        assert_eq!(cpu.stk_pop(), 0x01);
+        assert_eq!(cpu.stk_pop(), 0x10);
+    }
+
+    #[test]
+    fn test_cmos_wai_masked_irq() {
+        let mut cpu = setup_cmos(0x1000);
+        cpu.writ_byte(0x1000, 0xCB);
+        cpu.regset_mut().set_irq_disabled(true);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert!(!cpu.inthandle(Irq));
+
+        assert_eq!(cpu.run_state(), RunState::Running);
+        assert_eq!(cpu.pc(), 0x1001);
+    }
+
+    #[test]
+    fn test_cmos_stp() {
+        let mut cpu = setup_cmos(0x1000);
+        cpu.writ_byte(0x1000, 0xDB);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert!(!cpu.inthandle(Nmi));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+
+        assert_eq!(cpu.run_state(), RunState::Stopped);
+        assert_eq!(cpu.pc(), 0x1001);
+
+        cpu.reset();
+        assert_eq!(cpu.run_state(), RunState::Running);
+    }
+
+    #[test]
+    fn test_nmos_nmi_keeps_decimal() {
+        let mut cpu = Cpu::new_custompc(0x1000);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.regset_mut().set_decimal_mode(true);
+
+        assert!(cpu.inthandle(Nmi));
+        assert!(cpu.regset().decimal_mode());
+    }
+
+    #[test]
+    fn test_stringify_disassembly_cmos() {
+        let mut cpu = setup_cmos(0x1000);
+        // lda ($10) ; jmp ($1234, X) ; bbs7 $10, -3 ; ply
+        let prog: Vec<Byte> = vec![0xB2, 0x10, 0x7C, 0x34, 0x12, 0xFF, 0x10, 0xFD, 0x7A];
+        let _ = cpu.load_program(&prog, 0x1000, prog.len(), false);
+
+        let str_res = Asm::stringify_range(&mut cpu, 0x1000, prog.len() as u16);
+
+        let expected_str = String::from(
+            "\
+                0x1000\tlda\t(0x10)\t; Izp\n\
+                0x1002\tjmp\t(0x1234, X)\t; Iax\n\
+                0x1005\tbbs7\t0x10, 0xfd\t; Zpr\n\
+                0x1008\tply\t\t; Imp\n\
+        ",
+        );
+        assert_eq!(str_res.ok(), Some(expected_str));
+    }
+
+    #[test]
+    fn test_ricoh_decodes_as_nmos() {
+        for opcode in 0x00..=0xFF {
+            let i = Instruction::decode_for(CpuVariant::Ricoh2A03, opcode);
+            let nmos = Instruction::decode_by(opcode);
+            assert_eq!(i, nmos);
+            assert_eq!(i.illegal(), nmos.illegal());
+        }
+    }
+
+    #[test]
+    fn test_ricoh_program() {
+        let mut cpu = Cpu::new_variant(CpuVariant::Ricoh2A03);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        // sed ; lda #$19 ; clc ; adc #$01 ; jmp ($10FF)
+        let prog: Vec<Byte> = vec![0xF8, 0xA9, 0x19, 0x18, 0x69, 0x01, 0x6C, 0xFF, 0x10];
+        let _ = cpu.load_program(&prog, 0x1000, prog.len(), true);
+        cpu.writ_byte(0x10FF, 0x34);
+        cpu.writ_byte(0x1100, 0x12);
+
+        for _ in 0..4 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert!(cpu.regset().decimal_mode());
+        assert_eq!(cpu.regset().accumulator(), 0x1A);
+
+        // The page wrap bug of the NMOS 6502 is still there -- the high
+        // byte is read from 0x1000 which holds the `sed`
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0xF834);
+    }
+
+    #[test]
+    fn test_micro_programs_match_timings() {
+        // Every micro program has to complete exactly on the last cycle
+        // of its instruction. Otherwise `clock_cycle` fails an assertion.
+        for variant in [CpuVariant::Nmos, CpuVariant::Cmos, CpuVariant::Ricoh2A03] {
+            for opcode in 0x00..=0xFF {
+                for (index, operand) in [(0x00, 0x00), (0xFF, 0xFF)] {
+                    let mut cpu = Cpu::new_variant(variant);
+                    cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+                    cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Execute);
+                    let _ = cpu.load_program(&[opcode, operand, operand], 0x0200, 3, true);
+                    cpu.regset_mut().set_x_index(index);
+                    cpu.regset_mut().set_y_index(index);
+
+                    assert_eq!(cpu.full_instruction(), Ok(()));
+                }
+            }
+        }
+    }
+
+    /// Bus which records every access as (address, data, is write)
+    struct TracingBus {
+        mem: Vec<Byte>,
+        trace: RefCell<Vec<(Address, Byte, bool)>>,
+    }
+
+    impl Bus for TracingBus {
+        fn read(&self, address: Address) -> Option<Byte> {
+            let data = self.mem[usize::from(address)];
+            self.trace.borrow_mut().push((address, data, false));
+            Some(data)
+        }
+
+        fn write(&mut self, address: Address, data: Byte) {
+            self.mem[usize::from(address)] = data;
+            self.trace.borrow_mut().push((address, data, true));
+        }
+
+        fn read_seq(&self, address: Address, len: u16) -> Option<Vec<Byte>> {
+            let start = usize::from(address);
+            Some(self.mem[start..start + usize::from(len)].to_vec())
+        }
+    }
+
+    fn trace_instruction(variant: CpuVariant, prog: &[Byte]) -> Vec<(Address, Byte, bool)> {
+        let bus = Rc::new(RefCell::new(TracingBus {
+            mem: vec![0x00; 0x10000],
+            trace: RefCell::new(Vec::new()),
+        }));
+        let mut cpu = Cpu::new_variant(variant);
+        cpu.connect_to(bus.clone());
+        let _ = cpu.load_program(prog, 0x0200, prog.len(), true);
+        cpu.regset_mut().set_x_index(0x10);
+        bus.borrow().trace.borrow_mut().clear();
+
+        let mut cycles = 0;
+        loop {
+            assert_eq!(cpu.clock_cycle(), Ok(()));
+            cycles += 1;
+            // One bus access per cycle
+            assert_eq!(bus.borrow().trace.borrow().len(), cycles);
+            if cpu.time().residual() == 0 {
+                break;
+            }
+        }
+
+        let trace = bus.borrow().trace.replace(Vec::new());
+        trace
+    }
+
+    #[test]
+    fn test_cycles_read_absolute_x_page_crossed() {
+        // lda $12F8,X
+        let trace = trace_instruction(CpuVariant::Nmos, &[0xBD, 0xF8, 0x12]);
+        assert_eq!(
+            trace,
+            vec![
+                (0x0200, 0xBD, false),
+                (0x0201, 0xF8, false),
+                (0x0202, 0x12, false),
+                (0x1208, 0x00, false),
+                (0x1308, 0x00, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycles_read_modify_write() {
+        // inc $10
+        let trace = trace_instruction(CpuVariant::Nmos, &[0xE6, 0x10]);
+        assert_eq!(
+            trace,
+            vec![
+                (0x0200, 0xE6, false),
+                (0x0201, 0x10, false),
+                (0x0010, 0x00, false),
+                (0x0010, 0x00, true),
+                (0x0010, 0x01, true),
+            ]
+        );
+
+        // The 65C02 reads the value again instead of writing it back
+        let trace = trace_instruction(CpuVariant::Cmos, &[0xE6, 0x10]);
+        assert_eq!(trace[3], (0x0010, 0x00, false));
+        assert_eq!(trace[4], (0x0010, 0x01, true));
+    }
+
+    #[test]
+    fn test_cycles_cmos_shift_absolute_x() {
+        // asl $1200,X
+        let trace = trace_instruction(CpuVariant::Cmos, &[0x1E, 0x00, 0x12]);
+        assert_eq!(
+            trace,
+            vec![
+                (0x0200, 0x1E, false),
+                (0x0201, 0x00, false),
+                (0x0202, 0x12, false),
+                (0x1210, 0x00, false),
+                (0x1210, 0x00, false),
+                (0x1210, 0x00, true),
+            ]
+        );
+
+        // Crossing a page costs a cycle, in which the operand is read again
+        let trace = trace_instruction(CpuVariant::Cmos, &[0x7E, 0xF8, 0x12]);
+        assert_eq!(
+            trace,
+            vec![
+                (0x0200, 0x7E, false),
+                (0x0201, 0xF8, false),
+                (0x0202, 0x12, false),
+                (0x0202, 0x12, false),
+                (0x1308, 0x00, false),
+                (0x1308, 0x00, false),
+                (0x1308, 0x00, true),
+            ]
+        );
+
+        // The NMOS 6502 always takes 7 cycles, and so do `inc` and `dec`
+        assert_eq!(
+            trace_instruction(CpuVariant::Nmos, &[0x1E, 0x00, 0x12]).len(),
+            7
+        );
+        assert_eq!(
+            trace_instruction(CpuVariant::Cmos, &[0xFE, 0x00, 0x12]).len(),
+            7
+        );
+    }
+
+    #[test]
+    fn test_cycles_jsr() {
+        // jsr $1234
+        let trace = trace_instruction(CpuVariant::Nmos, &[0x20, 0x34, 0x12]);
+        assert_eq!(
+            trace,
+            vec![
+                (0x0200, 0x20, false),
+                (0x0201, 0x34, false),
+                (0x01FD, 0x00, false),
+                (0x01FD, 0x02, true),
+                (0x01FC, 0x02, true),
+                (0x0202, 0x12, false),
+            ]
+        );
+    }
+
+    /// IRQ handler at 0x2000 and NMI handler at 0x3000, both filled with `nop`s
+    fn setup_interrupts(variant: CpuVariant, prog: &[Byte]) -> Cpu {
+        let mut cpu = Cpu::new_variant(variant);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        let _ = cpu.load_program(prog, 0x0200, prog.len(), true);
+        let _ = cpu.load_program(&[0xEA; 0x10], 0x2000, 0x10, false);
+        let _ = cpu.load_program(&[0xEA; 0x10], 0x3000, 0x10, false);
+        cpu.writ_byte(0xFFFA, 0x00);
+        cpu.writ_byte(0xFFFB, 0x30);
+        cpu.writ_byte(0xFFFE, 0x00);
+        cpu.writ_byte(0xFFFF, 0x20);
+        cpu
+    }
+
+    #[test]
+    fn test_irq_line_delayed_after_cli() {
+        // cli ; nop ; nop
+        let mut cpu = setup_interrupts(CpuVariant::Nmos, &[0x58, 0xEA, 0xEA]);
+        cpu.regset_mut().set_irq_disabled(true);
+        cpu.set_irq_line(true);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert!(!cpu.regset().irq_disabled());
+        assert!(!cpu.interrupt_handles().pending_irq());
+
+        // The first `nop` still executes
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x0202);
+        assert!(cpu.interrupt_handles().pending_irq());
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x2000);
+        assert_eq!(cpu.time().elapsed(), 2 + 2 + 7);
+        assert!(cpu.regset().irq_disabled());
+        assert_eq!(cpu.stk_pop() & (1 << 4), 0);
+        assert_eq!(cpu.stk_pop(), 0x02);
+        assert_eq!(cpu.stk_pop(), 0x02);
+
+        // The line is level-triggered but now masked
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x2001);
+    }
+
+    #[test]
+    fn test_irq_line_taken_after_sei() {
+        // sei ; nop
+        let mut cpu = setup_interrupts(CpuVariant::Nmos, &[0x78, 0xEA]);
+        cpu.regset_mut().set_irq_disabled(false);
+        cpu.set_irq_line(true);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert!(cpu.regset().irq_disabled());
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x2000);
+        assert_eq!(cpu.stk_pop() & (1 << 2), 1 << 2);
+        assert_eq!(cpu.stk_pop(), 0x01);
+    }
+
+    #[test]
+    fn test_irq_line_released() {
+        // nop ; nop
+        let mut cpu = setup_interrupts(CpuVariant::Nmos, &[0xEA, 0xEA]);
+        cpu.regset_mut().set_irq_disabled(false);
+
+        // Asserted only during the last cycle of the first `nop`,
+        // so it is never sampled on a next-to-last cycle
+        assert_eq!(cpu.clock_cycle(), Ok(()));
+        cpu.set_irq_line(true);
+        assert_eq!(cpu.clock_cycle(), Ok(()));
+        cpu.set_irq_line(false);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x0202);
+        assert!(!cpu.interrupt_handles().pending_irq());
+    }
+
+    #[test]
+    fn test_nmi_line_edge_triggered() {
+        let mut cpu = setup_interrupts(CpuVariant::Nmos, &[0xEA; 4]);
+        cpu.set_nmi_line(true);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x0201);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x3000);
+
+        // Holding the line does not request another NMI
+        for _ in 0..4 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.pc(), 0x3004);
+
+        cpu.set_nmi_line(false);
+        cpu.set_nmi_line(true);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x3000);
+        assert_eq!(cpu.stk_pop() & (1 << 4), 0);
+        assert_eq!(cpu.stk_pop(), 0x05);
+        assert_eq!(cpu.stk_pop(), 0x30);
+    }
+
+    #[test]
+    fn test_nmi_hijacks_brk() {
+        // brk
+        let mut cpu = setup_interrupts(CpuVariant::Nmos, &[0x00]);
+        for _ in 0..3 {
+            assert_eq!(cpu.clock_cycle(), Ok(()));
+        }
+        cpu.set_nmi_line(true);
+
+        for _ in 0..4 {
+            assert_eq!(cpu.clock_cycle(), Ok(()));
+        }
+        assert_eq!(cpu.time().residual(), 0);
+        assert_eq!(cpu.pc(), 0x3000);
+        assert!(!cpu.interrupt_handles().pending_nmi());
+
+        // The pushed status still tells that it was a `brk`
+        assert_eq!(cpu.stk_pop() & (1 << 4), 1 << 4);
+        assert_eq!(cpu.stk_pop(), 0x02);
+
+        // The NMI has been handled
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x3001);
+    }
+
+    #[test]
+    fn test_cmos_wai_irq_line() {
+        // wai ; nop
+        let mut cpu = setup_interrupts(CpuVariant::Cmos, &[0xCB, 0xEA]);
+        cpu.regset_mut().set_irq_disabled(false);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        for _ in 0..10 {
+            assert_eq!(cpu.clock_cycle(), Ok(()));
+        }
+        assert_eq!(cpu.run_state(), RunState::Waiting);
+        assert_eq!(cpu.pc(), 0x0201);
+
+        cpu.set_irq_line(true);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.run_state(), RunState::Running);
+        assert_eq!(cpu.pc(), 0x2000);
+    }
+
+    #[test]
+    fn test_irq_line_wired_or() {
+        let line = IrqLine::new();
+        let timer = line.source("timer");
+        let serial = line.source("serial");
+        assert!(!line.asserted());
+
+        timer.assert();
+        serial.assert();
+        timer.assert();
+        assert!(line.asserted());
+        assert_eq!(line.asserting(), vec!["timer", "serial"]);
+
+        timer.release();
+        assert!(line.asserted());
+        assert_eq!(line.asserting(), vec!["serial"]);
+
+        // Dropping a source releases it
+        drop(serial);
+        assert!(!line.asserted());
+        assert!(line.asserting().is_empty());
+    }
+
+    #[test]
+    fn test_irq_line_dropped_source_unregistered() {
+        let line = IrqLine::new();
+        let timer = line.source("timer");
+        let serial = line.source("serial");
+        serial.assert();
+        assert_eq!(line.source_count(), 2);
+
+        drop(serial);
+        assert_eq!(line.source_count(), 1);
+
+        // A reconnected device takes the freed slot, released
+        for _ in 0..4 {
+            let serial = line.source("serial2");
+            assert_eq!(line.source_count(), 2);
+            assert!(!serial.is_asserted());
+            serial.assert();
+            assert_eq!(line.asserting(), vec!["serial2"]);
+        }
+        assert_eq!(line.source_count(), 1);
+        assert!(!line.asserted());
+        assert_eq!(timer.name(), "timer");
+    }
+
+    /// Bus with a device which asserts its IRQ source
+    /// while bit 0 of 0xD000 is set
+    struct IrqBus {
+        bus: MainBus,
+        irq: Option<IrqSource>,
+    }
+
+    impl Bus for IrqBus {
+        fn read(&self, address: Address) -> Option<Byte> {
+            self.bus.read(address)
+        }
+
+        fn write(&mut self, address: Address, data: Byte) {
+            if address == 0xD000 {
+                if let Some(irq) = &self.irq {
+                    irq.set(data & 1 != 0);
+                }
+            }
+            self.bus.write(address, data)
+        }
+
+        fn read_seq(&self, address: Address, len: u16) -> Option<Vec<Byte>> {
+            self.bus.read_seq(address, len)
+        }
+
+        fn connect_irq(&mut self, line: &IrqLine) {
+            self.irq = Some(line.source("device"));
+        }
+    }
+
+    #[test]
+    fn test_irq_from_device() {
+        let mut cpu = Cpu::new_custompc(0x0200);
+        cpu.connect_to(Rc::new(RefCell::new(IrqBus {
+            bus: MainBus::new(),
+            irq: None,
+        })));
+        // lda #1 ; sta $D000 ; nop
+        let prog: Vec<Byte> = vec![0xA9, 0x01, 0x8D, 0x00, 0xD0, 0xEA];
+        let _ = cpu.load_program(&prog, 0x0200, prog.len(), true);
+        cpu.writ_byte(0xFFFE, 0x00);
+        cpu.writ_byte(0xFFFF, 0x20);
+        cpu.regset_mut().set_irq_disabled(false);
+
+        cpu.set_irq_line(true);
+        assert_eq!(cpu.irq_sources_asserting(), vec!["host".to_string()]);
+        cpu.set_irq_line(false);
+        assert!(cpu.irq_sources_asserting().is_empty());
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.irq_sources_asserting(), vec!["device".to_string()]);
+
+        // The store happens on the last cycle, so the `nop` still executes
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x0206);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0x2000);
+    }
+
+    fn setup_reset(variant: CpuVariant) -> Cpu {
+        let mut cpu = Cpu::new_variant(variant);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+        cpu.writ_byte(0xFFFC, 0x34);
+        cpu.writ_byte(0xFFFD, 0x12);
+        cpu
+    }
+
+    #[test]
+    fn test_power_on_reset() {
+        let mut cpu = setup_reset(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0x42);
+        cpu.writ_byte(0x0100, 0xAA);
+        cpu.writ_byte(0x01FF, 0xBB);
+        cpu.writ_byte(0x01FE, 0xCC);
+
+        cpu.reset_with(ResetKind::PowerOn);
+        for _ in 0..7 {
+            assert_eq!(cpu.clock_cycle(), Ok(()));
+        }
+
+        assert_eq!(cpu.time().residual(), 0);
+        assert_eq!(cpu.time().elapsed(), 7);
+        assert_eq!(cpu.pc(), 0x1234);
+        assert_eq!(cpu.regset().accumulator(), 0x00);
+        assert_eq!(cpu.regset().stk_ptr(), 0xFD);
+        assert_eq!(cpu.regset().status(), 0x24);
+
+        // Nothing is written to the stack
+        assert_eq!(cpu.read_some(0x01FE, 2), vec![0xCC, 0xBB]);
+        assert_eq!(cpu.read_byte(0x0100), 0xAA);
+    }
+
+    #[test]
+    fn test_power_on_random_reset() {
+        let mut cpu1 = setup_reset(CpuVariant::Nmos);
+        let mut cpu2 = setup_reset(CpuVariant::Nmos);
+        cpu1.reset_with(ResetKind::PowerOnRandom { seed: 6502 });
+        cpu2.reset_with(ResetKind::PowerOnRandom { seed: 6502 });
+        assert_eq!(cpu1.regset(), cpu2.regset());
+
+        let stk_ptr = cpu1.regset().stk_ptr();
+        assert_eq!(cpu1.full_instruction(), Ok(()));
+        assert_eq!(cpu1.pc(), 0x1234);
+        assert_eq!(cpu1.regset().stk_ptr(), stk_ptr.wrapping_sub(3));
+        assert!(cpu1.regset().irq_disabled());
+    }
+
+    #[test]
+    fn test_warm_reset() {
+        // lda #$10 ; ldx #$20 ; ldy #$30 ; pha
+        let mut cpu = setup_reset(CpuVariant::Cmos);
+        let prog: Vec<Byte> = vec![0xA9, 0x10, 0xA2, 0x20, 0xA0, 0x30, 0x48];
+        let _ = cpu.load_program(&prog, 0x0200, prog.len(), true);
+        for _ in 0..3 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_irq_disabled(false);
+
+        // Abandons the `pha` in the middle of it
+        assert_eq!(cpu.clock_cycle(), Ok(()));
+        cpu.reset();
+        assert_eq!(cpu.full_instruction(), Ok(()));
+
+        assert_eq!(cpu.pc(), 0x1234);
+        assert_eq!(cpu.regset().accumulator(), 0x10);
+        assert_eq!(cpu.regset().x_index(), 0x20);
+        assert_eq!(cpu.regset().y_index(), 0x30);
+        assert_eq!(cpu.regset().stk_ptr(), 0xFA);
+        assert!(cpu.regset().irq_disabled());
+        assert!(!cpu.regset().decimal_mode());
+        assert_eq!(cpu.time().elapsed(), 2 * 3 + 1 + 7);
+    }
 }
@@ -1,294 +1,427 @@
 #[cfg(test)]
 mod test {
     use crate::mos6502::{
-        Address, AddressingOutput, AddressingOutput::*, Byte, Cpu, Instruction, MainBus, Opcode,
-        Operand, RegisterSet, Word, BRK_VECTOR,
+        Address, AddressingOutput, AddressingOutput::*, Byte, Cpu, CpuVariant, MainBus,
     };
-    use crate::mos6502_addressing_modes::*;
-    use crate::mos6502_instruction_set::*;
 
     use std::cell::RefCell;
     use std::rc::Rc;
 
-    // use super::*;
-
     /// A setup() routine for all tests.
-    fn setup(custom_pc: Word, connect: bool, opcode: Opcode, operand: Operand) -> Cpu {
-        let mut cpu = Cpu::new_custompc(custom_pc);
+    fn setup(variant: CpuVariant) -> Cpu {
+        let mut cpu = Cpu::new_variant(variant);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
 
-        cpu.i_decode_and_set(opcode, operand);
+        cpu
+    }
 
-        if connect {
-            cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
-        }
+    /// Places `program` at `address` and runs it as a single instruction.
+    /// Returns the addressing output and the number of cycles it took.
+    fn run(cpu: &mut Cpu, address: Address, program: &[Byte]) -> (AddressingOutput, u64) {
+        assert!(cpu
+            .load_program(program, address, program.len(), true)
+            .is_ok());
 
-        cpu
+        let start = cpu.time().elapsed();
+        assert_eq!(cpu.full_instruction(), Ok(()));
+
+        (
+            cpu.i().unwrap().amode_output(),
+            cpu.time().elapsed() - start,
+        )
     }
 
     #[test]
     fn test_implied_am() {
-        let mut cpu = setup(0x0000, false, 0x08, None);
+        let mut cpu = setup(CpuVariant::Nmos);
         cpu.regset_mut().set_accumulator(0x1A);
 
-        let result = implied_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x08]);
 
-        assert_eq!(result.ok(), Some(ValueOnly(0x1A)));
-        assert_eq!(cpu.pc(), 0x0000);
+        assert_eq!(result, ValueOnly(0x1A));
+        assert_eq!(cpu.pc(), 0x0401);
+        assert_eq!(cycles, 3);
     }
 
     #[test]
     fn test_immediate_am() {
-        let mut cpu = setup(0x0000, true, 0xA9, Some(0x10));
-        cpu.writ_byte(0xFA, 0x10);
+        let mut cpu = setup(CpuVariant::Nmos);
 
-        let result = immediate_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0xA9, 0x10]);
 
-        assert_eq!(result.ok(), Some(ValueOnly(0x10)));
-        assert_eq!(cpu.pc(), 0x0000);
+        assert_eq!(result, ValueOnly(0x10));
+        assert_eq!(cpu.regset().accumulator(), 0x10);
+        assert_eq!(cpu.pc(), 0x0402);
+        assert_eq!(cycles, 2);
     }
 
     #[test]
     fn test_zeropage_am() {
-        let mut cpu = setup(0x0000, true, 0x25, Some(0x35));
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
         cpu.writ_byte(0x35, 0x10);
 
-        let result = zeropage_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x25, 0x35]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x10,
                 address: 0x35,
-            })
+            }
         );
+        assert_eq!(cpu.regset().accumulator(), 0x10);
+        assert_eq!(cycles, 3);
     }
 
     #[test]
     fn test_zeropage_x_offset_am() {
-        let mut cpu = setup(0x00, true, 0x35, Some(0x35));
-        *cpu.regset_mut().x_index_mut() = 3;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_x_index(3);
         cpu.writ_byte(0x35 + 3, 0x10);
 
-        let result = zeropage_x_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x35, 0x35]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x10,
                 address: 0x35 + 3,
-            })
+            }
         );
+        assert_eq!(cycles, 4);
     }
 
     #[test]
     fn test_zeropage_offset_wrapping_am() {
-        let mut cpu = setup(0x00, true, 0x35, Some(0x35));
-        *cpu.regset_mut().y_index_mut() = 0xff;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_y_index(0xFF);
         cpu.writ_byte(0x35 - 1, 0x10);
 
-        let result = zeropage_y_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0xB6, 0x35]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x10,
                 address: 0x35 - 1,
-            })
+            }
         );
+        assert_eq!(cpu.regset().x_index(), 0x10);
+        assert_eq!(cycles, 4);
     }
 
     #[test]
     fn test_absolute_am() {
-        let mut cpu = setup(0x00, true, 0x2D, Some(0x0210));
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
         cpu.writ_byte(0x0210, 0x10);
 
-        let result = absolute_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x2D, 0x10, 0x02]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x10,
                 address: 0x210,
-            })
+            }
         );
+        assert_eq!(cpu.pc(), 0x0403);
+        assert_eq!(cycles, 4);
     }
 
     #[test]
     fn test_absolute_offset_am() {
-        let mut cpu = setup(0x00, true, 0x3D, Some(0x0210));
-        *cpu.regset_mut().y_index_mut() = 0xA;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_y_index(0xA);
         cpu.writ_byte(0x0210 + 0xA, 0x10);
 
-        let result = absolute_y_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x39, 0x10, 0x02]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x10,
                 address: 0x210 + 0xA,
-            })
+            }
+        );
+        assert_eq!(cycles, 4);
+    }
+
+    #[test]
+    fn test_absolute_offset_am_page_cross() {
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_y_index(0xF0);
+        cpu.writ_byte(0x0210 + 0xF0, 0x10);
+
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x39, 0x10, 0x02]);
+
+        assert_eq!(
+            result,
+            Fetched {
+                value: 0x10,
+                address: 0x210 + 0xF0,
+            }
         );
+        assert_eq!(cycles, 5);
     }
 
     #[test]
     fn test_relative_am_w_positive_number() {
-        let mut cpu = setup(0x00, true, 0x30, Some(0x10));
-        cpu.writ_byte(0x10, 0x10);
+        let mut cpu = setup(CpuVariant::Nmos);
 
-        let result = relative_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x30, 0x10]);
 
-        assert_eq!(result.ok(), Some(AbsoluteAddress(0x10)));
+        assert_eq!(result, AbsoluteAddress(0x0412));
+        // The branch is not taken
+        assert_eq!(cpu.pc(), 0x0402);
+        assert_eq!(cycles, 2);
     }
 
     #[test]
     fn test_relative_am_w_negative_number() {
-        let mut cpu = setup(0x90, true, 0x30, Some(0x80));
-        cpu.writ_byte(0x10, 0x10);
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_negative(true);
 
-        let result = relative_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x008E, &[0x30, 0x80]);
 
-        assert_eq!(result.ok(), Some(AbsoluteAddress(0x10)));
+        assert_eq!(result, AbsoluteAddress(0x10));
+        assert_eq!(cpu.pc(), 0x10);
+        assert_eq!(cycles, 3);
     }
 
     #[test]
     fn test_indirect_am_page_cross() {
-        let mut cpu = setup(0x00, true, 0x6C, Some(0x1011));
+        let mut cpu = setup(CpuVariant::Nmos);
         cpu.writ_byte(0x1011, 0x01);
         cpu.writ_byte(0x1012, 0xFF);
 
-        let result = indirect_am(&mut cpu);
+        let (_, cycles) = run(&mut cpu, 0x0400, &[0x6C, 0x11, 0x10]);
 
-        assert_eq!(result.ok(), Some(AbsoluteAddress(0xFF01)));
+        assert_eq!(cpu.pc(), 0xFF01);
+        assert_eq!(cycles, 5);
     }
 
     #[test]
     fn test_indirect_am() {
-        let mut cpu = setup(0x00, true, 0x6C, Some(0x10FF));
+        let mut cpu = setup(CpuVariant::Nmos);
         cpu.writ_byte(0x10FF, 0x01);
         cpu.writ_byte(0x1000, 0xA7);
 
-        let result = indirect_am(&mut cpu);
+        let (_, cycles) = run(&mut cpu, 0x0400, &[0x6C, 0xFF, 0x10]);
 
-        assert_eq!(result.ok(), Some(AbsoluteAddress(0xA701)));
+        assert_eq!(cpu.pc(), 0xA701);
+        assert_eq!(cycles, 5);
     }
 
     #[test]
     fn test_indirect_xoffset_am_1() {
-        let mut cpu = setup(0x00, true, 0x21, Some(0x20));
-        *cpu.regset_mut().x_index_mut() = 0x04;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_x_index(0x04);
         cpu.writ_byte(0x24, 0x74);
         cpu.writ_byte(0x25, 0x20);
         cpu.writ_byte(0x2074, 0x10);
 
-        let result = indirect_x_am(&mut cpu);
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x21, 0x20]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x10,
                 address: 0x2074,
-            })
+            }
         );
+        assert_eq!(cycles, 6);
     }
 
     #[test]
     fn test_indirect_xoffset_am_2() {
-        let mut cpu = setup(0x00, true, 0x21, Some(0x25));
-        *cpu.regset_mut().x_index_mut() = 0x10;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_x_index(0x10);
         cpu.writ_byte(0x35, 0x01);
         cpu.writ_byte(0x36, 0xA7);
         cpu.writ_byte(0xA701, 0x19);
 
-        let result = indirect_x_am(&mut cpu);
+        let (result, _) = run(&mut cpu, 0x0400, &[0x21, 0x25]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x19,
                 address: 0xA701,
-            })
+            }
         );
     }
 
     #[test]
     fn test_indirect_xoffset_am_3() {
-        let mut cpu = setup(0x00, true, 0x21, Some(0xFE));
-        *cpu.regset_mut().x_index_mut() = 0x01;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_x_index(0x01);
         cpu.writ_byte(0x0000, 0xFE);
         cpu.writ_byte(0x00FF, 0x01);
         cpu.writ_byte(0xFE01, 0x17);
 
-        let result = indirect_x_am(&mut cpu);
+        let (result, _) = run(&mut cpu, 0x0400, &[0x21, 0xFE]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x17,
                 address: 0xFE01,
-            })
+            }
         );
     }
 
     #[test]
     fn test_indirect_yoffset_am_1() {
-        let mut cpu = setup(0x00, true, 0x31, Some(0x25));
-        *cpu.regset_mut().y_index_mut() = 0x10;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_y_index(0x10);
         cpu.writ_byte(0x0025, 0xFF);
         cpu.writ_byte(0x0026, 0xA7);
         cpu.writ_byte(0xA80F, 0x34);
 
-        let result = indirect_y_am(&mut cpu);
-        let marked_extra_cycle = cpu.time().residual() == 1;
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x31, 0x25]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x34,
                 address: 0xA80F,
-            })
+            }
         );
-        assert_eq!(marked_extra_cycle, true);
+        // One extra cycle for the page cross
+        assert_eq!(cycles, 6);
     }
 
     #[test]
     fn test_indirect_yoffset_am_2() {
-        let mut cpu = setup(0x00, true, 0x31, Some(0x25));
-        *cpu.regset_mut().y_index_mut() = 0x10;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_y_index(0x10);
         cpu.writ_byte(0x0025, 0x01);
         cpu.writ_byte(0x0026, 0xA7);
         cpu.writ_byte(0xA711, 0x34);
 
-        let result = indirect_y_am(&mut cpu);
-        let marked_extra_cycle = cpu.time().residual() == 1;
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x31, 0x25]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x34,
                 address: 0xA711,
-            })
+            }
         );
-        assert_eq!(marked_extra_cycle, false);
+        assert_eq!(cycles, 5);
     }
 
     #[test]
     fn test_indirect_yoffset_am_3() {
-        let mut cpu = setup(0x00, true, 0x31, Some(0x86));
-        *cpu.regset_mut().y_index_mut() = 0x10;
+        let mut cpu = setup(CpuVariant::Nmos);
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_y_index(0x10);
         cpu.writ_byte(0x0086, 0x28);
         cpu.writ_byte(0x0087, 0x40);
         cpu.writ_byte(0x4038, 0x37);
 
-        let result = indirect_y_am(&mut cpu);
-        let marked_extra_cycle = cpu.time().residual() == 1;
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0x31, 0x86]);
 
         assert_eq!(
-            result.ok(),
-            Some(Fetched {
+            result,
+            Fetched {
                 value: 0x37,
                 address: 0x4038,
-            })
+            }
+        );
+        assert_eq!(cycles, 5);
+    }
+
+    #[test]
+    fn test_indirect_am_cmos() {
+        let mut cpu = setup(CpuVariant::Cmos);
+        cpu.writ_byte(0x10FF, 0x01);
+        cpu.writ_byte(0x1000, 0xA7);
+        cpu.writ_byte(0x1100, 0x3C);
+
+        let (_, cycles) = run(&mut cpu, 0x0400, &[0x6C, 0xFF, 0x10]);
+
+        assert_eq!(cpu.pc(), 0x3C01);
+        assert_eq!(cycles, 6);
+    }
+
+    #[test]
+    fn test_indirect_zeropage_am() {
+        let mut cpu = setup(CpuVariant::Cmos);
+        cpu.writ_byte(0x20, 0x74);
+        cpu.writ_byte(0x21, 0x20);
+        cpu.writ_byte(0x2074, 0x42);
+
+        let (result, cycles) = run(&mut cpu, 0x0400, &[0xB2, 0x20]);
+
+        assert_eq!(
+            result,
+            Fetched {
+                value: 0x42,
+                address: 0x2074,
+            }
+        );
+        assert_eq!(cpu.regset().accumulator(), 0x42);
+        assert_eq!(cycles, 5);
+    }
+
+    #[test]
+    fn test_indirect_zeropage_am_wrapping() {
+        let mut cpu = setup(CpuVariant::Cmos);
+        cpu.writ_byte(0xFF, 0x74);
+        cpu.writ_byte(0x00, 0x20);
+        cpu.writ_byte(0x2074, 0x42);
+
+        let (result, _) = run(&mut cpu, 0x0400, &[0xB2, 0xFF]);
+
+        assert_eq!(
+            result,
+            Fetched {
+                value: 0x42,
+                address: 0x2074,
+            }
+        );
+    }
+
+    #[test]
+    fn test_indirect_absolute_x_am() {
+        let mut cpu = setup(CpuVariant::Cmos);
+        cpu.regset_mut().set_x_index(0x01);
+        cpu.writ_byte(0x10FF, 0x01);
+        cpu.writ_byte(0x1100, 0xA7);
+
+        let (_, cycles) = run(&mut cpu, 0x0400, &[0x7C, 0xFE, 0x10]);
+
+        assert_eq!(cpu.pc(), 0xA701);
+        assert_eq!(cycles, 6);
+    }
+
+    #[test]
+    fn test_zeropage_relative_am() {
+        let mut cpu = setup(CpuVariant::Cmos);
+        cpu.writ_byte(0x10, 0x42);
+
+        let (result, cycles) = run(&mut cpu, 0x1000, &[0x0F, 0x10, 0xFD]);
+
+        assert_eq!(
+            result,
+            Fetched {
+                value: 0x42,
+                address: 0x1000,
+            }
         );
-        assert_eq!(marked_extra_cycle, false);
+        // Bit 0 is clear, so the branch is taken
+        assert_eq!(cpu.pc(), 0x1000);
+        assert_eq!(cycles, 6);
     }
 }
@@ -1,6 +1,6 @@
 use crate::mos6502::{
-    Address, AddressingOutput, AddressingOutput::*, Byte, Cpu, Instruction, MainBus, Opcode,
-    RegisterSet, Word, BRK_VECTOR,
+    Address, AddressingOutput, AddressingOutput::*, Byte, Cpu, CpuError, CpuVariant, Instruction,
+    MainBus, Opcode, RegisterSet, RunState, Word, BRK_VECTOR,
 };
 use crate::mos6502_instruction_set::*;
 
@@ -24,6 +24,22 @@ mod test {
         cpu
     }
 
+    /// Same as setup() but for a concrete cpu variant.
+    fn setup_variant(
+        variant: CpuVariant,
+        custom_pc: Word,
+        opcode: Opcode,
+        operand: Option<Word>,
+    ) -> Cpu {
+        let mut cpu = Cpu::new_variant(variant);
+        cpu.regset_mut().set_prog_counter(custom_pc);
+        cpu.connect_to(Rc::new(RefCell::new(MainBus::new())));
+
+        cpu.i_decode_and_set(opcode, operand);
+
+        cpu
+    }
+
     #[test]
     fn test_adc_regular() {
         let mut cpu = setup(0x0000, true, Some(0x69), Some(0x10));
@@ -106,6 +122,60 @@ mod test {
         assert_eq!(regs.overflowed(), true);
     }
 
+    #[test]
+    fn test_adc_decimal() {
+        let mut cpu = setup(0x0000, false, Some(0x69), Some(0x01));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x09);
+
+        let res = adc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x10);
+        assert!(!regs.carry());
+        assert!(!regs.zero());
+        assert!(!regs.negative());
+        assert!(!regs.overflowed());
+    }
+
+    #[test]
+    fn test_adc_decimal_carry() {
+        let mut cpu = setup(0x0000, false, Some(0x69), Some(0x01));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x99);
+
+        let res = adc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x00);
+        assert!(regs.carry());
+        // On the NMOS 6502 the Zero flag follows the binary result ($9A)
+        assert!(!regs.zero());
+        assert!(regs.negative());
+        assert!(!regs.overflowed());
+    }
+
+    #[test]
+    fn test_adc_decimal_overflowed() {
+        let mut cpu = setup(0x0000, false, Some(0x69), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x10));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x79);
+
+        let res = adc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x89);
+        assert!(!regs.carry());
+        assert!(regs.negative());
+        assert!(regs.overflowed());
+    }
+
     #[test]
     fn test_and_zero() {
         let mut cpu = setup(0x0000, true, Some(0x29), Some(0x0));
@@ -475,6 +545,22 @@ mod test {
         assert_eq!(regs.zero(), false);
     }
 
+    #[test]
+    fn test_dey() {
+        let mut cpu = setup(0xFEBE, true, Some(0x88), None);
+        cpu.regset_mut().set_x_index(0x42);
+        cpu.regset_mut().set_y_index(0x00);
+
+        let res = dey(&mut cpu);
+
+        let regs = cpu.regset();
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(regs.y_index(), 0xFF);
+        assert_eq!(regs.x_index(), 0x42);
+        assert!(regs.negative());
+        assert!(!regs.zero());
+    }
+
     #[test]
     fn test_inc() {
         let mut cpu = setup(0xFEBE, true, Some(0xE8), None);
@@ -822,6 +908,186 @@ mod test {
         assert_eq!(regs.overflowed(), false);
     }
 
+    #[test]
+    fn test_sbc_decimal() {
+        let mut cpu = setup(0x0000, false, Some(0xE9), Some(0x01));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0x10);
+
+        let res = sbc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x09);
+        assert!(regs.carry());
+        assert!(!regs.zero());
+        assert!(!regs.negative());
+        assert!(!regs.overflowed());
+    }
+
+    #[test]
+    fn test_sbc_decimal_borrow() {
+        let mut cpu = setup(0x0000, false, Some(0xE9), Some(0x01));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0x00);
+
+        let res = sbc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x99);
+        assert!(!regs.carry());
+        assert!(!regs.zero());
+        assert!(regs.negative());
+        assert!(!regs.overflowed());
+    }
+
+    /// The expected results of a decimal mode operation -- the value of the
+    /// accumulator and the C, Z, N and V flags (in that order).
+    type DecimalOutcome = (Byte, bool, bool, bool, bool);
+
+    /// Reference model of the NMOS decimal mode `ADC` written directly after
+    /// sequences 1 and 2 of [this](http://www.6502.org/tutorials/decimal_mode.html)
+    /// tutorial, using signed arithmetic for the N and V flags.
+    fn decimal_adc_reference(a: Byte, b: Byte, c: bool) -> DecimalOutcome {
+        let c = i32::from(c);
+        let mut al = i32::from(a & 0x0F) + i32::from(b & 0x0F) + c;
+        if al >= 0x0A {
+            al = ((al + 0x06) & 0x0F) + 0x10;
+        }
+
+        let mut sum = i32::from(a & 0xF0) + i32::from(b & 0xF0) + al;
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+
+        let signed = i32::from((a & 0xF0) as i8) + i32::from((b & 0xF0) as i8) + al;
+        let binary = (i32::from(a) + i32::from(b) + c) & 0xFF;
+
+        (
+            (sum & 0xFF) as Byte,
+            sum >= 0x100,
+            binary == 0,
+            signed & 0x80 != 0,
+            !(-128..=127).contains(&signed),
+        )
+    }
+
+    /// Reference model of the NMOS decimal mode `SBC` written directly after
+    /// sequence 3 of the same tutorial. All flags are the binary ones.
+    fn decimal_sbc_reference(a: Byte, b: Byte, c: bool) -> DecimalOutcome {
+        let c = i32::from(c);
+        let mut al = i32::from(a & 0x0F) - i32::from(b & 0x0F) + c - 1;
+        if al < 0 {
+            al = ((al - 0x06) & 0x0F) - 0x10;
+        }
+
+        let mut diff = i32::from(a & 0xF0) - i32::from(b & 0xF0) + al;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+
+        let binary = i32::from(a) - i32::from(b) + c - 1;
+        let signed = i32::from(a as i8) - i32::from(b as i8) + c - 1;
+
+        (
+            (diff & 0xFF) as Byte,
+            binary >= 0,
+            binary & 0xFF == 0,
+            binary & 0x80 != 0,
+            !(-128..=127).contains(&signed),
+        )
+    }
+
+    fn decimal_table(reference: fn(Byte, Byte, bool) -> DecimalOutcome) -> Vec<DecimalOutcome> {
+        let mut table = Vec::with_capacity(256 * 256 * 2);
+        for a in 0..=0xFF {
+            for b in 0..=0xFF {
+                for &c in [false, true].iter() {
+                    table.push(reference(a, b, c));
+                }
+            }
+        }
+        table
+    }
+
+    fn run_decimal_table(
+        opcode: Opcode,
+        execute: fn(&mut Cpu) -> Result<(), CpuError>,
+        table: &[DecimalOutcome],
+    ) {
+        let mut cpu = setup(0x0000, false, Some(opcode), None);
+        let mut expected = table.iter();
+
+        for a in 0..=0xFF {
+            for b in 0..=0xFF {
+                for &c in [false, true].iter() {
+                    cpu.i_mut().unwrap().set_amode_output(ValueOnly(b));
+                    let regs = cpu.regset_mut();
+                    regs.set_status(0x00);
+                    regs.set_decimal_mode(true);
+                    regs.set_carry(c);
+                    regs.set_accumulator(a);
+
+                    assert_eq!(execute(&mut cpu), Ok(()));
+
+                    let regs = cpu.regset();
+                    let actual = (
+                        regs.accumulator(),
+                        regs.carry(),
+                        regs.zero(),
+                        regs.negative(),
+                        regs.overflowed(),
+                    );
+                    let outcome = *expected.next().unwrap();
+                    assert_eq!(actual, outcome, "A = {:#04x}, M = {:#04x}, C = {}", a, b, c);
+                }
+            }
+        }
+    }
+
+    fn from_bcd(value: Byte) -> u16 {
+        u16::from(value >> 4) * 10 + u16::from(value & 0x0F)
+    }
+
+    fn to_bcd(value: u16) -> Byte {
+        (((value / 10) << 4) | (value % 10)) as Byte
+    }
+
+    #[test]
+    fn test_decimal_reference_with_valid_bcd() {
+        // Sanity check of the reference models against plain decimal arithmetic
+        for a in (0..100).map(to_bcd) {
+            for b in (0..100).map(to_bcd) {
+                for &c in [false, true].iter() {
+                    let sum = from_bcd(a) + from_bcd(b) + u16::from(c);
+                    let (value, carry, ..) = decimal_adc_reference(a, b, c);
+                    assert_eq!((value, carry), (to_bcd(sum % 100), sum >= 100));
+
+                    let diff = 100 + from_bcd(a) - from_bcd(b) - u16::from(!c);
+                    let (value, carry, ..) = decimal_sbc_reference(a, b, c);
+                    assert_eq!((value, carry), (to_bcd(diff % 100), diff >= 100));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_adc_decimal_exhaustive() {
+        let table = decimal_table(decimal_adc_reference);
+        run_decimal_table(0x69, adc, &table);
+    }
+
+    #[test]
+    fn test_sbc_decimal_exhaustive() {
+        let table = decimal_table(decimal_sbc_reference);
+        run_decimal_table(0xE9, sbc, &table);
+    }
+
     #[test]
     fn test_rol() {
         let mut cpu = setup(0x0000, true, Some(0x69), Some(0x01));
@@ -912,4 +1178,733 @@ mod test {
         assert_eq!(regs.carry(), true);
         assert_eq!(regs.negative(), false);
     }
+
+    #[test]
+    fn test_undocumented_opcodes_decoded() {
+        let illegal: Vec<Instruction> = (0..=0xFF)
+            .map(Instruction::decode_by)
+            .filter(|i| i.illegal())
+            .collect();
+
+        assert_eq!(illegal.len(), 105);
+        assert_eq!(illegal.iter().filter(|i| i.mnemonic() == "jam").count(), 12);
+        assert_eq!(illegal.iter().filter(|i| i.mnemonic() == "nop").count(), 27);
+    }
+
+    #[test]
+    fn test_jam() {
+        let mut cpu = setup(0x1001, true, Some(0x02), None);
+        cpu.i_mut().unwrap().set_loaded_from(0x1000);
+
+        let res = jam(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.pc(), 0x1000);
+    }
+
+    #[test]
+    fn test_slo() {
+        let mut cpu = setup(0x0000, true, Some(0x07), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xC1,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_accumulator(0x04);
+
+        let res = slo(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x82);
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x86);
+        assert!(regs.carry());
+        assert!(!regs.zero());
+        assert!(regs.negative());
+    }
+
+    #[test]
+    fn test_rla() {
+        let mut cpu = setup(0x0000, true, Some(0x27), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x81,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0x0C);
+
+        let res = rla(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x03);
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x00);
+        assert!(regs.carry());
+        assert!(regs.zero());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_sre() {
+        let mut cpu = setup(0x0000, true, Some(0x47), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x03,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_accumulator(0x81);
+
+        let res = sre(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x01);
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x80);
+        assert!(regs.carry());
+        assert!(!regs.zero());
+        assert!(regs.negative());
+    }
+
+    #[test]
+    fn test_rra() {
+        let mut cpu = setup(0x0000, true, Some(0x67), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x03,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0x10);
+
+        let res = rra(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x81);
+        let regs = cpu.regset();
+        // 0x10 + 0x81 + 1 (the carry shifted out of memory)
+        assert_eq!(regs.accumulator(), 0x92);
+        assert!(!regs.carry());
+        assert!(regs.negative());
+        assert!(!regs.overflowed());
+    }
+
+    #[test]
+    fn test_rra_decimal() {
+        let mut cpu = setup(0x0000, true, Some(0x67), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x12,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x15);
+
+        let res = rra(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x09);
+        assert_eq!(cpu.regset().accumulator(), 0x24);
+        assert!(!cpu.regset().carry());
+    }
+
+    #[test]
+    fn test_sax() {
+        let mut cpu = setup(0x0000, true, Some(0x87), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x00,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_accumulator(0xF3);
+        cpu.regset_mut().set_x_index(0x3C);
+        let status = cpu.regset().status();
+
+        let res = sax(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x30);
+        assert_eq!(cpu.regset().status(), status);
+    }
+
+    #[test]
+    fn test_lax() {
+        let mut cpu = setup(0x0000, true, Some(0xA7), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x80,
+            address: 0x10,
+        });
+
+        let res = lax(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x80);
+        assert_eq!(regs.x_index(), 0x80);
+        assert!(!regs.zero());
+        assert!(regs.negative());
+    }
+
+    #[test]
+    fn test_dcp() {
+        let mut cpu = setup(0x0000, true, Some(0xC7), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x11,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_accumulator(0x10);
+
+        let res = dcp(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x10);
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x10);
+        assert!(regs.carry());
+        assert!(regs.zero());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_isc() {
+        let mut cpu = setup(0x0000, true, Some(0xE7), Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xFF,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0x05);
+
+        let res = isc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x00);
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x05);
+        assert!(regs.carry());
+        assert!(!regs.zero());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_anc() {
+        let mut cpu = setup(0x0000, true, Some(0x0B), Some(0x81));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x81));
+        cpu.regset_mut().set_accumulator(0xF0);
+
+        let res = anc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x80);
+        assert!(regs.carry());
+        assert!(regs.negative());
+        assert!(!regs.zero());
+    }
+
+    #[test]
+    fn test_alr() {
+        let mut cpu = setup(0x0000, true, Some(0x4B), Some(0x03));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x03));
+        cpu.regset_mut().set_accumulator(0xFF);
+
+        let res = alr(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x01);
+        assert!(regs.carry());
+        assert!(!regs.negative());
+        assert!(!regs.zero());
+    }
+
+    #[test]
+    fn test_arr() {
+        let mut cpu = setup(0x0000, true, Some(0x6B), Some(0xC0));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0xC0));
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0xFF);
+
+        let res = arr(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0xE0);
+        assert!(regs.carry());
+        assert!(!regs.overflowed());
+        assert!(regs.negative());
+        assert!(!regs.zero());
+    }
+
+    #[test]
+    fn test_arr_overflowed() {
+        let mut cpu = setup(0x0000, true, Some(0x6B), Some(0x80));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x80));
+        cpu.regset_mut().set_accumulator(0xFF);
+
+        let res = arr(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x40);
+        assert!(regs.carry());
+        assert!(regs.overflowed());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_arr_decimal() {
+        let mut cpu = setup(0x0000, true, Some(0x6B), Some(0xFF));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0xFF));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x99);
+
+        let res = arr(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        // 0x99 >> 1 = 0x4C, then both nibbles get adjusted since both of 0x99 are over 5
+        assert_eq!(regs.accumulator(), 0xA2);
+        assert!(regs.carry());
+        assert!(regs.overflowed());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_sbx() {
+        let mut cpu = setup(0x0000, true, Some(0xCB), Some(0x02));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x02));
+        cpu.regset_mut().set_accumulator(0x0F);
+        cpu.regset_mut().set_x_index(0x3C);
+        cpu.regset_mut().set_decimal_mode(true);
+
+        let res = sbx(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.x_index(), 0x0A);
+        assert_eq!(regs.accumulator(), 0x0F);
+        assert!(regs.carry());
+        assert!(!regs.zero());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_xaa() {
+        let mut cpu = setup(0x0000, true, Some(0x8B), Some(0xFF));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0xFF));
+        cpu.regset_mut().set_accumulator(0x00);
+        cpu.regset_mut().set_x_index(0x3F);
+
+        let res = xaa(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.regset().accumulator(), 0xEE & 0x3F);
+
+        cpu.magic_constants_mut().set_xaa(0xFF);
+        cpu.regset_mut().set_accumulator(0x00);
+        let res = xaa(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.regset().accumulator(), 0x3F);
+    }
+
+    #[test]
+    fn test_lxa() {
+        let mut cpu = setup(0x0000, true, Some(0xAB), Some(0x0F));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x0F));
+        cpu.magic_constants_mut().set_lxa(0x00);
+        cpu.regset_mut().set_accumulator(0x81);
+
+        let res = lxa(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x01);
+        assert_eq!(regs.x_index(), 0x01);
+        assert!(!regs.zero());
+        assert!(!regs.negative());
+    }
+
+    #[test]
+    fn test_ahx() {
+        let mut cpu = setup(0x0000, true, Some(0x9F), Some(0x1210));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x00,
+            address: 0x1212,
+        });
+        cpu.regset_mut().set_accumulator(0xFF);
+        cpu.regset_mut().set_x_index(0xF7);
+        cpu.regset_mut().set_y_index(0x02);
+
+        let res = ahx(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x1212), 0xF7 & 0x13);
+
+        cpu.magic_constants_mut().set_ahx(0xFF);
+        let res = ahx(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x1212), 0xF7);
+    }
+
+    #[test]
+    fn test_tas() {
+        let mut cpu = setup(0x0000, true, Some(0x9B), Some(0x1210));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x00,
+            address: 0x1212,
+        });
+        cpu.regset_mut().set_accumulator(0x3F);
+        cpu.regset_mut().set_x_index(0xF7);
+        cpu.regset_mut().set_y_index(0x02);
+
+        let res = tas(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.regset().stk_ptr(), 0x37);
+        assert_eq!(cpu.read_byte(0x1212), 0x37 & 0x13);
+
+        cpu.magic_constants_mut().set_tas(0x20);
+        let res = tas(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x1212), 0x37 & 0x33);
+    }
+
+    #[test]
+    fn test_shy() {
+        let mut cpu = setup(0x0000, true, Some(0x9C), Some(0x1210));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x00,
+            address: 0x1212,
+        });
+        cpu.regset_mut().set_x_index(0x02);
+        cpu.regset_mut().set_y_index(0xFF);
+
+        let res = shy(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x1212), 0x13);
+
+        cpu.magic_constants_mut().set_shy(0x0C);
+        let res = shy(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x1212), 0x1F);
+    }
+
+    #[test]
+    fn test_shx_page_cross() {
+        let mut cpu = setup(0x0000, true, Some(0x9E), Some(0x12F0));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x00,
+            address: 0x1310,
+        });
+        cpu.regset_mut().set_x_index(0x05);
+        cpu.regset_mut().set_y_index(0x20);
+
+        let res = shx(&mut cpu);
+
+        // The value is 0x05 & 0x13 and it also replaces the high byte of the address
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x0110), 0x01);
+        assert_eq!(cpu.read_byte(0x1310), 0x00);
+
+        // 0x05 & (0x13 | 0x04)
+        cpu.magic_constants_mut().set_shx(0x04);
+        let res = shx(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x0510), 0x05);
+    }
+
+    #[test]
+    fn test_las() {
+        let mut cpu = setup(0x0000, true, Some(0xBB), Some(0x1210));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xAF,
+            address: 0x1210,
+        });
+        cpu.regset_mut().set_stk_ptr(0xF5);
+
+        let res = las(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0xA5);
+        assert_eq!(regs.x_index(), 0xA5);
+        assert_eq!(regs.stk_ptr(), 0xA5);
+        assert!(regs.negative());
+        assert!(!regs.zero());
+
+        cpu.magic_constants_mut().set_las(0x0A);
+        cpu.regset_mut().set_stk_ptr(0xF5);
+        let res = las(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0xAF);
+        assert_eq!(regs.stk_ptr(), 0xAF);
+    }
+
+    #[test]
+    fn test_bit_immediate() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x89, Some(0xC0));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0xC0));
+        cpu.regset_mut().set_accumulator(0x3F);
+
+        let res = bit(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert!(regs.zero());
+        assert!(!regs.negative());
+        assert!(!regs.overflowed());
+    }
+
+    #[test]
+    fn test_inc_accumulator() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x1A, None);
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x7F));
+        cpu.regset_mut().set_accumulator(0x7F);
+
+        let res = inc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x80);
+        assert!(regs.negative());
+        assert!(!regs.zero());
+    }
+
+    #[test]
+    fn test_dec_accumulator() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x3A, None);
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_accumulator(0x01);
+
+        let res = dec(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x00);
+        assert!(!regs.negative());
+        assert!(regs.zero());
+    }
+
+    #[test]
+    fn test_brk_cmos() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0xFEBE, 0x00, None);
+        cpu.regset_mut().set_decimal_mode(true);
+
+        let res = brk(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let saved_status = cpu.stk_pop();
+        assert!(saved_status & (1 << 3) != 0);
+        assert!(!cpu.regset().decimal_mode());
+    }
+
+    #[test]
+    fn test_brk_nmos_keeps_decimal() {
+        let mut cpu = setup(0xFEBE, true, Some(0x00), None);
+        cpu.regset_mut().set_decimal_mode(true);
+
+        let res = brk(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert!(cpu.regset().decimal_mode());
+    }
+
+    #[test]
+    fn test_bra() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x1002, 0x80, Some(0x10));
+        cpu.i_mut()
+            .unwrap()
+            .set_amode_output(AbsoluteAddress(0x1012));
+
+        let res = bra(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.pc(), 0x1012);
+        assert_eq!(cpu.time().residual(), 1);
+    }
+
+    #[test]
+    fn test_phx_plx() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0xDA, None);
+        cpu.regset_mut().set_x_index(0x80);
+
+        assert_eq!(phx(&mut cpu).ok(), Some(()));
+        cpu.regset_mut().set_x_index(0x00);
+        assert_eq!(plx(&mut cpu).ok(), Some(()));
+
+        let regs = cpu.regset();
+        assert_eq!(regs.x_index(), 0x80);
+        assert_eq!(regs.stk_ptr(), 0xFD);
+        assert!(regs.negative());
+        assert!(!regs.zero());
+    }
+
+    #[test]
+    fn test_phy_ply() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x5A, None);
+        cpu.regset_mut().set_y_index(0x00);
+
+        assert_eq!(phy(&mut cpu).ok(), Some(()));
+        cpu.regset_mut().set_y_index(0x42);
+        assert_eq!(ply(&mut cpu).ok(), Some(()));
+
+        let regs = cpu.regset();
+        assert_eq!(regs.y_index(), 0x00);
+        assert_eq!(regs.stk_ptr(), 0xFD);
+        assert!(!regs.negative());
+        assert!(regs.zero());
+    }
+
+    #[test]
+    fn test_stz() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x9C, Some(0x1234));
+        cpu.writ_byte(0x1234, 0xFF);
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xFF,
+            address: 0x1234,
+        });
+
+        let res = stz(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x1234), 0x00);
+    }
+
+    #[test]
+    fn test_trb() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x14, Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xF0,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_accumulator(0x30);
+
+        let res = trb(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0xC0);
+        assert!(!cpu.regset().zero());
+        assert_eq!(cpu.regset().accumulator(), 0x30);
+    }
+
+    #[test]
+    fn test_tsb() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x04, Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xF0,
+            address: 0x10,
+        });
+        cpu.regset_mut().set_accumulator(0x0F);
+
+        let res = tsb(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0xFF);
+        assert!(cpu.regset().zero());
+        assert_eq!(cpu.regset().accumulator(), 0x0F);
+    }
+
+    #[test]
+    fn test_rmb_smb() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0x57, Some(0x10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0xFF,
+            address: 0x10,
+        });
+        assert_eq!(rmb5(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0xDF);
+
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x00,
+            address: 0x10,
+        });
+        assert_eq!(smb7(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.read_byte(0x10), 0x80);
+    }
+
+    #[test]
+    fn test_bbr_bbs() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x1003, 0x0F, Some(0x0D10));
+        cpu.i_mut().unwrap().set_amode_output(Fetched {
+            value: 0x02,
+            address: 0x1010,
+        });
+
+        assert_eq!(bbr1(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.pc(), 0x1003);
+        assert_eq!(cpu.time().residual(), 0);
+
+        assert_eq!(bbr0(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.pc(), 0x1010);
+        assert_eq!(cpu.time().residual(), 1);
+
+        cpu.regset_mut().set_prog_counter(0x1003);
+        assert_eq!(bbs0(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.pc(), 0x1003);
+
+        assert_eq!(bbs1(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.pc(), 0x1010);
+    }
+
+    #[test]
+    fn test_wai_stp() {
+        let mut cpu = setup_variant(CpuVariant::Cmos, 0x0000, 0xCB, None);
+        assert_eq!(cpu.run_state(), RunState::Running);
+
+        assert_eq!(wai(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.run_state(), RunState::Waiting);
+
+        assert_eq!(stp(&mut cpu).ok(), Some(()));
+        assert_eq!(cpu.run_state(), RunState::Stopped);
+    }
+
+    #[test]
+    fn test_adc_decimal_ricoh() {
+        let mut cpu = setup_variant(CpuVariant::Ricoh2A03, 0x0000, 0x69, Some(0x01));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x09);
+
+        let res = adc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x0A);
+        assert!(regs.decimal_mode());
+    }
+
+    #[test]
+    fn test_sbc_decimal_ricoh() {
+        let mut cpu = setup_variant(CpuVariant::Ricoh2A03, 0x0000, 0xE9, Some(0x01));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0x01));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_carry(true);
+        cpu.regset_mut().set_accumulator(0x10);
+
+        let res = sbc(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x0F);
+        assert!(regs.carry());
+    }
+
+    #[test]
+    fn test_arr_decimal_ricoh() {
+        let mut cpu = setup_variant(CpuVariant::Ricoh2A03, 0x0000, 0x6B, Some(0xFF));
+        cpu.i_mut().unwrap().set_amode_output(ValueOnly(0xFF));
+        cpu.regset_mut().set_decimal_mode(true);
+        cpu.regset_mut().set_accumulator(0x99);
+
+        let res = arr(&mut cpu);
+
+        assert_eq!(res.ok(), Some(()));
+        let regs = cpu.regset();
+        assert_eq!(regs.accumulator(), 0x4C);
+        assert!(regs.carry());
+        assert!(regs.overflowed());
+    }
 }
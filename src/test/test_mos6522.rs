@@ -0,0 +1,351 @@
+#[cfg(test)]
+mod test {
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+    use crate::mos6522::*;
+    use std::sync::{Arc, Mutex};
+
+    fn ticks(via: &mut Via, n: usize) {
+        for _ in 0..n {
+            via.tick();
+        }
+    }
+
+    fn flags(via: &Via) -> Byte {
+        via.peek(IFR).unwrap()
+    }
+
+    #[test]
+    fn test_via_ports() {
+        let mut via = Via::new();
+        via.write(DDRA, 0xF0);
+        via.write(ORA, 0xAA);
+        via.set_port_a(0x55);
+        assert_eq!(via.port_a(), 0xA5);
+        assert_eq!(via.read(ORA), Some(0xA5));
+
+        via.write(DDRB, 0x0F);
+        via.write(ORB, 0x33);
+        via.set_port_b(0xC0);
+        assert_eq!(via.port_b(), 0xC3);
+        assert_eq!(via.read(ORB), Some(0xC3));
+        assert_eq!(via.read(DDRB), Some(0x0F));
+    }
+
+    #[test]
+    fn test_via_input_latching() {
+        let mut via = Via::new();
+        via.write(ACR, 0x01);
+        via.set_port_a(0x12);
+        via.set_ca1(false);
+        via.set_port_a(0x34);
+
+        // Reading without handshake leaves the flag alone
+        assert_eq!(via.read(ORA_NH), Some(0x12));
+        assert_eq!(flags(&via) & IRQ_CA1, IRQ_CA1);
+        assert_eq!(via.read(ORA), Some(0x12));
+        assert_eq!(flags(&via) & IRQ_CA1, 0);
+    }
+
+    #[test]
+    fn test_via_t1_one_shot() {
+        let line = IrqLine::new();
+        let mut via = Via::new();
+        via.connect_irq(&line);
+        via.write(IER, 0x80 | IRQ_T1);
+        via.write(ACR, 0x80);
+        via.write(T1C_L, 0x03);
+        via.write(T1C_H, 0x00);
+        assert_eq!(via.port_b() & 0x80, 0x00);
+
+        // Loaded on the first tick, then counts 3, 2, 1, 0 and 0xFFFF
+        ticks(&mut via, 4);
+        assert_eq!(via.peek(T1C_L), Some(0x00));
+        assert_eq!(flags(&via), 0x00);
+        assert!(!line.asserted());
+
+        via.tick();
+        assert_eq!(flags(&via), IRQ_ANY | IRQ_T1);
+        assert_eq!(via.port_b() & 0x80, 0x80);
+        assert_eq!(line.asserting(), vec!["6522".to_string()]);
+
+        assert_eq!(via.read(T1C_L), Some(0xFF));
+        assert_eq!(flags(&via), 0x00);
+        assert!(!line.asserted());
+
+        // The counter goes on, but a one-shot fires once
+        ticks(&mut via, 0x10000);
+        assert_eq!(flags(&via), 0x00);
+        assert_eq!(via.port_b() & 0x80, 0x80);
+    }
+
+    #[test]
+    fn test_via_t1_free_run() {
+        let mut via = Via::new();
+        via.write(ACR, 0xC0);
+        via.write(T1C_L, 0x03);
+        via.write(T1C_H, 0x00);
+
+        let mut edges = vec![];
+        let mut pb7 = via.port_b() & 0x80;
+        for cycle in 1..=20 {
+            via.tick();
+            if flags(&via) & IRQ_T1 != 0 {
+                edges.push(cycle);
+                via.write(IFR, IRQ_T1);
+            }
+            assert_ne!(via.port_b() & 0x80 == pb7, edges.last() == Some(&cycle));
+            pb7 = via.port_b() & 0x80;
+        }
+        // A period of N + 2
+        assert_eq!(edges, vec![5, 10, 15, 20]);
+
+        // A new latch is taken at the next reload
+        via.write(T1L_L, 0x00);
+        via.write(T1L_H, 0x00);
+        ticks(&mut via, 6);
+        via.write(IFR, 0x7F);
+        ticks(&mut via, 2);
+        assert_eq!(flags(&via) & IRQ_T1, IRQ_T1);
+    }
+
+    #[test]
+    fn test_via_t2() {
+        let mut via = Via::new();
+        via.write(T2C_L, 0x02);
+        via.write(T2C_H, 0x00);
+        ticks(&mut via, 3);
+        assert_eq!(flags(&via), 0x00);
+        via.tick();
+        assert_eq!(flags(&via) & IRQ_T2, IRQ_T2);
+        assert_eq!(via.read(T2C_L), Some(0xFF));
+        assert_eq!(flags(&via), 0x00);
+
+        ticks(&mut via, 0x10000);
+        assert_eq!(flags(&via), 0x00);
+
+        // Counting pulses on PB6
+        via.write(ACR, 0x20);
+        via.write(T2C_L, 0x03);
+        via.write(T2C_H, 0x00);
+        via.tick();
+        for _ in 0..3 {
+            ticks(&mut via, 10);
+            assert_eq!(flags(&via), 0x00);
+            via.set_port_b(0xBF);
+            via.set_port_b(0xFF);
+        }
+        assert_eq!(flags(&via) & IRQ_T2, IRQ_T2);
+    }
+
+    #[test]
+    fn test_via_shift_out() {
+        let mut via = Via::new();
+        via.write(ACR, 0x18);
+        via.write(SR, 0xA5);
+
+        // A bit every cycle
+        let mut bits = vec![];
+        for _ in 0..8 {
+            assert_eq!(flags(&via) & IRQ_SR, 0x00);
+            via.tick();
+            bits.push(via.cb2());
+        }
+        assert_eq!(
+            bits,
+            vec![true, false, true, false, false, true, false, true]
+        );
+        assert_eq!(flags(&via) & IRQ_SR, IRQ_SR);
+        assert_eq!(via.peek(SR), Some(0xA5));
+
+        // Stopped after 8 bits
+        ticks(&mut via, 4);
+        assert_eq!(via.peek(SR), Some(0xA5));
+        via.read(SR);
+        assert_eq!(flags(&via) & IRQ_SR, 0x00);
+    }
+
+    #[test]
+    fn test_via_shift_in_phi2() {
+        let mut via = Via::new();
+        via.write(ACR, 0x08);
+        via.set_cb2(true);
+        via.read(SR);
+
+        ticks(&mut via, 7);
+        assert_eq!(flags(&via) & IRQ_SR, 0x00);
+        via.tick();
+        assert_eq!(flags(&via) & IRQ_SR, IRQ_SR);
+        assert_eq!(via.peek(SR), Some(0xFF));
+    }
+
+    #[test]
+    fn test_via_shift_under_t2() {
+        let mut via = Via::new();
+        via.write(T2C_L, 0x01);
+        via.write(ACR, 0x14);
+        via.write(SR, 0x80);
+
+        // A bit every 2 * (N + 2) cycles
+        ticks(&mut via, 8 * 6 - 1);
+        assert_eq!(flags(&via) & IRQ_SR, 0x00);
+        via.tick();
+        assert_eq!(flags(&via) & IRQ_SR, IRQ_SR);
+    }
+
+    #[test]
+    fn test_via_shift_in_external() {
+        let mut via = Via::new();
+        via.write(ACR, 0x0C);
+        via.read(SR);
+        for &bit in &[true, true, false, false, true, false, true, true] {
+            via.set_cb2(bit);
+            via.set_cb1(false);
+            via.set_cb1(true);
+        }
+        assert_eq!(via.peek(SR), Some(0xCB));
+        assert_eq!(flags(&via) & IRQ_SR, IRQ_SR);
+    }
+
+    #[test]
+    fn test_via_handshake() {
+        let mut via = Via::new();
+
+        // CA2 in handshake mode, CA1 active on its falling edge
+        via.write(PCR, 0x08);
+        assert!(via.ca2());
+        via.write(ORA, 0x41);
+        assert!(!via.ca2());
+        via.tick();
+        assert!(!via.ca2());
+        via.set_ca1(false);
+        assert!(via.ca2());
+        assert_eq!(flags(&via) & IRQ_CA1, IRQ_CA1);
+        via.set_ca1(true);
+
+        // Pulse mode lasts one cycle
+        via.write(PCR, 0x0A);
+        via.read(ORA);
+        assert!(!via.ca2());
+        assert_eq!(flags(&via) & IRQ_CA1, 0x00);
+        via.tick();
+        assert!(via.ca2());
+
+        // CB2 handshakes on writes only, CB1 active on its rising edge
+        via.write(PCR, 0x90);
+        via.read(ORB);
+        assert!(via.cb2());
+        via.write(ORB, 0x00);
+        assert!(!via.cb2());
+        via.set_cb1(false);
+        assert!(!via.cb2());
+        via.set_cb1(true);
+        assert!(via.cb2());
+        assert_eq!(flags(&via) & IRQ_CB1, IRQ_CB1);
+
+        // Fixed outputs
+        via.write(PCR, 0xCC);
+        assert!(!via.ca2());
+        assert!(!via.cb2());
+        via.write(PCR, 0xEE);
+        assert!(via.ca2());
+        assert!(via.cb2());
+    }
+
+    #[test]
+    fn test_via_ca2_input() {
+        let mut via = Via::new();
+
+        // Negative edge, cleared by accessing the port
+        via.set_ca2(false);
+        assert_eq!(flags(&via) & IRQ_CA2, IRQ_CA2);
+        via.read(ORA);
+        assert_eq!(flags(&via) & IRQ_CA2, 0x00);
+
+        // Positive edge, independent of the port
+        via.write(PCR, 0x06);
+        via.set_ca2(true);
+        assert_eq!(flags(&via) & IRQ_CA2, IRQ_CA2);
+        via.read(ORA);
+        assert_eq!(flags(&via) & IRQ_CA2, IRQ_CA2);
+        via.write(IFR, IRQ_CA2);
+        assert_eq!(flags(&via), 0x00);
+    }
+
+    #[test]
+    fn test_via_interrupt_enable() {
+        let line = IrqLine::new();
+        let mut via = Via::new();
+        via.connect_irq(&line);
+
+        assert_eq!(via.read(IER), Some(0x80));
+        via.write(IER, 0x80 | IRQ_CA1 | IRQ_CB1);
+        assert_eq!(via.read(IER), Some(0x80 | IRQ_CA1 | IRQ_CB1));
+        via.write(IER, IRQ_CB1);
+        assert_eq!(via.read(IER), Some(0x80 | IRQ_CA1));
+
+        via.set_cb1(false);
+        assert_eq!(flags(&via), IRQ_CB1);
+        assert!(!line.asserted());
+        via.set_ca1(false);
+        assert_eq!(flags(&via), IRQ_ANY | IRQ_CA1 | IRQ_CB1);
+        assert!(line.asserted());
+
+        via.write(IER, IRQ_CA1);
+        assert_eq!(flags(&via), IRQ_CA1 | IRQ_CB1);
+        assert!(!line.asserted());
+
+        via.write(IER, 0x80 | IRQ_CB1);
+        assert!(line.asserted());
+        via.reset();
+        assert_eq!(flags(&via), 0x00);
+        assert!(!line.asserted());
+    }
+
+    #[test]
+    fn test_via_interrupts_cpu() {
+        let mut rom = vec![0xEA; 0x1000];
+        rom[..0x13].copy_from_slice(&[
+            0xA9, 0xC0, // LDA #$C0
+            0x8D, 0x0E, 0xD0, // STA $D00E
+            0xA9, 0x10, // LDA #$10
+            0x8D, 0x04, 0xD0, // STA $D004
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x05, 0xD0, // STA $D005
+            0x58, // CLI
+            0x4C, 0x10, 0xF0, // JMP $F010
+        ]);
+        rom[0x100..0x104].copy_from_slice(&[
+            0xAD, 0x04, 0xD0, // LDA $D004
+            0x40, // RTI
+        ]);
+        rom[0xFFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF1]);
+
+        let via = Arc::new(Mutex::new(Via::new()));
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x1FFF)
+            .device(0xD000..=0xD00F, via.clone())
+            .rom(0xF000..=0xFFFF, &rom)
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::with_bus(map);
+        cpu.reset_with(ResetKind::PowerOn);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+
+        for _ in 0..100 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+            if cpu.pc() == 0xF100 {
+                break;
+            }
+        }
+        assert_eq!(cpu.pc(), 0xF100);
+        assert_eq!(cpu.irq_sources_asserting(), vec!["6522".to_string()]);
+
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert!(cpu.irq_sources_asserting().is_empty());
+        assert_eq!(via.lock().unwrap().peek(IFR), Some(0x00));
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        assert_eq!(cpu.pc(), 0xF010);
+    }
+}
@@ -0,0 +1,227 @@
+#[cfg(test)]
+mod test {
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+    use crate::mos6532::*;
+    use std::sync::{Arc, Mutex};
+
+    fn ticks(riot: &mut Riot, n: usize) {
+        for _ in 0..n {
+            riot.tick();
+        }
+    }
+
+    /// Writes a register and ticks the cycle of the write, as a cpu would
+    fn write(riot: &mut Riot, offset: Address, data: Byte) {
+        riot.write(offset, data);
+        riot.tick();
+    }
+
+    #[test]
+    fn test_riot_ram() {
+        let mut riot = Riot::new();
+        for offset in 0x00..0x80 {
+            riot.write(offset, offset as Byte ^ 0x5A);
+        }
+        for offset in 0x00..0x80 {
+            assert_eq!(riot.read(offset), Some(offset as Byte ^ 0x5A));
+        }
+        riot.write(0x81, 0xFF);
+        assert_eq!(riot.read(0x01), Some(0x5B));
+    }
+
+    #[test]
+    fn test_riot_ports() {
+        let mut riot = Riot::new();
+        riot.set_port_a(0x0F);
+        riot.set_port_b(0x81);
+        assert_eq!(riot.read(0x80), Some(0x0F));
+        assert_eq!(riot.read(0x82), Some(0x81));
+
+        riot.write(0x81, 0xF0);
+        riot.write(0x80, 0xA5);
+        riot.write(0x83, 0xFF);
+        riot.write(0x82, 0x42);
+        assert_eq!(riot.read(0x80), Some(0xAF));
+        assert_eq!(riot.port_a(), 0xAF);
+        assert_eq!(riot.read(0x81), Some(0xF0));
+        assert_eq!(riot.read(0x82), Some(0x42));
+        assert_eq!(riot.port_b(), 0x42);
+        assert_eq!(riot.read(0x83), Some(0xFF));
+
+        riot.reset();
+        assert_eq!(riot.port_a(), 0x0F);
+        assert_eq!(riot.port_b(), 0x81);
+    }
+
+    #[test]
+    fn test_riot_timer_prescalers() {
+        for (offset, interval) in [(0x94, 1), (0x95, 8), (0x96, 64), (0x97, 1024)].iter() {
+            let mut riot = Riot::new();
+            write(&mut riot, *offset, 3);
+
+            // Decremented on the next cycle, then once per interval
+            riot.tick();
+            assert_eq!(riot.timer(), 2);
+            ticks(&mut riot, interval - 1);
+            assert_eq!(riot.timer(), 2);
+            riot.tick();
+            assert_eq!(riot.timer(), 1);
+
+            ticks(&mut riot, 2 * interval - 1);
+            assert_eq!(riot.timer(), 0);
+            assert_eq!(riot.flags(), 0x00);
+            riot.tick();
+            assert_eq!(riot.timer(), 0xFF);
+            assert_eq!(riot.flags(), FLAG_TIMER);
+        }
+    }
+
+    #[test]
+    fn test_riot_timer_underflow() {
+        let mut riot = Riot::new();
+        write(&mut riot, 0x96, 1);
+        ticks(&mut riot, 64);
+        assert_eq!(riot.read(0x84), Some(0x00));
+        riot.tick();
+        assert_eq!(riot.peek(0x85), Some(FLAG_TIMER));
+
+        // After the underflow every cycle counts
+        riot.tick();
+        assert_eq!(riot.timer(), 0xFE);
+        ticks(&mut riot, 0xFE);
+        assert_eq!(riot.timer(), 0x00);
+
+        // Reading the timer clears the flag, which is set on each underflow
+        assert_eq!(riot.read(0x84), Some(0x00));
+        assert_eq!(riot.flags(), 0x00);
+        riot.tick();
+        assert_eq!(riot.flags(), FLAG_TIMER);
+
+        // Writing it restores the interval
+        write(&mut riot, 0x95, 2);
+        assert_eq!(riot.flags(), 0x00);
+        ticks(&mut riot, 2);
+        assert_eq!(riot.timer(), 1);
+    }
+
+    #[test]
+    fn test_riot_timer_irq() {
+        let line = IrqLine::new();
+        let mut riot = Riot::new();
+        riot.connect_irq(&line);
+
+        write(&mut riot, 0x9D, 2);
+        ticks(&mut riot, 16);
+        assert!(!line.asserted());
+        riot.tick();
+        assert_eq!(line.asserting(), vec!["6532".to_string()]);
+
+        // Reading the flags leaves the timer flag alone
+        assert_eq!(riot.read(0x85), Some(FLAG_TIMER));
+        assert!(line.asserted());
+
+        // Reading the timer with A3 set keeps the IRQ enabled
+        riot.read(0x8C);
+        assert!(!line.asserted());
+        ticks(&mut riot, 0xFF);
+        assert!(!line.asserted());
+        riot.tick();
+        assert!(line.asserted());
+        riot.read(0x84);
+        ticks(&mut riot, 0x100);
+        assert_eq!(riot.flags(), FLAG_TIMER);
+        assert!(!line.asserted());
+
+        // Without its IRQ enabled, the timer only sets the flag
+        write(&mut riot, 0x94, 0);
+        riot.tick();
+        assert_eq!(riot.flags(), FLAG_TIMER);
+        assert!(!line.asserted());
+    }
+
+    #[test]
+    fn test_riot_pa7() {
+        let line = IrqLine::new();
+        let mut riot = Riot::new();
+        riot.connect_irq(&line);
+
+        // A negative edge by default, without an IRQ
+        riot.set_port_a(0x7F);
+        assert_eq!(riot.flags(), FLAG_PA7);
+        assert!(!line.asserted());
+        assert_eq!(riot.read(0x85), Some(FLAG_PA7));
+        assert_eq!(riot.flags(), 0x00);
+        riot.set_port_a(0xFF);
+        assert_eq!(riot.flags(), 0x00);
+
+        // A positive edge with an IRQ
+        riot.write(0x87, 0x00);
+        riot.set_port_a(0x00);
+        assert_eq!(riot.flags(), 0x00);
+        riot.set_port_a(0x80);
+        assert_eq!(riot.flags(), FLAG_PA7);
+        assert!(line.asserted());
+        riot.read(0x85);
+        assert!(!line.asserted());
+
+        // The pin may be driven by the port itself
+        riot.write(0x80, 0x00);
+        riot.write(0x81, 0x80);
+        riot.write(0x80, 0x80);
+        assert_eq!(riot.flags(), FLAG_PA7);
+        assert!(line.asserted());
+
+        // Disabling the IRQ releases the line, not the flag
+        riot.write(0x85, 0x00);
+        assert!(!line.asserted());
+        assert_eq!(riot.flags(), FLAG_PA7);
+    }
+
+    #[test]
+    fn test_riot_interrupts_cpu() {
+        let mut rom = vec![0xEA; 0x1000];
+        rom[..0x09].copy_from_slice(&[
+            0xA9, 0x02, // LDA #$02
+            0x8D, 0x9D, 0x02, // STA $029D
+            0x58, // CLI
+            0x4C, 0x06, 0xF0, // JMP $F006
+        ]);
+        rom[0x100..0x109].copy_from_slice(&[
+            0xAD, 0x85, 0x02, // LDA $0285
+            0x85, 0x81, // STA $81
+            0xAD, 0x84, 0x02, // LDA $0284
+            0x40, // RTI
+        ]);
+        rom[0xFFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF1]);
+
+        // Wired like in the Atari 2600, with the RAM (and the stack) at 0x80
+        let riot = Arc::new(Mutex::new(Riot::new()));
+        let map = MemoryMap::builder()
+            .mirror(0x0080..=0x00FF, 0x0200)
+            .mirror(0x0180..=0x01FF, 0x0200)
+            .device(0x0200..=0x02FF, riot.clone())
+            .rom(0xF000..=0xFFFF, &rom)
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::with_bus(map);
+        cpu.reset_with(ResetKind::PowerOn);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+        let start = cpu.time().elapsed();
+
+        while cpu.pc() != 0xF100 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+            assert!(cpu.time().elapsed() - start < 100);
+        }
+        assert_eq!(cpu.irq_sources_asserting(), vec!["6532".to_string()]);
+
+        for _ in 0..4 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+        }
+        assert_eq!(cpu.pc(), 0xF006);
+        assert_eq!(cpu.read_byte(0x0081), FLAG_TIMER);
+        assert_eq!(riot.lock().unwrap().peek(0x01), Some(FLAG_TIMER));
+        assert!(cpu.irq_sources_asserting().is_empty());
+    }
+}
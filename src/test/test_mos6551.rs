@@ -0,0 +1,267 @@
+#[cfg(test)]
+mod test {
+    use crate::memory_map::*;
+    use crate::mos6502::*;
+    use crate::mos6551::*;
+    use std::sync::{Arc, Mutex};
+
+    /// 19200 baud, 8 data bits, 1 stop bit, on a 1 MHz clock
+    const CONTROL_19200_8N1: Byte = 0x1F;
+    const CHAR_CYCLES: u64 = 521;
+
+    /// An ACIA set up for 19200 8N1, with the receiver interrupt
+    /// enabled and the transmitter on
+    fn acia() -> Acia<ByteQueue> {
+        let mut acia = Acia::new(ByteQueue::new(), 1_000_000);
+        acia.write(CONTROL, CONTROL_19200_8N1);
+        acia.write(COMMAND, 0x09);
+        acia
+    }
+
+    fn ticks(acia: &mut Acia<ByteQueue>, n: u64) {
+        for _ in 0..n {
+            acia.tick();
+        }
+    }
+
+    #[test]
+    fn test_acia_timing() {
+        let mut acia = Acia::new(ByteQueue::new(), 1_000_000);
+        assert_eq!(acia.baud(), 115_200.0);
+        assert_eq!(acia.frame_bits(), 10);
+        assert_eq!(acia.char_cycles(), 87);
+
+        acia.write(CONTROL, CONTROL_19200_8N1);
+        assert_eq!(acia.char_cycles(), CHAR_CYCLES);
+
+        // 300 baud, 7 data bits, parity, 2 stop bits
+        acia.write(CONTROL, 0xA6);
+        acia.write(COMMAND, 0x2B);
+        assert_eq!(acia.frame_bits(), 11);
+        assert_eq!(acia.char_cycles(), 36_667);
+
+        // 8 data bits with parity have a single stop bit
+        acia.write(CONTROL, 0x86);
+        assert_eq!(acia.frame_bits(), 11);
+    }
+
+    #[test]
+    fn test_acia_transmit() {
+        let mut acia = acia();
+        assert_eq!(acia.read(STATUS), Some(STATUS_TDRE));
+
+        acia.write(DATA, b'H');
+        assert_eq!(acia.read(STATUS), Some(0x00));
+
+        // Moved into the shift register right away
+        acia.tick();
+        assert_eq!(acia.read(STATUS), Some(STATUS_TDRE));
+        acia.write(DATA, b'i');
+        ticks(&mut acia, CHAR_CYCLES - 1);
+        assert!(acia.backend().output().is_empty());
+
+        acia.tick();
+        assert_eq!(acia.backend().output(), b"H");
+        ticks(&mut acia, CHAR_CYCLES);
+        assert_eq!(acia.backend_mut().take_output(), b"Hi".to_vec());
+        assert!(acia.backend().output().is_empty());
+    }
+
+    #[test]
+    fn test_acia_transmitter_off() {
+        let mut acia = acia();
+        acia.write(COMMAND, 0x01);
+        acia.write(DATA, b'X');
+        ticks(&mut acia, 2 * CHAR_CYCLES);
+        assert!(acia.backend().output().is_empty());
+        assert_eq!(acia.read(STATUS), Some(0x00));
+
+        acia.write(COMMAND, 0x09);
+        ticks(&mut acia, CHAR_CYCLES + 1);
+        assert_eq!(acia.backend().output(), b"X");
+    }
+
+    #[test]
+    fn test_acia_receive() {
+        let line = IrqLine::new();
+        let mut acia = acia();
+        acia.connect_irq(&line);
+        acia.backend_mut().push(b"OK");
+
+        ticks(&mut acia, CHAR_CYCLES - 1);
+        assert_eq!(acia.read(STATUS), Some(STATUS_TDRE));
+        acia.tick();
+        assert!(line.asserted());
+        assert_eq!(
+            acia.peek(STATUS),
+            Some(STATUS_IRQ | STATUS_TDRE | STATUS_RDRF)
+        );
+
+        // Reading the status releases the IRQ, reading the data the RDRF
+        assert_eq!(
+            acia.read(STATUS),
+            Some(STATUS_IRQ | STATUS_TDRE | STATUS_RDRF)
+        );
+        assert!(!line.asserted());
+        assert_eq!(acia.read(DATA), Some(b'O'));
+        assert_eq!(acia.read(STATUS), Some(STATUS_TDRE));
+
+        ticks(&mut acia, CHAR_CYCLES);
+        assert_eq!(acia.read(DATA), Some(b'K'));
+        assert_eq!(acia.backend().pending(), 0);
+    }
+
+    #[test]
+    fn test_acia_overrun() {
+        let mut acia = acia();
+        acia.backend_mut().push(b"ABC");
+        ticks(&mut acia, 3 * CHAR_CYCLES);
+        assert_eq!(
+            acia.read(STATUS),
+            Some(STATUS_IRQ | STATUS_TDRE | STATUS_RDRF | STATUS_OVERRUN)
+        );
+        assert_eq!(acia.read(DATA), Some(b'A'));
+        assert_eq!(acia.read(STATUS), Some(STATUS_TDRE));
+    }
+
+    #[test]
+    fn test_acia_word_length() {
+        let mut acia = acia();
+        // 7 data bits
+        acia.write(CONTROL, 0x3F);
+        acia.backend_mut().push(&[0xC1]);
+        acia.write(DATA, 0xFF);
+        let cycles = acia.char_cycles();
+        ticks(&mut acia, 2 * cycles);
+        assert_eq!(acia.read(DATA), Some(0x41));
+        assert_eq!(acia.backend().output(), &[0x7F]);
+    }
+
+    #[test]
+    fn test_acia_echo() {
+        let mut acia = acia();
+        acia.write(COMMAND, 0x11);
+        acia.backend_mut().push(b"e");
+        ticks(&mut acia, CHAR_CYCLES);
+        assert_eq!(acia.backend().output(), b"e");
+        assert_eq!(acia.read(DATA), Some(b'e'));
+    }
+
+    #[test]
+    fn test_acia_interrupts() {
+        let line = IrqLine::new();
+        let mut acia = acia();
+        acia.connect_irq(&line);
+
+        // Receiver interrupts disabled, transmitter ones enabled
+        acia.write(COMMAND, 0x07);
+        assert!(line.asserted());
+        assert_eq!(acia.read(STATUS), Some(STATUS_IRQ | STATUS_TDRE));
+        assert!(!line.asserted());
+
+        acia.write(DATA, b'!');
+        acia.tick();
+        assert!(line.asserted());
+        acia.read(STATUS);
+        acia.backend_mut().push(b"?");
+        ticks(&mut acia, CHAR_CYCLES);
+        assert!(!line.asserted());
+
+        // Not ready, no interrupts at all
+        acia.write(COMMAND, 0x04);
+        acia.write(DATA, b'!');
+        ticks(&mut acia, 2 * CHAR_CYCLES);
+        assert!(!line.asserted());
+
+        // The programmed reset
+        acia.write(COMMAND, 0xE7);
+        assert!(line.asserted());
+        acia.write(STATUS, 0x00);
+        assert!(!line.asserted());
+        assert_eq!(acia.read(COMMAND), Some(0xE0));
+        assert_eq!(acia.read(CONTROL), Some(CONTROL_19200_8N1));
+
+        acia.reset();
+        assert_eq!(acia.read(COMMAND), Some(0x02));
+        assert_eq!(acia.read(CONTROL), Some(0x00));
+    }
+
+    #[test]
+    fn test_acia_echoes_cpu() {
+        let mut rom = vec![0xEA; 0x1000];
+        rom[..0x19].copy_from_slice(&[
+            0xA9, 0x1F, // LDA #$1F
+            0x8D, 0x03, 0xD0, // STA $D003
+            0xA9, 0x0B, // LDA #$0B
+            0x8D, 0x02, 0xD0, // STA $D002
+            0xAD, 0x01, 0xD0, // LDA $D001
+            0x29, 0x08, // AND #$08
+            0xF0, 0xF9, // BEQ $F00A
+            0xAD, 0x00, 0xD0, // LDA $D000
+            0x8D, 0x00, 0xD0, // STA $D000
+            0x4C, 0x0A, // JMP $F00A
+        ]);
+        rom[0x19] = 0xF0;
+        rom[0xFFC..].copy_from_slice(&[0x00, 0xF0, 0x00, 0xF0]);
+
+        let acia = Arc::new(Mutex::new(Acia::new(ByteQueue::new(), 1_000_000)));
+        let map = MemoryMap::builder()
+            .ram(0x0000..=0x1FFF)
+            .device(0xD000..=0xD003, acia.clone())
+            .rom(0xF000..=0xFFFF, &rom)
+            .build()
+            .unwrap();
+
+        let mut cpu = Cpu::with_bus(map);
+        cpu.reset_with(ResetKind::PowerOn);
+        assert_eq!(cpu.full_instruction(), Ok(()));
+
+        acia.lock().unwrap().backend_mut().push(b"6502");
+        while acia.lock().unwrap().backend().output().len() < 4 {
+            assert_eq!(cpu.full_instruction(), Ok(()));
+            assert!(cpu.time().elapsed() < 10 * CHAR_CYCLES);
+        }
+        assert_eq!(acia.lock().unwrap().backend().output(), b"6502");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_acia_pty() {
+        use std::io::{Read, Write};
+
+        // Not every sandbox has pseudo-terminals
+        let pty = match Pty::open() {
+            Ok(pty) => pty,
+            Err(_) => return,
+        };
+        assert!(pty.name().starts_with("/dev/"));
+        let mut slave = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(pty.name())
+            .unwrap();
+
+        let mut acia = Acia::new(pty, 1_000_000);
+        acia.write(CONTROL, CONTROL_19200_8N1);
+        acia.write(COMMAND, 0x0B);
+
+        slave.write_all(b"Z").unwrap();
+        let mut cycles = 0;
+        while acia.peek(STATUS).unwrap() & STATUS_RDRF == 0 {
+            acia.tick();
+            cycles += 1;
+            assert!(cycles < 1_000_000);
+        }
+        assert_eq!(acia.read(DATA), Some(b'Z'));
+
+        for &data in b"ok\n" {
+            acia.write(DATA, data);
+            for _ in 0..=CHAR_CYCLES {
+                acia.tick();
+            }
+        }
+        let mut line = [0; 3];
+        slave.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"ok\n");
+    }
+}